@@ -7,8 +7,9 @@ use anyhow::Context as _;
 use librad::git::Urn;
 
 use radicle_common::args::{Args, Error, Help};
+use radicle_common::petname::Petnames;
 use radicle_common::Interactive;
-use radicle_common::{fmt, keys, profile, project};
+use radicle_common::{keys, profile, project};
 use radicle_terminal as term;
 
 pub const HELP: Help = Help {
@@ -154,6 +155,8 @@ pub fn execute(options: Options, profile: &profile::Profile) -> anyhow::Result<P
         fetch: true,
         upstream: true,
     };
+    let petnames = Petnames::open(profile)?;
+
     for peer in &project.remotes {
         if peer == storage.peer_id() {
             continue;
@@ -170,7 +173,7 @@ pub fn execute(options: Options, profile: &profile::Profile) -> anyhow::Result<P
             term::success!(
                 "Remote-tracking branch {} created for {}",
                 term::format::highlight(&branch),
-                term::format::tertiary(fmt::peer(peer))
+                term::format::tertiary(petnames.peer(peer))
             );
         }
     }
@@ -0,0 +1,329 @@
+use std::env;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Context as _};
+
+use radicle_common as common;
+use radicle_common::args::{Args, Error, Help};
+use radicle_common::ci::{self, Config};
+use radicle_common::cobs::patch::{CheckStatus, Patch, RevisionIx};
+use radicle_common::{cobs, git, keys, project};
+use radicle_terminal as term;
+
+/// Number of characters of job output shown next to the spinner.
+pub const PROGRESS_WIDTH: usize = 60;
+
+pub const HELP: Help = Help {
+    name: "ci",
+    description: env!("CARGO_PKG_DESCRIPTION"),
+    version: env!("CARGO_PKG_VERSION"),
+    usage: r#"
+Usage
+
+    rad ci run [<patch-id>] [<option>...]
+
+    Runs the jobs defined in the `.radicle/ci.toml` file of a patch
+    revision, or of `HEAD` if no patch is specified. The revision is
+    checked out in a temporary worktree, and its own jobs are run there.
+
+    When running against a patch, the result of each job is attached to
+    the revision as a check, which can be published with `rad push`.
+
+    Example `.radicle/ci.toml`:
+
+        [jobs.build]
+        command = "cargo build"
+
+        [jobs.test]
+        command = "cargo test"
+        timeout = 1200  # In seconds, defaults to 600
+
+Options
+
+    -r, --revision <number>   Revision number to run against, defaults to the latest
+        --job <name>          Only run the given job
+        --no-record           Don't attach the results to the patch
+        --help                Print help
+"#,
+};
+
+#[derive(Debug)]
+pub struct Options {
+    pub id: Option<cobs::Identifier>,
+    pub revision: Option<RevisionIx>,
+    pub jobs: Vec<String>,
+    pub record: bool,
+}
+
+impl Args for Options {
+    fn from_args(args: Vec<OsString>) -> anyhow::Result<(Self, Vec<OsString>)> {
+        use lexopt::prelude::*;
+
+        let mut parser = lexopt::Parser::from_args(args);
+        let mut op: Option<String> = None;
+        let mut id: Option<cobs::Identifier> = None;
+        let mut revision: Option<RevisionIx> = None;
+        let mut jobs = Vec::new();
+        let mut record = true;
+
+        while let Some(arg) = parser.next()? {
+            match arg {
+                Long("revision") | Short('r') => {
+                    let value = parser.value()?;
+                    let ix =
+                        RevisionIx::from_str(value.to_str().unwrap_or_default()).map_err(|_| {
+                            anyhow!("invalid revision number `{}`", value.to_string_lossy())
+                        })?;
+                    revision = Some(ix);
+                }
+                Long("job") => {
+                    jobs.push(parser.value()?.to_string_lossy().into());
+                }
+                Long("no-record") => {
+                    record = false;
+                }
+                Long("help") => {
+                    return Err(Error::Help.into());
+                }
+                Value(val) if op.is_none() => match val.to_string_lossy().as_ref() {
+                    "run" => op = Some(String::from("run")),
+                    unknown => anyhow::bail!("unknown operation '{}'", unknown),
+                },
+                Value(val) if id.is_none() => {
+                    let val = val
+                        .to_str()
+                        .ok_or_else(|| anyhow!("patch id specified is not UTF-8"))?;
+
+                    id = Some(
+                        cobs::Identifier::from_str(val)
+                            .map_err(|_| anyhow!("invalid patch id '{}'", val))?,
+                    );
+                }
+                _ => return Err(anyhow!(arg.unexpected())),
+            }
+        }
+
+        if op.is_none() {
+            anyhow::bail!("an operation must be specified, see `rad ci --help`");
+        }
+        if id.is_none() && revision.is_some() {
+            anyhow::bail!("a revision can only be specified along with a patch id");
+        }
+
+        Ok((
+            Options {
+                id,
+                revision,
+                jobs,
+                record,
+            },
+            vec![],
+        ))
+    }
+}
+
+pub fn run(options: Options, ctx: impl term::Context) -> anyhow::Result<()> {
+    let (urn, repo) = project::cwd()
+        .map_err(|_| anyhow!("this command must be run in the context of a project"))?;
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| anyhow!("cannot run CI jobs in a bare repository"))?
+        .to_path_buf();
+    let profile = ctx.profile()?;
+    let signer = term::signer(&profile)?;
+    let storage = keys::storage(&profile, signer)?;
+    let cobs = cobs::store(&profile, &storage)?;
+    let patches = cobs.patches();
+
+    let (target, oid, description) = match &options.id {
+        Some(id) => {
+            let (patch_id, patch) = patches
+                .resolve::<Patch>(&urn, id)?
+                .ok_or_else(|| anyhow!("couldn't find patch {} locally", id))?;
+            let revision_ix = options.revision.unwrap_or_else(|| patch.version());
+            let revision = patch
+                .revisions
+                .get(revision_ix)
+                .ok_or_else(|| anyhow!("revision R{} does not exist", revision_ix))?;
+            let description = format!(
+                "{} {} ({})",
+                term::format::tertiary(common::fmt::cob(&patch_id)),
+                term::format::dim(format!("R{}", revision_ix)),
+                term::format::secondary(common::fmt::oid(&revision.oid)),
+            );
+
+            (Some((patch_id, revision_ix)), *revision.oid, description)
+        }
+        None => {
+            let head = repo
+                .head()?
+                .target()
+                .ok_or_else(|| anyhow!("invalid HEAD ref; aborting"))?;
+            let description = format!(
+                "HEAD ({})",
+                term::format::secondary(common::fmt::oid(&head)),
+            );
+
+            (None, head, description)
+        }
+    };
+    repo.find_commit(oid)
+        .context("patch head not found in local repository")?;
+
+    // Jobs are defined by the revision under test, not by the working copy.
+    let worktree = Worktree::add(&workdir, oid)?;
+    let config = Config::load(worktree.path()).map_err(|err| common::Error::WithHint {
+        err: anyhow!(
+            "couldn't load {} at {}: {}",
+            ci::CONFIG_PATH,
+            common::fmt::oid(&oid),
+            err
+        ),
+        hint: "hint: jobs are defined in `.radicle/ci.toml`, see `rad ci --help`",
+    })?;
+
+    let mut jobs = config.jobs.into_iter().collect::<Vec<_>>();
+    if !options.jobs.is_empty() {
+        for name in &options.jobs {
+            if !jobs.iter().any(|(n, _)| n == name) {
+                anyhow::bail!("job '{}' is not defined in {}", name, ci::CONFIG_PATH);
+            }
+        }
+        jobs.retain(|(n, _)| options.jobs.contains(n));
+    }
+    if jobs.is_empty() {
+        anyhow::bail!("no jobs defined in {}", ci::CONFIG_PATH);
+    }
+
+    term::info!("Running {} job(s) against {}", jobs.len(), description);
+    term::blank();
+
+    let mut reports = Vec::new();
+
+    for (name, job) in &jobs {
+        let mut spinner = term::spinner(format!("{}...", term::format::highlight(name)));
+        let report = ci::run(name, job, worktree.path(), |line| {
+            let line = line.chars().take(PROGRESS_WIDTH).collect::<String>();
+            spinner.message(format!(
+                "{} {}",
+                term::format::highlight(name),
+                term::format::dim(line)
+            ));
+        })
+        .with_context(|| format!("failed to run job '{}'", name))?;
+
+        spinner.message(format!(
+            "{} {} {}",
+            term::format::highlight(name),
+            report.outcome,
+            term::format::dim(format!("({}s)", report.duration.as_secs()))
+        ));
+        if report.status() == CheckStatus::Pass {
+            spinner.finish();
+        } else {
+            spinner.failed();
+
+            for line in &report.tail {
+                term::indented(&term::format::dim(line));
+            }
+        }
+        reports.push(report);
+    }
+    drop(worktree);
+
+    if let (Some((patch_id, revision_ix)), true) = (target, options.record) {
+        for report in &reports {
+            patches.check(
+                &urn,
+                &patch_id,
+                revision_ix,
+                &report.name,
+                report.status(),
+                report.summary(),
+            )?;
+        }
+        term::blank();
+        term::success!(
+            "Check results attached to {} {}, use {} to publish",
+            term::format::tertiary(common::fmt::cob(&patch_id)),
+            term::format::dim(format!("R{}", revision_ix)),
+            term::format::secondary("`rad push`")
+        );
+    }
+
+    let failed = reports
+        .iter()
+        .filter(|r| r.status() == CheckStatus::Fail)
+        .count();
+    if failed > 0 {
+        anyhow::bail!("{} of {} job(s) failed", failed, reports.len());
+    }
+
+    Ok(())
+}
+
+/// A temporary git worktree, removed when dropped.
+struct Worktree {
+    repo: PathBuf,
+    path: PathBuf,
+}
+
+impl Worktree {
+    fn add(repo: &Path, oid: git::Oid) -> anyhow::Result<Self> {
+        // Runs can happen concurrently, even against the same revision, so every
+        // run gets its own worktree.
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos());
+        let name = format!("{}-{}-{}", oid, process::id(), nanos);
+        let path = env::temp_dir().join("rad-ci").join(name);
+        let worktree = Self {
+            repo: repo.to_path_buf(),
+            path,
+        };
+        // Forget about worktrees of interrupted runs.
+        git::git(repo, ["worktree", "prune"]).ok();
+
+        git::git(
+            repo,
+            [
+                "worktree",
+                "add",
+                "--detach",
+                &worktree.path.to_string_lossy(),
+                &oid.to_string(),
+            ],
+        )
+        .context("failed to create worktree")?;
+
+        Ok(worktree)
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn remove(&self) {
+        git::git(
+            &self.repo,
+            [
+                "worktree",
+                "remove",
+                "--force",
+                &self.path.to_string_lossy(),
+            ],
+        )
+        .ok();
+        git::git(&self.repo, ["worktree", "prune"]).ok();
+    }
+}
+
+impl Drop for Worktree {
+    fn drop(&mut self) {
+        self.remove();
+    }
+}
@@ -3,7 +3,8 @@ use std::{io::ErrorKind, iter, process};
 
 use anyhow::anyhow;
 use rad_help::*;
-use radicle_common::profile;
+use radicle_common::sync::queue::{self, Queue};
+use radicle_common::{keys, profile, tokio};
 use radicle_terminal as term;
 
 pub const NAME: &str = "rad";
@@ -100,7 +101,42 @@ fn run(command: Command) -> Result<(), Option<anyhow::Error>> {
     Ok(())
 }
 
+/// Retry the queued pushes that are due. This is only done when a signer is
+/// available without prompting the user, ie. via `ssh-agent`, and when the output
+/// goes to a terminal, so that scripts never see it.
+fn retry_queued_pushes() {
+    if !atty::is(atty::Stream::Stdout) {
+        return;
+    }
+    let profile = match profile::default() {
+        Ok(profile) => profile,
+        Err(_) => return,
+    };
+    match Queue::open(&profile) {
+        Ok(queue) if queue.due(queue::now()).next().is_some() => {}
+        _ => return,
+    }
+    let sock = match keys::ssh_auth_sock() {
+        Ok(sock) => sock,
+        Err(_) => return,
+    };
+    let rt = match tokio::runtime::Runtime::new() {
+        Ok(rt) => rt,
+        Err(_) => return,
+    };
+    if let Err(err) = term::sync::flush(&profile, sock, &rt, false) {
+        term::warning(&format!("Failed to retry queued pushes: {}", err));
+    }
+}
+
 fn run_other(exe: &str, args: &[OsString]) -> Result<(), Option<anyhow::Error>> {
+    // Queued pushes are retried before commands that talk to seeds anyway, so that
+    // other commands aren't slowed down by the network. `rad sync --flush` retries
+    // them on demand.
+    if matches!(exe, "clone" | "pull" | "push" | "track") {
+        retry_queued_pushes();
+    }
+
     match exe {
         #[cfg(feature = "ethereum")]
         "account" => {
@@ -127,6 +163,14 @@ fn run_other(exe: &str, args: &[OsString]) -> Result<(), Option<anyhow::Error>>
                 args.to_vec(),
             );
         }
+        "ci" => {
+            term::run_command_args::<rad_ci::Options, _>(
+                rad_ci::HELP,
+                "Ci",
+                rad_ci::run,
+                args.to_vec(),
+            );
+        }
         "clone" => {
             term::run_command_args::<rad_clone::Options, _>(
                 rad_clone::HELP,
@@ -135,6 +179,14 @@ fn run_other(exe: &str, args: &[OsString]) -> Result<(), Option<anyhow::Error>>
                 args.to_vec(),
             );
         }
+        "cob" => {
+            term::run_command_args::<rad_cob::Options, _>(
+                rad_cob::HELP,
+                "Cob",
+                rad_cob::run,
+                args.to_vec(),
+            );
+        }
         "comment" => {
             term::run_command_args::<rad_comment::Options, _>(
                 rad_comment::HELP,
@@ -177,6 +229,14 @@ fn run_other(exe: &str, args: &[OsString]) -> Result<(), Option<anyhow::Error>>
                 args.to_vec(),
             );
         }
+        "inbox" => {
+            term::run_command_args::<rad_inbox::Options, _>(
+                rad_inbox::HELP,
+                "Inbox",
+                rad_inbox::run,
+                args.to_vec(),
+            );
+        }
         "init" => {
             term::run_command_args::<rad_init::Options, _>(
                 rad_init::HELP,
@@ -233,6 +293,14 @@ fn run_other(exe: &str, args: &[OsString]) -> Result<(), Option<anyhow::Error>>
                 args.to_vec(),
             );
         }
+        "peer" => {
+            term::run_command_args::<rad_peer::Options, _>(
+                rad_peer::HELP,
+                "Peer",
+                rad_peer::run,
+                args.to_vec(),
+            );
+        }
         "pull" => {
             term::run_command_args::<rad_pull::Options, _>(
                 rad_pull::HELP,
@@ -273,6 +341,22 @@ fn run_other(exe: &str, args: &[OsString]) -> Result<(), Option<anyhow::Error>>
                 args.to_vec(),
             );
         }
+        "search" => {
+            term::run_command_args::<rad_search::Options, _>(
+                rad_search::HELP,
+                "Search",
+                rad_search::run,
+                args.to_vec(),
+            );
+        }
+        "seed" => {
+            term::run_command_args::<rad_seed::Options, _>(
+                rad_seed::HELP,
+                "Seed",
+                rad_seed::run,
+                args.to_vec(),
+            );
+        }
         "self" => {
             term::run_command_args::<rad_self::Options, _>(
                 rad_self::HELP,
@@ -281,6 +365,14 @@ fn run_other(exe: &str, args: &[OsString]) -> Result<(), Option<anyhow::Error>>
                 args.to_vec(),
             );
         }
+        "show" => {
+            term::run_command_args::<rad_show::Options, _>(
+                rad_show::HELP,
+                "Show",
+                rad_show::run,
+                args.to_vec(),
+            );
+        }
         "sync" => {
             term::run_command_args::<rad_sync::Options, _>(
                 rad_sync::HELP,
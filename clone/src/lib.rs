@@ -10,6 +10,7 @@ use url::Url;
 
 use radicle_common::args::{Args, Error, Help};
 use radicle_common::seed;
+use radicle_common::sync::http::Transport;
 use radicle_common::Interactive;
 use radicle_common::{git, identity, keys, profile, project, sync};
 use radicle_terminal as term;
@@ -27,6 +28,7 @@ Options
 
     --no-confirm    Don't ask for confirmation during clone
     --seed <addr>   Seed to clone from
+    --transport <t> Transport to fetch with: `auto`, `link` or `https` (default: auto)
     --help          Print help
 
 "#,
@@ -42,6 +44,7 @@ enum Origin {
 pub struct Options {
     origin: Origin,
     interactive: Interactive,
+    transport: Transport,
 }
 
 impl Args for Options {
@@ -52,12 +55,19 @@ impl Args for Options {
         let mut origin: Option<Origin> = None;
         let mut interactive = Interactive::Yes;
         let mut seed = None;
+        let mut transport = Transport::default();
 
         while let Some(arg) = parser.next()? {
             match arg {
                 Long("seed") if seed.is_none() => {
                     seed = Some(seed::parse_value(&mut parser)?);
                 }
+                Long("transport") => {
+                    let value = parser.value()?;
+                    let value = value.to_string_lossy();
+
+                    transport = value.parse().map_err(|e: String| anyhow!(e))?;
+                }
                 Long("no-confirm") => {
                     interactive = Interactive::No;
                 }
@@ -106,6 +116,7 @@ impl Args for Options {
             Options {
                 origin,
                 interactive,
+                transport,
             },
             vec![],
         ))
@@ -115,7 +126,13 @@ impl Args for Options {
 pub fn run(options: Options, ctx: impl term::Context) -> anyhow::Result<()> {
     match options.origin {
         Origin::Radicle(origin) => {
-            clone_project(origin.urn, origin.seed, options.interactive, ctx)?;
+            clone_project(
+                origin.urn,
+                origin.seed,
+                options.interactive,
+                options.transport,
+                ctx,
+            )?;
         }
         Origin::Git(url) => {
             let profile = ctx.profile()?;
@@ -129,6 +146,7 @@ pub fn clone_project(
     urn: Urn,
     seed: Option<sync::Seed<String>>,
     interactive: Interactive,
+    transport: Transport,
     ctx: impl term::Context,
 ) -> anyhow::Result<()> {
     let profile = ctx.profile()?;
@@ -140,6 +158,10 @@ pub fn clone_project(
                 seed,
             }),
             verbose: true,
+            sync: sync::Options {
+                transport,
+                ..sync::Options::default()
+            },
             ..rad_sync::Options::default()
         },
         profile.clone(),
@@ -0,0 +1,209 @@
+use std::ffi::OsString;
+use std::fs::File;
+use std::io;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Context as _};
+
+use radicle_common::args::{Args, Error, Help};
+use radicle_common::cobs::bundle::{self, Filter, Update};
+use radicle_common::cobs::Identifier;
+use radicle_common::{cobs, git, keys, project};
+use radicle_terminal as term;
+
+pub const HELP: Help = Help {
+    name: "cob",
+    description: env!("CARGO_PKG_DESCRIPTION"),
+    version: env!("CARGO_PKG_VERSION"),
+    usage: r#"
+Usage
+
+    rad cob export <file> [--type <type>]... [--id <id>]... [<option>...]
+    rad cob import <file> [<option>...]
+
+    Exports the collaborative objects of the current project, eg. issues
+    and patches, to a bundle file, or imports them from one. Bundles are
+    self-contained and can be exchanged offline, like `git bundle`.
+
+    When exporting, objects can be filtered by type (`issue`, `patch` or
+    `label`) and by id. By default, all objects are exported.
+
+    When importing, all changes are verified before anything is written
+    to local storage: changes must be signed by the peer that published
+    them, or by a device of their author. Our own changes are never
+    imported.
+
+Options
+
+    --type <type>    Only export objects of the given type
+    --id <id>        Only export the given object
+    --help           Print help
+"#,
+};
+
+#[derive(Debug)]
+pub enum Operation {
+    Export {
+        typenames: Vec<String>,
+        ids: Vec<Identifier>,
+    },
+    Import,
+}
+
+#[derive(Debug)]
+pub struct Options {
+    pub op: Operation,
+    pub file: PathBuf,
+}
+
+impl Args for Options {
+    fn from_args(args: Vec<OsString>) -> anyhow::Result<(Self, Vec<OsString>)> {
+        use lexopt::prelude::*;
+
+        let mut parser = lexopt::Parser::from_args(args);
+        let mut op: Option<String> = None;
+        let mut file: Option<PathBuf> = None;
+        let mut typenames = Vec::new();
+        let mut ids = Vec::new();
+
+        while let Some(arg) = parser.next()? {
+            match arg {
+                Long("type") => {
+                    let val = parser.value()?;
+                    let val = val
+                        .to_str()
+                        .ok_or_else(|| anyhow!("type specified is not UTF-8"))?;
+
+                    typenames.push(val.to_owned());
+                }
+                Long("id") => {
+                    let val = parser.value()?;
+                    let val = val
+                        .to_str()
+                        .ok_or_else(|| anyhow!("object id specified is not UTF-8"))?;
+
+                    ids.push(Identifier::from_str(val)?);
+                }
+                Long("help") => {
+                    return Err(Error::Help.into());
+                }
+                Value(val) if op.is_none() => match val.to_string_lossy().as_ref() {
+                    "export" | "import" => op = Some(val.to_string_lossy().into_owned()),
+                    unknown => anyhow::bail!("unknown operation '{}'", unknown),
+                },
+                Value(val) if file.is_none() => {
+                    file = Some(PathBuf::from(val));
+                }
+                _ => return Err(anyhow!(arg.unexpected())),
+            }
+        }
+
+        let file = file.ok_or_else(|| anyhow!("a bundle file must be specified"))?;
+        let op = match op.as_deref() {
+            Some("export") => Operation::Export { typenames, ids },
+            Some("import") => {
+                if !typenames.is_empty() || !ids.is_empty() {
+                    anyhow::bail!("`--type` and `--id` can only be used when exporting");
+                }
+                Operation::Import
+            }
+            _ => anyhow::bail!("an operation must be specified, see `rad cob --help`"),
+        };
+
+        Ok((Options { op, file }, vec![]))
+    }
+}
+
+pub fn run(options: Options, ctx: impl term::Context) -> anyhow::Result<()> {
+    let profile = ctx.profile()?;
+    let signer = term::signer(&profile)?;
+    let storage = keys::storage(&profile, signer)?;
+    let (urn, _) = project::cwd()
+        .map_err(|_| anyhow!("this command must be run in the context of a project"))?;
+    let monorepo = git::Repository::open_bare(profile.paths().git_dir())?;
+    let peer = *storage.peer_id();
+
+    match options.op {
+        Operation::Export { typenames, ids } => {
+            let cobs = cobs::store(&profile, &storage)?;
+            let mut filter = Filter::default();
+
+            for name in typenames {
+                let typename = cobs::typenames()
+                    .into_iter()
+                    .find(|t| cobs::kind(t) == name || t.to_string() == name)
+                    .ok_or_else(|| anyhow!("unknown object type '{}'", name))?;
+
+                filter.typenames.push(typename.clone());
+            }
+            for id in ids {
+                let (_, id) = cobs
+                    .resolve_any(&urn, &id)?
+                    .ok_or_else(|| anyhow!("object '{}' not found", id))?;
+
+                filter.ids.push(id);
+            }
+
+            let file = File::create(&options.file)
+                .with_context(|| format!("couldn't create {}", options.file.display()))?;
+            let refs = bundle::export(&monorepo, &urn, &peer, &filter, io::BufWriter::new(file))?;
+
+            term::success!(
+                "Exported {} ref(s) to {}",
+                refs.len(),
+                term::format::highlight(options.file.display())
+            );
+        }
+        Operation::Import => {
+            if project::get(&storage, &urn)?.is_none() {
+                anyhow::bail!("project {} was not found in local storage", urn);
+            }
+            let file = File::open(&options.file)
+                .with_context(|| format!("couldn't open {}", options.file.display()))?;
+            let spinner = term::spinner("Verifying and importing changes...");
+            let updates = match bundle::import(&monorepo, &urn, &peer, io::BufReader::new(file)) {
+                Ok(updates) => {
+                    spinner.finish();
+                    updates
+                }
+                Err(err) => {
+                    spinner.failed();
+                    return Err(err.into());
+                }
+            };
+
+            for (r, update) in &updates {
+                let status = match update {
+                    Update::Created => term::format::positive("created"),
+                    Update::Updated => term::format::positive("updated"),
+                    Update::Unchanged => term::format::dim("unchanged"),
+                    Update::Diverged => term::format::negative("diverged"),
+                    Update::Refused => term::format::dim("ours, skipped"),
+                };
+                term::info!(
+                    "{} {} {} {}",
+                    term::format::tertiary(cobs::kind(&r.typename)),
+                    term::format::highlight(radicle_common::fmt::cob(&r.id)),
+                    term::format::dim(r.peer),
+                    status
+                );
+            }
+
+            cobs::store(&profile, &storage)?.refresh(&urn);
+
+            let diverged = updates
+                .iter()
+                .filter(|(_, u)| *u == Update::Diverged)
+                .count();
+            if diverged > 0 {
+                term::warning(&format!(
+                    "{} ref(s) have diverged from local state and were not imported",
+                    diverged
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
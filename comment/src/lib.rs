@@ -102,23 +102,35 @@ pub fn run(options: Options, ctx: impl term::Context) -> anyhow::Result<()> {
         return Ok(());
     }
 
-    if let Some(id) = cobs.resolve_id::<issue::Issue>(&project, &cob_id)? {
-        if let Some(reply_to_index) = options.reply_index {
-            cobs.issues()
-                .reply(&project, &id, reply_to_index, &message)?;
-        } else {
-            cobs.issues().comment(&project, &id, &message)?;
+    match cobs.resolve_any(&project, &cob_id)? {
+        Some((typename, id)) if typename == *issue::TYPENAME => {
+            if let Some(reply_to_index) = options.reply_index {
+                cobs.issues()
+                    .reply(&project, &id, reply_to_index, &message)?;
+            } else {
+                cobs.issues().comment(&project, &id, &message)?;
+            }
+        }
+        Some((typename, id)) if typename == *patch::TYPENAME => {
+            let patch = cobs
+                .patches()
+                .get(&project, &id)?
+                .ok_or_else(|| anyhow!("Couldn't load patch {}", id))?;
+
+            if let Some(reply_to_index) = options.reply_index {
+                cobs.patches()
+                    .reply(&project, &id, patch.version(), reply_to_index, &message)?;
+            } else {
+                cobs.patches()
+                    .comment(&project, &id, patch.version(), &message)?;
+            }
+        }
+        Some((typename, id)) => {
+            anyhow::bail!("Cannot comment on {} {}", cobs::kind(&typename), id);
         }
-    } else if let Some((id, patch)) = cobs.resolve::<patch::Patch>(&project, &cob_id)? {
-        if let Some(reply_to_index) = options.reply_index {
-            cobs.patches()
-                .reply(&project, &id, patch.version(), reply_to_index, &message)?;
-        } else {
-            cobs.patches()
-                .comment(&project, &id, patch.version(), &message)?;
+        None => {
+            anyhow::bail!("Couldn't find issue or patch {}", cob_id);
         }
-    } else {
-        anyhow::bail!("Couldn't find issue or patch {}", cob_id);
     }
 
     Ok(())
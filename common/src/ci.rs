@@ -0,0 +1,398 @@
+//! Local CI jobs, defined in a project configuration file.
+//!
+//! Jobs are shell commands that are run against a checkout of a patch revision,
+//! eg.
+//!
+//! ```toml
+//! [jobs.build]
+//! command = "cargo build --all"
+//!
+//! [jobs.test]
+//! command = "cargo test --all"
+//! timeout = 1200
+//! ```
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read};
+#[cfg(unix)]
+use std::os::unix::process::CommandExt;
+use std::path::Path;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::cobs::patch::CheckStatus;
+
+/// Path of the CI configuration, relative to the project root.
+pub const CONFIG_PATH: &str = ".radicle/ci.toml";
+/// Default job timeout, in seconds.
+pub const DEFAULT_TIMEOUT: u64 = 10 * 60;
+/// Number of output lines kept for the job summary.
+pub const TAIL_LINES: usize = 20;
+
+/// How often a running job is polled.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// How long to keep reading the output of a job once its command has exited.
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Config {
+    /// Jobs, by name. Jobs are run in name order.
+    #[serde(default)]
+    pub jobs: BTreeMap<String, Job>,
+}
+
+impl Config {
+    /// Read the CI configuration of the project checked out at `root`.
+    pub fn load<P: AsRef<Path>>(root: P) -> Result<Self, io::Error> {
+        let content = fs::read_to_string(root.as_ref().join(CONFIG_PATH))?;
+        let config = toml::from_str(&content)?;
+
+        Ok(config)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Job {
+    /// Shell command to run.
+    pub command: String,
+    /// Timeout in seconds, after which the job is killed.
+    #[serde(default)]
+    pub timeout: Option<u64>,
+    /// Additional environment variables.
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+}
+
+impl Job {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout.unwrap_or(DEFAULT_TIMEOUT))
+    }
+}
+
+/// How a job ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// The command exited successfully.
+    Passed,
+    /// The command exited with an error, or was killed by a signal.
+    Failed { code: Option<i32> },
+    /// The command didn't finish in time and was killed.
+    TimedOut,
+}
+
+impl From<ExitStatus> for Outcome {
+    fn from(status: ExitStatus) -> Self {
+        if status.success() {
+            Self::Passed
+        } else {
+            Self::Failed {
+                code: status.code(),
+            }
+        }
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Passed => write!(f, "passed"),
+            Self::Failed { code: Some(code) } => write!(f, "failed with exit code {}", code),
+            Self::Failed { code: None } => write!(f, "was terminated"),
+            Self::TimedOut => write!(f, "timed out"),
+        }
+    }
+}
+
+/// The result of running a job.
+#[derive(Debug, Clone)]
+pub struct Report {
+    /// Job name.
+    pub name: String,
+    /// How the job ended.
+    pub outcome: Outcome,
+    /// How long the job ran for.
+    pub duration: Duration,
+    /// The last lines of output.
+    pub tail: Vec<String>,
+}
+
+impl Report {
+    pub fn status(&self) -> CheckStatus {
+        match self.outcome {
+            Outcome::Passed => CheckStatus::Pass,
+            _ => CheckStatus::Fail,
+        }
+    }
+
+    /// Summary to record with the check result. Includes the end of the output
+    /// of failed jobs.
+    pub fn summary(&self) -> String {
+        let mut summary = format!("{} in {}s", self.outcome, self.duration.as_secs());
+
+        if self.outcome != Outcome::Passed && !self.tail.is_empty() {
+            summary.push_str("\n\n");
+            summary.push_str(&self.tail.join("\n"));
+        }
+        summary
+    }
+}
+
+/// Run a job in the given directory. Every line of output is passed to `progress`.
+///
+/// The job ends when its command exits, even if processes it started in the
+/// background still hold on to its output. On unix, the job runs in its own process
+/// group, so that any process it started is killed once the job ends or times out.
+pub fn run(
+    name: &str,
+    job: &Job,
+    dir: &Path,
+    mut progress: impl FnMut(&str),
+) -> Result<Report, io::Error> {
+    let start = Instant::now();
+    let timeout = job.timeout();
+    let mut command = Command::new("sh");
+    command
+        .arg("-c")
+        .arg(&job.command)
+        .current_dir(dir)
+        .envs(&job.env)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    // SAFETY: `setpgid` is async-signal-safe.
+    #[cfg(unix)]
+    unsafe {
+        command.pre_exec(|| {
+            if libc::setpgid(0, 0) == 0 {
+                Ok(())
+            } else {
+                Err(io::Error::last_os_error())
+            }
+        });
+    }
+    let mut child = command.spawn()?;
+
+    let (tx, rx) = mpsc::channel();
+    if let Some(stdout) = child.stdout.take() {
+        forward(stdout, tx.clone());
+    }
+    if let Some(stderr) = child.stderr.take() {
+        forward(stderr, tx);
+    }
+
+    let mut tail = VecDeque::with_capacity(TAIL_LINES);
+    let mut record = |line: String| {
+        progress(&line);
+
+        if tail.len() == TAIL_LINES {
+            tail.pop_front();
+        }
+        tail.push_back(line);
+    };
+    let mut closed = false;
+    let outcome = loop {
+        if let Some(status) = child.try_wait()? {
+            // Pick up the output that was written before the command exited, without
+            // waiting for background processes to close the output streams.
+            let exited = Instant::now();
+            while exited.elapsed() < DRAIN_TIMEOUT {
+                match rx.recv_timeout(POLL_INTERVAL) {
+                    Ok(line) => record(line),
+                    Err(_) => break,
+                }
+            }
+            kill(&mut child);
+
+            break Outcome::from(status);
+        }
+        if start.elapsed() >= timeout {
+            kill(&mut child);
+            child.wait()?;
+
+            break Outcome::TimedOut;
+        }
+        if closed {
+            thread::sleep(POLL_INTERVAL);
+            continue;
+        }
+        match rx.recv_timeout(POLL_INTERVAL) {
+            Ok(line) => record(line),
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            // Both output streams were closed.
+            Err(mpsc::RecvTimeoutError::Disconnected) => closed = true,
+        }
+    };
+
+    Ok(Report {
+        name: name.to_owned(),
+        outcome,
+        duration: start.elapsed(),
+        tail: tail.into_iter().collect(),
+    })
+}
+
+/// Kill the process group of a job.
+#[cfg(unix)]
+fn kill(child: &mut Child) {
+    // SAFETY: The child is a process group leader, so the group id is its pid.
+    let killed = unsafe { libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL) } == 0;
+
+    if !killed {
+        child.kill().ok();
+    }
+}
+
+/// Kill a job. Processes it started are not killed along with it.
+#[cfg(not(unix))]
+fn kill(child: &mut Child) {
+    child.kill().ok();
+}
+
+/// Forward the lines of a stream to a channel, from a separate thread.
+fn forward<R: Read + Send + 'static>(stream: R, tx: mpsc::Sender<String>) {
+    thread::spawn(move || {
+        for line in BufReader::new(stream).lines() {
+            match line {
+                Ok(line) => {
+                    if tx.send(line).is_err() {
+                        break;
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn job(command: &str, timeout: Option<u64>) -> Job {
+        Job {
+            command: command.to_owned(),
+            timeout,
+            env: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_config() {
+        let config: Config = toml::from_str(
+            r#"
+            [jobs.build]
+            command = "cargo build"
+
+            [jobs.test]
+            command = "cargo test"
+            timeout = 60
+            env = { RUST_BACKTRACE = "1" }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.jobs.keys().collect::<Vec<_>>(),
+            vec!["build", "test"]
+        );
+        assert_eq!(
+            config.jobs["build"].timeout(),
+            Duration::from_secs(DEFAULT_TIMEOUT)
+        );
+        assert_eq!(config.jobs["test"].timeout(), Duration::from_secs(60));
+        assert_eq!(config.jobs["test"].env["RUST_BACKTRACE"], "1");
+    }
+
+    #[test]
+    fn test_run() {
+        let dir = std::env::temp_dir();
+        let mut lines = Vec::new();
+
+        let report = run("ok", &job("echo hello; echo world", None), &dir, |l| {
+            lines.push(l.to_owned())
+        })
+        .unwrap();
+        assert_eq!(report.outcome, Outcome::Passed);
+        assert_eq!(report.status(), CheckStatus::Pass);
+        assert_eq!(lines, vec!["hello", "world"]);
+
+        let report = run("fail", &job("echo oops >&2; exit 3", None), &dir, |_| {}).unwrap();
+        assert_eq!(report.outcome, Outcome::Failed { code: Some(3) });
+        assert_eq!(report.tail, vec!["oops"]);
+        assert!(report.summary().ends_with("oops"));
+
+        let report = run("slow", &job("sleep 5", Some(0)), &dir, |_| {}).unwrap();
+        assert_eq!(report.outcome, Outcome::TimedOut);
+        assert_eq!(report.status(), CheckStatus::Fail);
+    }
+
+    /// Wait for a process to be gone, for up to ten seconds.
+    #[cfg(unix)]
+    fn is_killed(pid: libc::pid_t) -> bool {
+        let deadline = Instant::now() + Duration::from_secs(10);
+
+        while Instant::now() < deadline {
+            // SAFETY: Signal `0` only checks whether the process exists.
+            if unsafe { libc::kill(pid, 0) } != 0 {
+                return true;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        false
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_run_background_output() {
+        let dir = std::env::temp_dir();
+        let mut pids = Vec::new();
+
+        // The command exits right away, but leaves a process behind that keeps
+        // its output open.
+        let report = run(
+            "background",
+            &job("sleep 30 & echo $!", Some(60)),
+            &dir,
+            |l| pids.push(l.parse::<libc::pid_t>().unwrap()),
+        )
+        .unwrap();
+
+        assert_eq!(report.outcome, Outcome::Passed);
+        assert!(report.duration < Duration::from_secs(30));
+        assert_eq!(pids.len(), 1);
+        // The leftover process is killed once the job ends.
+        assert!(is_killed(pids[0]));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_run_timeout_kills_children() {
+        let dir = std::env::temp_dir().join(format!("rad-ci-{}", std::process::id()));
+        let pidfile = dir.join("pid");
+
+        fs::create_dir_all(&dir).unwrap();
+
+        let report = run(
+            "spawn",
+            &job("sleep 30 & echo $! > pid; wait", Some(1)),
+            &dir,
+            |_| {},
+        )
+        .unwrap();
+        assert_eq!(report.outcome, Outcome::TimedOut);
+
+        // If the shell got to start the background process before it was killed,
+        // that process was killed along with it.
+        if let Ok(pid) = fs::read_to_string(&pidfile) {
+            if let Ok(pid) = pid.trim().parse::<libc::pid_t>() {
+                assert!(is_killed(pid));
+            }
+        }
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -1,6 +1,11 @@
+pub mod bundle;
+pub mod cache;
+pub mod inbox;
 pub mod issue;
 pub mod label;
 pub mod patch;
+pub mod policy;
+pub mod search;
 pub mod shared;
 pub mod user;
 
@@ -0,0 +1,519 @@
+//! Offline exchange of collaborative objects.
+//!
+//! A bundle holds the COB refs of a project along with all the git objects they
+//! point to, in the format of a `git bundle` (v2). Ref names are fully qualified
+//! and always include the peer that published the change, eg.
+//!
+//!   refs/namespaces/<project>/refs/remotes/<peer>/cobs/<typename>/<id>
+//!
+//! so that bundles can be imported by any peer, and even be inspected and fetched
+//! from with plain `git`.
+use std::collections::HashSet;
+use std::io::{self, BufRead, Read, Write};
+use std::str::FromStr;
+
+use librad::collaborative_objects::{ObjectId, TypeName};
+use librad::git::Urn;
+use librad::identities::git::Identities;
+use librad::identities::sign::Signatures;
+use librad::identities::Person;
+use librad::PeerId;
+
+use crate::git;
+
+/// Bundle file header.
+pub const HEADER: &str = "# v2 git bundle";
+/// Commit trailer pointing to the identity of a change's author.
+const AUTHOR_TRAILER: &str = "X-Rad-Author";
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("i/o error: {0}")]
+    Io(#[from] io::Error),
+    #[error("git error: {0}")]
+    Git(#[from] git2::Error),
+    #[error("invalid bundle: {0}")]
+    Format(String),
+    #[error("bundle is for project {found}, expected {expected}")]
+    Namespace { expected: Urn, found: Urn },
+    #[error("change {0} is not signed")]
+    Unsigned(git2::Oid),
+    #[error("change {0} has an invalid signature")]
+    Signature(git2::Oid),
+    #[error("change {oid} was not signed by {peer} or a delegate of its author")]
+    Signer { oid: git2::Oid, peer: PeerId },
+    #[error("the author identity of change {0} could not be verified")]
+    Author(git2::Oid),
+}
+
+/// A COB ref contained in a bundle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ref {
+    /// Project the object belongs to.
+    pub namespace: Urn,
+    /// Peer that published the changes.
+    pub peer: PeerId,
+    /// Object type.
+    pub typename: TypeName,
+    /// Object id.
+    pub id: ObjectId,
+    /// Latest change.
+    pub oid: git2::Oid,
+}
+
+impl Ref {
+    /// Fully qualified ref name, as written to a bundle.
+    pub fn name(&self) -> String {
+        format!(
+            "refs/namespaces/{}/refs/remotes/{}/cobs/{}/{}",
+            self.namespace.encode_id(),
+            self.peer,
+            self.typename,
+            self.id
+        )
+    }
+}
+
+impl FromStr for Ref {
+    type Err = Error;
+
+    /// Parse a bundle header line, ie. `<oid> <refname>`.
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::Format(format!("invalid ref `{}`", line));
+        let (oid, name) = line.split_once(' ').ok_or_else(invalid)?;
+        let oid = git2::Oid::from_str(oid).map_err(|_| invalid())?;
+        let components = name.split('/').collect::<Vec<_>>();
+
+        match components.as_slice() {
+            ["refs", "namespaces", ns, "refs", "remotes", peer, "cobs", typename, id] => Ok(Self {
+                namespace: Urn::try_from_id(ns).map_err(|_| invalid())?,
+                peer: PeerId::from_str(peer).map_err(|_| invalid())?,
+                typename: TypeName::from_str(typename).map_err(|_| invalid())?,
+                id: ObjectId::from_str(id).map_err(|_| invalid())?,
+                oid,
+            }),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// What to include in a bundle.
+#[derive(Debug, Default, Clone)]
+pub struct Filter {
+    /// Object types to include. All types are included if empty.
+    pub typenames: Vec<TypeName>,
+    /// Objects to include. All objects are included if empty.
+    pub ids: Vec<ObjectId>,
+}
+
+impl Filter {
+    fn matches(&self, r: &Ref) -> bool {
+        (self.typenames.is_empty() || self.typenames.contains(&r.typename))
+            && (self.ids.is_empty() || self.ids.contains(&r.id))
+    }
+}
+
+/// The outcome of importing a ref.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Update {
+    /// The ref didn't exist and was created.
+    Created,
+    /// The ref was fast-forwarded.
+    Updated,
+    /// The local ref already contains all changes of the bundle.
+    Unchanged,
+    /// The local and bundled histories have diverged; the ref was left alone.
+    Diverged,
+    /// The ref belongs to the local peer, whose changes are never imported.
+    Refused,
+}
+
+/// Get the COB refs of a project, as they would be written to a bundle.
+pub fn refs(
+    repo: &git2::Repository,
+    namespace: &Urn,
+    local: &PeerId,
+    filter: &Filter,
+) -> Result<Vec<Ref>, Error> {
+    let ns = namespace.encode_id();
+    let mut refs = Vec::new();
+
+    for r in repo.references_glob(&format!("refs/namespaces/{}/refs/cobs/*", ns))? {
+        let r = r?;
+        let (name, oid) = match (r.name(), r.target()) {
+            (Some(name), Some(oid)) => (name, oid),
+            _ => continue,
+        };
+        let suffix = name.splitn(5, '/').nth(4).unwrap_or_default();
+        let line = format!(
+            "{} refs/namespaces/{}/refs/remotes/{}/{}",
+            oid, ns, local, suffix
+        );
+
+        if let Ok(r) = Ref::from_str(&line) {
+            refs.push(r);
+        }
+    }
+    for r in repo.references_glob(&format!("refs/namespaces/{}/refs/remotes/*/cobs/*", ns))? {
+        let r = r?;
+
+        if let (Some(name), Some(oid)) = (r.name(), r.target()) {
+            if let Ok(r) = Ref::from_str(&format!("{} {}", oid, name)) {
+                refs.push(r);
+            }
+        }
+    }
+    refs.retain(|r| filter.matches(r));
+    refs.sort_by_key(|r| r.name());
+
+    Ok(refs)
+}
+
+/// Write a bundle of the COBs of a project. Returns the refs that were bundled.
+pub fn export<W: Write>(
+    repo: &git2::Repository,
+    namespace: &Urn,
+    local: &PeerId,
+    filter: &Filter,
+    mut writer: W,
+) -> Result<Vec<Ref>, Error> {
+    let refs = refs(repo, namespace, local, filter)?;
+    let mut walk = repo.revwalk()?;
+    let mut pack = repo.packbuilder()?;
+    let mut buf = git2::Buf::new();
+
+    for r in &refs {
+        walk.push(r.oid)?;
+    }
+    pack.insert_walk(&mut walk)?;
+    pack.write_buf(&mut buf)?;
+
+    writeln!(writer, "{}", HEADER)?;
+    for r in &refs {
+        writeln!(writer, "{} {}", r.oid, r.name())?;
+    }
+    writeln!(writer)?;
+    writer.write_all(&buf)?;
+    writer.flush()?;
+
+    Ok(refs)
+}
+
+/// Read a bundle header, leaving the reader at the start of the pack data.
+pub fn read_refs<R: BufRead>(reader: &mut R) -> Result<Vec<Ref>, Error> {
+    let mut line = String::new();
+
+    reader.read_line(&mut line)?;
+    if line.trim_end() != HEADER {
+        return Err(Error::Format(String::from("unrecognized header")));
+    }
+
+    let mut refs = Vec::new();
+    loop {
+        line.clear();
+
+        if reader.read_line(&mut line)? == 0 {
+            return Err(Error::Format(String::from("unexpected end of file")));
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if line.starts_with('-') {
+            return Err(Error::Format(String::from(
+                "bundles with prerequisites are not supported",
+            )));
+        }
+        refs.push(Ref::from_str(line)?);
+    }
+    Ok(refs)
+}
+
+/// Import a bundle of the COBs of `namespace`.
+///
+/// The pack is first written to a scratch repository, where all changes are
+/// verified: every change must be validly signed, either by the peer whose ref it
+/// is reachable from, or by a delegate of its author, and every ref must point to
+/// a change signed by its peer. Only then are the objects written to `repo` and
+/// refs updated. Refs are only ever fast-forwarded, since the changes published by
+/// a peer form a linear history. Refs of the `local` peer are never imported.
+pub fn import<R: BufRead>(
+    repo: &git2::Repository,
+    namespace: &Urn,
+    local: &PeerId,
+    mut reader: R,
+) -> Result<Vec<(Ref, Update)>, Error> {
+    let refs = read_refs(&mut reader)?;
+
+    if let Some(r) = refs.iter().find(|r| &r.namespace != namespace) {
+        return Err(Error::Namespace {
+            expected: namespace.clone(),
+            found: r.namespace.clone(),
+        });
+    }
+
+    let mut pack = Vec::new();
+    reader.read_to_end(&mut pack)?;
+
+    {
+        let scratch = git::Scratch::new(repo, "rad-cob-import")?;
+        let known = repo.odb()?;
+
+        if !pack.is_empty() {
+            write_pack(&scratch.repo, &pack)?;
+        }
+        let mut visited = HashSet::new();
+        for r in refs.iter().filter(|r| &r.peer != local) {
+            verify(&scratch.repo, &known, r, &mut visited)?;
+        }
+    }
+    if !pack.is_empty() {
+        write_pack(repo, &pack)?;
+    }
+
+    let mut updates = Vec::new();
+    for r in refs {
+        if &r.peer == local {
+            updates.push((r, Update::Refused));
+            continue;
+        }
+        let name = r.name();
+        let update = match repo.refname_to_id(&name) {
+            Ok(current) if current == r.oid => Update::Unchanged,
+            Ok(current) if repo.graph_descendant_of(current, r.oid)? => Update::Unchanged,
+            Ok(current) if repo.graph_descendant_of(r.oid, current)? => {
+                repo.reference(&name, r.oid, true, "rad cob import: fast-forward")?;
+                Update::Updated
+            }
+            Ok(_) => Update::Diverged,
+            Err(e) if e.code() == git2::ErrorCode::NotFound => {
+                repo.reference(&name, r.oid, false, "rad cob import: create")?;
+                Update::Created
+            }
+            Err(e) => return Err(e.into()),
+        };
+        updates.push((r, update));
+    }
+    Ok(updates)
+}
+
+/// Write pack data to the object database of a repository.
+fn write_pack(repo: &git2::Repository, pack: &[u8]) -> Result<(), Error> {
+    let odb = repo.odb()?;
+    let mut writer = odb.packwriter()?;
+
+    writer.write_all(pack)?;
+    writer.commit()?;
+
+    Ok(())
+}
+
+/// Verify the changes reachable from a bundled ref. Changes that are already
+/// `known` were verified when they were first stored, and are skipped.
+fn verify(
+    repo: &git2::Repository,
+    known: &git2::Odb,
+    r: &Ref,
+    visited: &mut HashSet<git2::Oid>,
+) -> Result<(), Error> {
+    let forged = |oid| Error::Signer { oid, peer: r.peer };
+
+    // Only the peer itself can have published its latest change.
+    if !signers(&repo.find_commit(r.oid)?)?.contains(&r.peer) {
+        return Err(forged(r.oid));
+    }
+
+    let mut walk = repo.revwalk()?;
+    walk.push(r.oid)?;
+
+    for oid in walk {
+        let oid = oid?;
+        if known.exists(oid) || !visited.insert(oid) {
+            continue;
+        }
+        let commit = repo.find_commit(oid)?;
+        let signers = signers(&commit)?;
+
+        // Identity commits don't have an author, and are verified along with the
+        // changes that refer to them.
+        let author = match author(&commit) {
+            Some(author) => author,
+            None => continue,
+        };
+        if signers.contains(&r.peer) {
+            continue;
+        }
+        let person = Identities::<Person>::from(repo)
+            .verify(author)
+            .map_err(|_| Error::Author(oid))?;
+
+        if !person
+            .delegations()
+            .iter()
+            .any(|key| signers.contains(&PeerId::from(*key)))
+        {
+            return Err(forged(oid));
+        }
+    }
+    Ok(())
+}
+
+/// Get the peers that signed a commit, checking that all signatures are valid.
+fn signers(commit: &git2::Commit) -> Result<Vec<PeerId>, Error> {
+    let oid = commit.id();
+    let signatures = Signatures::from_trailers(commit.message().unwrap_or_default())
+        .map_err(|_| Error::Unsigned(oid))?;
+
+    if signatures.is_empty() {
+        return Err(Error::Unsigned(oid));
+    }
+    // Changes are signed over the tree they point to.
+    let revision = commit.tree_id();
+    let mut signers = Vec::new();
+
+    for (key, sig) in signatures.iter() {
+        if !sig.verify(revision.as_bytes(), key) {
+            return Err(Error::Signature(oid));
+        }
+        signers.push(PeerId::from(*key));
+    }
+    Ok(signers)
+}
+
+/// Get the author identity commit of a change, if any.
+fn author(commit: &git2::Commit) -> Option<git2::Oid> {
+    commit
+        .message()
+        .unwrap_or_default()
+        .lines()
+        .find_map(|line| {
+            let (token, value) = line.split_once(':')?;
+
+            if token.trim() == AUTHOR_TRAILER {
+                git2::Oid::from_str(value.trim()).ok()
+            } else {
+                None
+            }
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cobs::issue::TYPENAME;
+    use crate::cobs::Store;
+    use crate::test;
+
+    #[test]
+    fn test_ref_roundtrip() {
+        let line = "d96f425412c9f8ad5d9a9a05c9831d0728e2338d \
+            refs/namespaces/hnrkyghsrokxzxpy9pww69xr11dr9q7edbxfo/refs/remotes/\
+            hyndb9g3jc4soj6zsbd9iyryrnkbh4nbjnmnm4pgttdpjd6ngkfs1w/cobs/\
+            xyz.radicle.issue/0186bd2f-63ff-4f6b-a6d5-d0d4b26b6a5b";
+        let r = Ref::from_str(line).unwrap();
+
+        assert_eq!(r.typename, *TYPENAME);
+        assert_eq!(format!("{} {}", r.oid, r.name()), line);
+        assert!(
+            Ref::from_str("d96f425412c9f8ad5d9a9a05c9831d0728e2338d refs/heads/master").is_err()
+        );
+    }
+
+    #[test]
+    fn test_export_import() {
+        let (storage, profile, whoami, project) = test::setup::profile();
+        let cobs = Store::new(whoami, profile.paths(), &storage);
+        let issues = cobs.issues();
+        let id = issues
+            .create(&project.urn(), "Bundled", "Travels by USB", &[])
+            .unwrap();
+        let repo = git2::Repository::open_bare(profile.paths().git_dir()).unwrap();
+        let peer = *storage.peer_id();
+
+        let mut bundle = Vec::new();
+        let exported = export(
+            &repo,
+            &project.urn(),
+            &peer,
+            &Filter::default(),
+            &mut bundle,
+        )
+        .unwrap();
+        assert_eq!(exported.len(), 1);
+        assert_eq!(exported[0].id, id);
+
+        // Our own changes are never imported.
+        let updates = import(&repo, &project.urn(), &peer, bundle.as_slice()).unwrap();
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].1, Update::Refused);
+
+        // Another peer can import them.
+        let other =
+            PeerId::from_str("hyb5to4rshftx4apgmu9s6wnsp4ddmp1mz6ijh4qqey7fb8wrpawxa").unwrap();
+        let updates = import(&repo, &project.urn(), &other, bundle.as_slice()).unwrap();
+        assert_eq!(updates[0].1, Update::Created);
+        assert_eq!(
+            repo.refname_to_id(&exported[0].name()).unwrap(),
+            exported[0].oid
+        );
+
+        // Importing under another project is refused.
+        let other = Urn::try_from_id("hnrkyghsrokxzxpy9pww69xr11dr9q7edbxfo").unwrap();
+        assert!(matches!(
+            import(&repo, &other, &peer, bundle.as_slice()),
+            Err(Error::Namespace { .. })
+        ));
+
+        // Filtering by type leaves the issue out.
+        let filter = Filter {
+            typenames: vec![crate::cobs::patch::TYPENAME.clone()],
+            ids: vec![],
+        };
+        assert!(refs(&repo, &project.urn(), &peer, &filter)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_import_forged() {
+        let (storage, profile, whoami, project) = test::setup::profile();
+        let cobs = Store::new(whoami, profile.paths(), &storage);
+        cobs.issues()
+            .create(&project.urn(), "Forged", "Not by who it says", &[])
+            .unwrap();
+        let repo = git2::Repository::open_bare(profile.paths().git_dir()).unwrap();
+        let peer = *storage.peer_id();
+        let alice =
+            PeerId::from_str("hyb5to4rshftx4apgmu9s6wnsp4ddmp1mz6ijh4qqey7fb8wrpawxa").unwrap();
+        let bob =
+            PeerId::from_str("hyd7wpd8p5aqnm9htsfoatxkckmw6ingnsdudns9code5xq17h1rhw").unwrap();
+
+        let mut bundle = Vec::new();
+        let exported = export(
+            &repo,
+            &project.urn(),
+            &peer,
+            &Filter::default(),
+            &mut bundle,
+        )
+        .unwrap();
+
+        // Claim that our changes were published by bob.
+        let (header, pack) =
+            bundle.split_at(bundle.windows(2).position(|w| w == b"\n\n").unwrap() + 2);
+        let header = String::from_utf8(header.to_vec())
+            .unwrap()
+            .replace(&peer.to_string(), &bob.to_string());
+        let forged = [header.as_bytes(), pack].concat();
+
+        assert!(matches!(
+            import(&repo, &project.urn(), &alice, forged.as_slice()),
+            Err(Error::Signer { peer, .. }) if peer == bob
+        ));
+        let forged = Ref {
+            peer: bob,
+            ..exported[0].clone()
+        };
+        assert!(repo.refname_to_id(&forged.name()).is_err());
+    }
+}
@@ -0,0 +1,359 @@
+//! On-disk index of materialized collaborative objects.
+//!
+//! Replaying an object's history is expensive, so we keep the materialized
+//! objects around, keyed by object id and the history tips they were built from.
+//! The tips are read directly from the monorepo refs, which is cheap, and only
+//! objects whose tips have changed since they were indexed are replayed.
+//!
+//! Objects written through the store are re-indexed right away, and the index of
+//! a project is refreshed after fetching it, so that listing objects rarely has
+//! to replay anything.
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use librad::collaborative_objects::{CollaborativeObjects, ObjectId, TypeName};
+use librad::git::Urn;
+use librad::paths::Paths;
+
+use crate::cobs::policy;
+use crate::cobs::shared::Cob;
+
+/// Name of the index directory, under the COB cache directory.
+pub const INDEX_DIR: &str = "index";
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("i/o error: {0}")]
+    Io(#[from] io::Error),
+    #[error("git error: {0}")]
+    Git(#[from] git2::Error),
+    #[error("index decoding error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("retrieve error: {0}")]
+    Retrieve(#[from] librad::collaborative_objects::error::Retrieve),
+    #[error("error materializing object {id}: {err}")]
+    Materialize { id: ObjectId, err: anyhow::Error },
+}
+
+/// Tips of an object's history, ie. the ref targets of every peer's copy.
+pub type Tips = BTreeSet<String>;
+
+/// An indexed object.
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry<T> {
+    tips: Tips,
+    /// Fingerprint of the policy the object was authorized with.
+    #[serde(default)]
+    policy: String,
+    object: T,
+}
+
+/// Index of all objects of a given type, under a given namespace.
+type Index<T> = BTreeMap<String, Entry<T>>;
+
+/// Persistent COB index.
+#[derive(Debug, Clone)]
+pub struct Cache {
+    /// Root of the index.
+    root: PathBuf,
+    /// Path to the monorepo.
+    git_dir: PathBuf,
+}
+
+impl Cache {
+    /// Open the cache for the given profile paths.
+    pub fn open(paths: &Paths) -> Self {
+        Self {
+            root: paths.cob_cache_dir().join(INDEX_DIR),
+            git_dir: paths.git_dir().to_path_buf(),
+        }
+    }
+
+    /// Get the history tips of all objects of type `T` under the given namespace.
+    ///
+    /// This only reads refs, and does not load any object.
+    pub fn tips<T: Cob>(&self, namespace: &Urn) -> Result<HashMap<ObjectId, Tips>, Error> {
+        self.tips_of(namespace, T::type_name())
+    }
+
+    /// Get the history tips of all objects of the given type under the given namespace.
+    pub fn tips_of(
+        &self,
+        namespace: &Urn,
+        typename: &TypeName,
+    ) -> Result<HashMap<ObjectId, Tips>, Error> {
+        let repo = git2::Repository::open_bare(&self.git_dir)?;
+        let ns = namespace.encode_id();
+        let globs = [
+            format!("refs/namespaces/{}/refs/cobs/{}/*", ns, typename),
+            format!("refs/namespaces/{}/refs/remotes/*/cobs/{}/*", ns, typename),
+        ];
+        let mut tips: HashMap<ObjectId, Tips> = HashMap::new();
+
+        for glob in globs.iter() {
+            for r in repo.references_glob(glob)? {
+                let r = r?;
+                let (name, target) = match (r.name(), r.target()) {
+                    (Some(name), Some(target)) => (name, target),
+                    _ => continue,
+                };
+                let id = match name.rsplit('/').next().map(ObjectId::from_str) {
+                    Some(Ok(id)) => id,
+                    _ => continue,
+                };
+                tips.entry(id).or_default().insert(target.to_string());
+            }
+        }
+        Ok(tips)
+    }
+
+    /// Get the ids of all objects of type `T` under the given namespace.
+    pub fn ids<T: Cob>(&self, namespace: &Urn) -> Result<Vec<ObjectId>, Error> {
+        self.ids_of(namespace, T::type_name())
+    }
+
+    /// Get the ids of all objects of the given type under the given namespace.
+    pub fn ids_of(&self, namespace: &Urn, typename: &TypeName) -> Result<Vec<ObjectId>, Error> {
+        self.tips_of(namespace, typename)
+            .map(|t| t.into_keys().collect())
+    }
+
+    /// Count the objects of type `T` under the given namespace.
+    pub fn count<T: Cob>(&self, namespace: &Urn) -> Result<usize, Error> {
+        self.tips::<T>(namespace).map(|t| t.len())
+    }
+
+    /// Get all objects of type `T` under the given namespace.
+    ///
+    /// Objects that are missing from the index, or whose history has changed since
+    /// they were indexed, are materialized from their history and the index is updated.
+    /// Objects that no longer exist are removed from the index. Objects are re-indexed
+    /// when the authorization context changes.
+    pub fn all<T>(
+        &self,
+        store: &CollaborativeObjects,
+        namespace: &Urn,
+        ctx: &policy::Context,
+    ) -> Result<Vec<(ObjectId, T)>, Error>
+    where
+        T: Cob + Clone + Serialize + DeserializeOwned,
+    {
+        self.filter(store, namespace, ctx, |_| true)
+    }
+
+    /// Get the objects of type `T` under the given namespace that match a predicate.
+    ///
+    /// The predicate is evaluated against the indexed objects, so only objects that
+    /// are out of date are replayed, and only matching objects are returned.
+    pub fn filter<T, P>(
+        &self,
+        store: &CollaborativeObjects,
+        namespace: &Urn,
+        ctx: &policy::Context,
+        predicate: P,
+    ) -> Result<Vec<(ObjectId, T)>, Error>
+    where
+        T: Cob + Clone + Serialize + DeserializeOwned,
+        P: Fn(&T) -> bool,
+    {
+        let path = self.path(namespace, T::type_name());
+        let tips = self.tips::<T>(namespace)?;
+        let mut index: Index<T> = self.read(&path).unwrap_or_default();
+        let mut stale = index.len() != tips.len();
+        let mut objects = Vec::with_capacity(tips.len());
+        let policy = ctx.fingerprint();
+
+        index.retain(|id, _| ObjectId::from_str(id).map_or(false, |id| tips.contains_key(&id)));
+
+        for (id, tips) in tips {
+            let key = id.to_string();
+
+            if let Some(entry) = index.get(&key) {
+                if entry.tips == tips && entry.policy == policy {
+                    if predicate(&entry.object) {
+                        objects.push((id, entry.object.clone()));
+                    }
+                    continue;
+                }
+            }
+            // The object is either new or was updated; replay its history.
+            let cob = match store.retrieve(namespace, T::type_name(), &id)? {
+                Some(cob) => cob,
+                None => continue,
+            };
+            let (object, violations) = T::from_history_authorized(cob.history(), ctx)
+                .map_err(|err| Error::Materialize { id, err })?;
+
+            for v in violations {
+                log::warn!("Skipped change to {} {}: {}", T::type_name(), id, v);
+            }
+            if predicate(&object) {
+                objects.push((id, object.clone()));
+            }
+            index.insert(
+                key,
+                Entry {
+                    tips,
+                    policy: policy.clone(),
+                    object,
+                },
+            );
+            stale = true;
+        }
+
+        if stale {
+            self.write(&path, &index)?;
+        }
+        Ok(objects)
+    }
+
+    /// Update the index entry of a single object of type `T`, eg. after a change
+    /// was written to it. The entry is removed if the object no longer exists.
+    pub fn update<T>(
+        &self,
+        store: &CollaborativeObjects,
+        namespace: &Urn,
+        id: &ObjectId,
+        ctx: &policy::Context,
+    ) -> Result<(), Error>
+    where
+        T: Cob + Serialize + DeserializeOwned,
+    {
+        let path = self.path(namespace, T::type_name());
+        let mut index: Index<T> = self.read(&path).unwrap_or_default();
+        let key = id.to_string();
+        let tips = self.tips::<T>(namespace)?.remove(id);
+        let cob = store.retrieve(namespace, T::type_name(), id)?;
+
+        match tips.zip(cob) {
+            Some((tips, cob)) => {
+                let (object, violations) = T::from_history_authorized(cob.history(), ctx)
+                    .map_err(|err| Error::Materialize { id: *id, err })?;
+
+                for v in violations {
+                    log::warn!("Skipped change to {} {}: {}", T::type_name(), id, v);
+                }
+                index.insert(
+                    key,
+                    Entry {
+                        tips,
+                        policy: ctx.fingerprint(),
+                        object,
+                    },
+                );
+            }
+            None => {
+                index.remove(&key);
+            }
+        }
+        self.write(&path, &index)
+    }
+
+    /// Remove the whole index. It will be rebuilt on next access.
+    pub fn clear(&self) -> Result<(), Error> {
+        match fs::remove_dir_all(&self.root) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn path(&self, namespace: &Urn, typename: &TypeName) -> PathBuf {
+        self.root
+            .join(namespace.encode_id())
+            .join(format!("{}.json", typename))
+    }
+
+    fn read<T: DeserializeOwned>(&self, path: &Path) -> Option<Index<T>> {
+        let bytes = fs::read(path).ok()?;
+
+        // A corrupt or outdated index is simply rebuilt.
+        serde_json::from_slice(&bytes).ok()
+    }
+
+    fn write<T: Serialize>(&self, path: &Path, index: &Index<T>) -> Result<(), Error> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // Write to a temporary file first, so that readers never see a partial index.
+        let tmp = path.with_extension("json.tmp");
+
+        fs::write(&tmp, serde_json::to_vec(index)?)?;
+        fs::rename(&tmp, path)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cobs::issue::Issue;
+    use crate::cobs::shared::Store;
+    use crate::test;
+
+    #[test]
+    fn test_cache_all() {
+        let (storage, profile, whoami, project) = test::setup::profile();
+        let cobs = Store::new(whoami, profile.paths(), &storage);
+        let cache = Cache::open(profile.paths());
+        let issues = cobs.issues();
+        let project = project.urn();
+
+        let issue_id = issues
+            .create(&project, "My first issue", "Blah blah blah.", &[])
+            .unwrap();
+        let path = cache.path(&project, &crate::cobs::issue::TYPENAME);
+
+        // Creating the issue indexes it.
+        assert_eq!(cache.count::<Issue>(&project).unwrap(), 1);
+        assert!(path.exists());
+
+        let all = cache
+            .all::<Issue>(&cobs, &project, &Default::default())
+            .unwrap();
+        assert_eq!(all.len(), 1);
+        assert_eq!(all[0].0, issue_id);
+
+        // Updating the object invalidates its index entry.
+        issues.comment(&project, &issue_id, "Ho ho ho.").unwrap();
+
+        let all = cache
+            .all::<Issue>(&cobs, &project, &Default::default())
+            .unwrap();
+        assert_eq!(all[0].1.comments().len(), 1);
+    }
+
+    #[test]
+    fn test_cache_update() {
+        let (storage, profile, whoami, project) = test::setup::profile();
+        let cobs = Store::new(whoami, profile.paths(), &storage);
+        let cache = Cache::open(profile.paths());
+        let issues = cobs.issues();
+        let project = project.urn();
+
+        // Writes go through the store, which keeps the index up to date.
+        let issue_id = issues
+            .create(&project, "My first issue", "Blah blah blah.", &[])
+            .unwrap();
+        issues.comment(&project, &issue_id, "Ho ho ho.").unwrap();
+
+        let path = cache.path(&project, &crate::cobs::issue::TYPENAME);
+        let index: Index<Issue> = cache.read(&path).unwrap();
+        let entry = index.get(&issue_id.to_string()).unwrap();
+
+        assert_eq!(entry.object.comments().len(), 1);
+        assert_eq!(
+            &entry.tips,
+            cache
+                .tips::<Issue>(&project)
+                .unwrap()
+                .get(&issue_id)
+                .unwrap()
+        );
+    }
+}
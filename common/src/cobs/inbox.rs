@@ -0,0 +1,700 @@
+//! Notification inbox for collaborative object changes.
+//!
+//! For every object, the inbox remembers the history tips and the changes that were
+//! last seen. Changes that weren't seen yet, and weren't made by us, are unread
+//! notifications. State is kept per profile, under the COB cache directory.
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use librad::collaborative_objects::{ObjectId, TypeName};
+use librad::git::Urn;
+use librad::paths::Paths;
+use librad::PeerId;
+
+use crate::cobs::cache::Tips;
+use crate::cobs::issue::{self, Issue};
+use crate::cobs::patch::{self, Patch, RevisionIx, Verdict};
+use crate::cobs::search::Kind;
+use crate::cobs::shared::{self, Author, Cob, Store, Timestamp};
+
+/// Name of the inbox directory, under the COB cache directory.
+pub const INBOX_DIR: &str = "inbox";
+/// Version of the inbox state.
+const VERSION: u32 = 2;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("i/o error: {0}")]
+    Io(#[from] io::Error),
+    #[error("inbox decoding error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Store(#[from] shared::Error),
+}
+
+/// A change to an object.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum Change {
+    /// The object was opened.
+    Opened,
+    /// A comment was added.
+    Comment { body: String },
+    /// A reply was added to a comment.
+    Reply { body: String },
+    /// A patch revision was added.
+    Revision { revision: RevisionIx },
+    /// A patch revision was reviewed.
+    Review {
+        revision: RevisionIx,
+        verdict: Option<Verdict>,
+    },
+    /// A patch revision was merged.
+    Merge { revision: RevisionIx },
+    /// The state of the object changed.
+    State { state: String },
+}
+
+impl Change {
+    /// The comment body of this change, if any.
+    pub fn body(&self) -> Option<&str> {
+        match self {
+            Self::Comment { body } | Self::Reply { body } => Some(body),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Opened => write!(f, "opened"),
+            Self::Comment { .. } => write!(f, "commented"),
+            Self::Reply { .. } => write!(f, "replied"),
+            Self::Revision { revision } => write!(f, "updated to R{}", revision),
+            Self::Review {
+                revision,
+                verdict: Some(verdict),
+            } => write!(f, "reviewed R{} ({})", revision, verdict),
+            Self::Review { revision, .. } => write!(f, "reviewed R{}", revision),
+            Self::Merge { revision } => write!(f, "merged R{}", revision),
+            Self::State { state } => write!(f, "marked as {}", state),
+        }
+    }
+}
+
+/// An unread change.
+#[derive(Debug, Clone, Serialize)]
+pub struct Notification {
+    /// Object kind.
+    pub kind: Kind,
+    /// Object id.
+    pub id: ObjectId,
+    /// Object title.
+    pub title: String,
+    /// What changed.
+    pub change: Change,
+    /// Who made the change, if known.
+    pub author: Option<Author>,
+    /// When the change was made. For state changes that weren't recorded, this is
+    /// when the object was created.
+    pub timestamp: Timestamp,
+    /// Whether the change mentions us.
+    pub mention: bool,
+    /// Peer that made the change, if known. Merges only record a peer.
+    #[serde(skip)]
+    peer: Option<PeerId>,
+    /// Key identifying the change within its object. Keys don't depend on the
+    /// position of a change, so that concurrent changes can't shift them.
+    #[serde(skip)]
+    key: String,
+}
+
+impl Notification {
+    /// Whether the change was made by us, either by our identity or our device.
+    fn is_own(&self, whoami: &Urn, peer: &PeerId) -> bool {
+        self.author.as_ref().map_or(false, |a| a.urn() == whoami)
+            || self.peer.as_ref() == Some(peer)
+    }
+}
+
+/// What was last seen of an object.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Seen {
+    tips: Tips,
+    changes: BTreeSet<String>,
+}
+
+/// Everything seen of the objects of a given type, under a given namespace.
+type Index = BTreeMap<String, Seen>;
+
+/// Objects that can be turned into notifications.
+trait Notify: Cob + Clone + Serialize + DeserializeOwned {
+    const KIND: Kind;
+
+    /// All changes made to the object so far.
+    fn notifications(&self, id: &ObjectId) -> Vec<Notification>;
+}
+
+/// Persistent notification inbox.
+#[derive(Debug, Clone)]
+pub struct Inbox {
+    root: PathBuf,
+}
+
+impl Inbox {
+    /// Open the inbox for the given profile paths.
+    pub fn open(paths: &Paths) -> Self {
+        Self {
+            root: paths.cob_cache_dir().join(INBOX_DIR),
+        }
+    }
+
+    /// Get the unread notifications of a project, oldest first.
+    ///
+    /// The first time a project is looked at, everything in it is marked as read,
+    /// so that only changes from then on show up.
+    pub fn unread(&self, store: &Store, project: &Urn) -> Result<Vec<Notification>, Error> {
+        let mut unread = self.unread_of::<Issue>(store, project)?;
+        unread.extend(self.unread_of::<Patch>(store, project)?);
+        unread.sort_by_key(|n| n.timestamp);
+
+        Ok(unread)
+    }
+
+    /// Mark notifications of a project as read. If an object id is given, only that
+    /// object's notifications are marked. Returns the number of notifications marked.
+    pub fn mark_read(
+        &self,
+        store: &Store,
+        project: &Urn,
+        id: Option<&ObjectId>,
+    ) -> Result<usize, Error> {
+        let marked = self.mark_read_of::<Issue>(store, project, id)?
+            + self.mark_read_of::<Patch>(store, project, id)?;
+
+        Ok(marked)
+    }
+
+    /// Remove all inbox state.
+    pub fn clear(&self) -> Result<(), Error> {
+        match fs::remove_dir_all(&self.root) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn unread_of<T: Notify>(
+        &self,
+        store: &Store,
+        project: &Urn,
+    ) -> Result<Vec<Notification>, Error> {
+        let path = self.path(project, T::type_name());
+        let mut index = match self.read(&path) {
+            Some(index) => index,
+            None => {
+                self.mark_read_of::<T>(store, project, None)?;
+                return Ok(vec![]);
+            }
+        };
+        let tips = store
+            .cache()
+            .tips::<T>(project)
+            .map_err(shared::Error::from)?;
+        let whoami = store.whoami.urn();
+        let mut unread = Vec::new();
+
+        for (id, obj) in store.all::<T>(project)? {
+            let seen = index.entry(id.to_string()).or_default();
+
+            // Nothing changed since we last looked.
+            if tips.get(&id) == Some(&seen.tips) {
+                continue;
+            }
+            for mut n in obj.notifications(&id) {
+                if seen.changes.contains(&n.key) {
+                    continue;
+                }
+                if n.is_own(&whoami, &store.peer_id) {
+                    continue;
+                }
+                n.mention = n
+                    .change
+                    .body()
+                    .map_or(false, |body| mentions(body, &whoami, &store.peer_id));
+
+                unread.push(n);
+            }
+        }
+        Ok(unread)
+    }
+
+    fn mark_read_of<T: Notify>(
+        &self,
+        store: &Store,
+        project: &Urn,
+        only: Option<&ObjectId>,
+    ) -> Result<usize, Error> {
+        let path = self.path(project, T::type_name());
+        let mut index = self.read(&path).unwrap_or_default();
+        let tips = store
+            .cache()
+            .tips::<T>(project)
+            .map_err(shared::Error::from)?;
+        let whoami = store.whoami.urn();
+        let mut marked = 0;
+
+        for (id, obj) in store.all::<T>(project)? {
+            if only.map_or(false, |only| only != &id) {
+                continue;
+            }
+            let seen = index.entry(id.to_string()).or_default();
+            let notifications = obj.notifications(&id);
+
+            // Our own changes were never unread, so they don't count.
+            marked += notifications
+                .iter()
+                .filter(|n| !seen.changes.contains(&n.key) && !n.is_own(&whoami, &store.peer_id))
+                .count();
+            seen.changes = notifications.into_iter().map(|n| n.key).collect();
+            seen.tips = tips.get(&id).cloned().unwrap_or_default();
+        }
+        // Forget about objects that no longer exist.
+        index.retain(|id, _| ObjectId::from_str(id).map_or(false, |id| tips.contains_key(&id)));
+
+        self.write(&path, &index)?;
+
+        Ok(marked)
+    }
+
+    fn path(&self, namespace: &Urn, typename: &TypeName) -> PathBuf {
+        // The version is bumped whenever notification keys change, so that inboxes
+        // with outdated keys start over instead of showing everything as unread.
+        self.root
+            .join(namespace.encode_id())
+            .join(format!("{}.v{}.json", typename, VERSION))
+    }
+
+    fn read(&self, path: &Path) -> Option<Index> {
+        let bytes = fs::read(path).ok()?;
+
+        serde_json::from_slice(&bytes).ok()
+    }
+
+    fn write(&self, path: &Path, index: &Index) -> Result<(), Error> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let tmp = path.with_extension("json.tmp");
+
+        fs::write(&tmp, serde_json::to_vec(index)?)?;
+        fs::rename(&tmp, path)?;
+
+        Ok(())
+    }
+}
+
+impl Notify for Issue {
+    const KIND: Kind = Kind::Issue;
+
+    fn notifications(&self, id: &ObjectId) -> Vec<Notification> {
+        let mut ns = Vec::new();
+        let mut push = |key: String, change, author: Option<&Author>, timestamp| {
+            ns.push(Notification {
+                kind: Self::KIND,
+                id: *id,
+                title: self.title.clone(),
+                change,
+                author: author.cloned(),
+                timestamp,
+                mention: false,
+                peer: author.map(|a| a.peer),
+                key,
+            });
+        };
+
+        push(
+            "opened".to_owned(),
+            Change::Opened,
+            Some(&self.author),
+            self.timestamp,
+        );
+        for comment in self.discussion.iter() {
+            let comment_key = format!("comment/{}", comment.id);
+
+            push(
+                comment_key.clone(),
+                Change::Comment {
+                    body: comment.body.clone(),
+                },
+                Some(&comment.author),
+                comment.timestamp,
+            );
+            for reply in comment.replies.iter() {
+                push(
+                    format!("{}/reply/{}", comment_key, reply.id),
+                    Change::Reply {
+                        body: reply.body.clone(),
+                    },
+                    Some(&reply.author),
+                    reply.timestamp,
+                );
+            }
+        }
+        let state = match self.state {
+            issue::State::Open => "open",
+            issue::State::Closed { .. } => "closed",
+        };
+        match &self.state_change {
+            Some(change) => push(
+                format!("state/{}", change.id),
+                Change::State {
+                    state: state.to_owned(),
+                },
+                Some(&change.author),
+                change.timestamp,
+            ),
+            // Older issues don't record who changed their state, or when.
+            None if state == "closed" => push(
+                "state/closed".to_owned(),
+                Change::State {
+                    state: state.to_owned(),
+                },
+                None,
+                self.timestamp,
+            ),
+            None => {}
+        }
+        ns
+    }
+}
+
+impl Notify for Patch {
+    const KIND: Kind = Kind::Patch;
+
+    fn notifications(&self, id: &ObjectId) -> Vec<Notification> {
+        let mut ns = Vec::new();
+        let mut push = |key: String, change, author: Option<&Author>, timestamp| {
+            ns.push(Notification {
+                kind: Self::KIND,
+                id: *id,
+                title: self.title.clone(),
+                change,
+                author: author.cloned(),
+                timestamp,
+                mention: false,
+                peer: author.map(|a| a.peer),
+                key,
+            });
+        };
+
+        let mut merges = Vec::new();
+
+        push(
+            "opened".to_owned(),
+            Change::Opened,
+            Some(&self.author),
+            self.timestamp,
+        );
+        for (r, revision) in self.revisions.iter().enumerate() {
+            let prefix = format!("revision/{}", revision.id);
+
+            // The first revision is part of opening the patch.
+            if r > 0 {
+                push(
+                    prefix.clone(),
+                    Change::Revision { revision: r },
+                    Some(&revision.comment.author),
+                    revision.timestamp,
+                );
+            }
+            for comment in revision.discussion.iter() {
+                let comment_key = format!("{}/comment/{}", prefix, comment.id);
+
+                push(
+                    comment_key.clone(),
+                    Change::Comment {
+                        body: comment.body.clone(),
+                    },
+                    Some(&comment.author),
+                    comment.timestamp,
+                );
+                for reply in comment.replies.iter() {
+                    push(
+                        format!("{}/reply/{}", comment_key, reply.id),
+                        Change::Reply {
+                            body: reply.body.clone(),
+                        },
+                        Some(&reply.author),
+                        reply.timestamp,
+                    );
+                }
+            }
+            // Reviews are replaced when updated, so the comment id tells versions apart.
+            for review in revision.reviews.values() {
+                push(
+                    format!("{}/review/{}", prefix, review.comment.id),
+                    Change::Review {
+                        revision: r,
+                        verdict: review.verdict,
+                    },
+                    Some(&review.author),
+                    review.timestamp,
+                );
+            }
+            // Merges only record the peer that merged, not its identity.
+            for merge in revision.merges.iter() {
+                merges.push(Notification {
+                    kind: Self::KIND,
+                    id: *id,
+                    title: self.title.clone(),
+                    change: Change::Merge { revision: r },
+                    author: None,
+                    timestamp: merge.timestamp,
+                    mention: false,
+                    peer: Some(merge.peer),
+                    key: format!("{}/merge/{}/{}", prefix, merge.peer, merge.commit),
+                });
+            }
+        }
+        // Archiving isn't recorded with an author or time, so it can only be
+        // notified once.
+        if let patch::State::Archived = self.state {
+            push(
+                "state/archived".to_owned(),
+                Change::State {
+                    state: "archived".to_owned(),
+                },
+                None,
+                self.timestamp,
+            );
+        }
+        ns.extend(merges);
+        ns
+    }
+}
+
+/// Check whether a comment body mentions the given identity, either by URN or peer id.
+pub fn mentions(body: &str, urn: &Urn, peer: &PeerId) -> bool {
+    body.contains(&urn.encode_id()) || body.contains(&peer.default_encoding())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test;
+
+    #[test]
+    fn test_mentions() {
+        let urn = Urn::from_str("rad:git:hnrkyghsrokxzxpy9pww69xr11dr9q7edbxfo").unwrap();
+        let peer =
+            PeerId::from_str("hyb5to4rshftx4apgmu9s6wnsp4ddmp1mz6ijh4qqey7fb8wrpawxa").unwrap();
+
+        assert!(mentions(
+            "Ping @rad:git:hnrkyghsrokxzxpy9pww69xr11dr9q7edbxfo",
+            &urn,
+            &peer
+        ));
+        assert!(mentions(
+            "cc hyb5to4rshftx4apgmu9s6wnsp4ddmp1mz6ijh4qqey7fb8wrpawxa",
+            &urn,
+            &peer
+        ));
+        assert!(!mentions("Nobody here", &urn, &peer));
+    }
+
+    #[test]
+    fn test_unread() {
+        let (storage, profile, whoami, project) = test::setup::profile();
+        let cobs = Store::new(whoami, profile.paths(), &storage);
+        let inbox = Inbox::open(profile.paths());
+        let issues = cobs.issues();
+        let project = project.urn();
+
+        let issue_id = issues
+            .create(&project, "My first issue", "Blah blah blah.", &[])
+            .unwrap();
+
+        // The first look initializes the inbox.
+        assert!(inbox.unread(&cobs, &project).unwrap().is_empty());
+
+        issues
+            .lifecycle(
+                &project,
+                &issue_id,
+                issue::State::Closed {
+                    reason: issue::CloseReason::Solved,
+                },
+            )
+            .unwrap();
+        // Our own changes never show up.
+        issues.comment(&project, &issue_id, "Ho ho ho.").unwrap();
+
+        assert!(inbox.unread(&cobs, &project).unwrap().is_empty());
+        assert_eq!(
+            inbox.mark_read(&cobs, &project, Some(&issue_id)).unwrap(),
+            0
+        );
+        assert!(inbox.unread(&cobs, &project).unwrap().is_empty());
+    }
+
+    /// Add a comment to an issue, as if it was made by someone else.
+    fn comment_as(cobs: &Store, project: &Urn, issue_id: &ObjectId, author: &Author, body: &str) {
+        use automerge::transaction::{CommitOptions, Transactable};
+        use automerge::{AutomergeError, ObjId, ObjType};
+        use librad::collaborative_objects::{EntryContents, UpdateObjectSpec};
+
+        let mut doc = cobs.issues().get_raw(project, issue_id).unwrap().unwrap();
+        doc.transact_with::<_, _, AutomergeError, _, ()>(
+            |_| CommitOptions::default(),
+            |tx| {
+                let (_, obj_id) = tx.get(ObjId::Root, "issue")?.unwrap();
+                let (_, discussion_id) = tx.get(&obj_id, "discussion")?.unwrap();
+                let comment =
+                    tx.insert_object(&discussion_id, tx.length(&discussion_id), ObjType::Map)?;
+
+                tx.put(&comment, "author", author.urn().to_string())?;
+                tx.put(&comment, "peer", author.peer.default_encoding())?;
+                tx.put(&comment, "body", body)?;
+                // All comments are made in the same second.
+                tx.put(&comment, "timestamp", Timestamp::new(1))?;
+                tx.put_object(&comment, "replies", ObjType::List)?;
+                tx.put_object(&comment, "reactions", ObjType::Map)?;
+
+                Ok(())
+            },
+        )
+        .unwrap();
+        let change = doc.get_last_local_change().unwrap().raw_bytes().to_vec();
+
+        cobs.update(
+            &cobs.whoami,
+            project,
+            UpdateObjectSpec {
+                object_id: *issue_id,
+                typename: issue::TYPENAME.clone(),
+                message: Some("Add comment".to_owned()),
+                changes: EntryContents::Automerge(change),
+            },
+        )
+        .unwrap();
+        cobs.reindex::<Issue>(project, issue_id);
+    }
+
+    #[test]
+    fn test_unread_others() {
+        let (storage, profile, whoami, project) = test::setup::profile();
+        let cobs = Store::new(whoami, profile.paths(), &storage);
+        let inbox = Inbox::open(profile.paths());
+        let issues = cobs.issues();
+        let project = project.urn();
+        let other = Author::new(
+            Urn::from_str("rad:git:hnrkyghsrokxzxpy9pww69xr11dr9q7edbxfo").unwrap(),
+            PeerId::from_str("hyb5to4rshftx4apgmu9s6wnsp4ddmp1mz6ijh4qqey7fb8wrpawxa").unwrap(),
+        );
+        let issue_id = issues
+            .create(&project, "My first issue", "Blah blah blah.", &[])
+            .unwrap();
+
+        // The first look initializes the inbox.
+        assert!(inbox.unread(&cobs, &project).unwrap().is_empty());
+
+        let mention = format!("Ping @{}", cobs.whoami.urn().encode_id());
+        comment_as(&cobs, &project, &issue_id, &other, "Ho ho ho.");
+        comment_as(&cobs, &project, &issue_id, &other, &mention);
+        issues.comment(&project, &issue_id, "Hi hi hi.").unwrap();
+
+        // Both comments show up, even though they were made by the same author in
+        // the same second. Our own comment doesn't.
+        let unread = inbox.unread(&cobs, &project).unwrap();
+        let mut bodies = unread
+            .iter()
+            .map(|n| (n.change.body().unwrap(), n.mention))
+            .collect::<Vec<_>>();
+        bodies.sort();
+
+        assert_eq!(bodies, vec![("Ho ho ho.", false), (mention.as_str(), true)]);
+        assert!(unread
+            .iter()
+            .all(|n| n.author.as_ref().map(|a| a.urn()) == Some(other.urn())));
+
+        assert_eq!(inbox.mark_read(&cobs, &project, None).unwrap(), 2);
+        assert!(inbox.unread(&cobs, &project).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_notifications() {
+        let (storage, profile, whoami, project) = test::setup::profile();
+        let cobs = Store::new(whoami, profile.paths(), &storage);
+        let issues = cobs.issues();
+        let project = project.urn();
+        let whoami = cobs.whoami.urn();
+        let other = Author::new(
+            Urn::from_str("rad:git:hnrkyghsrokxzxpy9pww69xr11dr9q7edbxfo").unwrap(),
+            PeerId::from_str("hyb5to4rshftx4apgmu9s6wnsp4ddmp1mz6ijh4qqey7fb8wrpawxa").unwrap(),
+        );
+
+        let issue_id = issues
+            .create(&project, "My first issue", "Blah blah blah.", &[])
+            .unwrap();
+        issues.comment(&project, &issue_id, "Ho ho ho.").unwrap();
+        issues
+            .lifecycle(
+                &project,
+                &issue_id,
+                issue::State::Closed {
+                    reason: issue::CloseReason::Solved,
+                },
+            )
+            .unwrap();
+
+        let mut issue = issues.get(&project, &issue_id).unwrap().unwrap();
+        let ns = issue.notifications(&issue_id);
+        let state = ns
+            .iter()
+            .find(|n| matches!(n.change, Change::State { .. }))
+            .unwrap();
+
+        // State changes are attributed to whoever made them.
+        assert!(state.is_own(&whoami, &cobs.peer_id));
+        assert_eq!(state.author.as_ref().map(|a| a.urn()), Some(&whoami));
+
+        // A comment made concurrently by someone else doesn't change the other keys.
+        let keys = ns.iter().map(|n| n.key.clone()).collect::<BTreeSet<_>>();
+        issue.discussion.insert(
+            0,
+            shared::Comment {
+                id: "1@other".to_owned(),
+                ..shared::Comment::new(other.clone(), "Hi!".to_owned(), Timestamp::new(1))
+            },
+        );
+        let concurrent = issue
+            .notifications(&issue_id)
+            .into_iter()
+            .map(|n| n.key)
+            .collect::<BTreeSet<_>>();
+        assert!(concurrent.is_superset(&keys));
+        assert_eq!(concurrent.len(), keys.len() + 1);
+
+        // Closing again, by someone else, is a new change.
+        let closed = state.key.clone();
+        issue.state_change = Some(shared::StateChange {
+            id: "2@other".to_owned(),
+            author: other,
+            timestamp: Timestamp::new(u32::MAX as u64),
+        });
+        let state = issue
+            .notifications(&issue_id)
+            .into_iter()
+            .find(|n| matches!(n.change, Change::State { .. }))
+            .unwrap();
+        assert_ne!(state.key, closed);
+        assert!(!state.is_own(&whoami, &cobs.peer_id));
+    }
+}
@@ -1,6 +1,6 @@
 #![allow(clippy::large_enum_variant)]
 use std::collections::{HashMap, HashSet};
-use std::convert::{TryFrom, TryInto};
+use std::convert::TryFrom;
 use std::ops::{ControlFlow, Deref};
 use std::str::FromStr;
 
@@ -16,13 +16,22 @@ use librad::git::identities::local::LocalIdentity;
 use librad::git::storage::ReadOnly;
 use librad::git::Urn;
 
+use crate::cobs::policy;
 use crate::cobs::shared;
 use crate::cobs::shared::*;
+use crate::cobs::user;
 
 lazy_static! {
     pub static ref TYPENAME: TypeName = FromStr::from_str("xyz.radicle.issue").unwrap();
 }
 
+/// Schema migrations of issue documents. The latest version is written on creation.
+pub static MIGRATIONS: Migrations = Migrations::new(&[Migration {
+    from: 0,
+    description: "Add version marker",
+    migrate: shared::unversioned,
+}]);
+
 /// Identifier for an issue.
 pub type IssueId = ObjectId;
 
@@ -90,6 +99,9 @@ pub struct Issue {
     pub discussion: Discussion,
     pub labels: HashSet<Label>,
     pub timestamp: Timestamp,
+    /// The last change of state, if it was recorded.
+    #[serde(default)]
+    pub state_change: Option<StateChange>,
 }
 
 impl Issue {
@@ -125,6 +137,10 @@ impl Issue {
         self.timestamp
     }
 
+    pub fn state_change(&self) -> Option<&StateChange> {
+        self.state_change.as_ref()
+    }
+
     pub fn resolve<S: AsRef<ReadOnly>>(&mut self, storage: &S) -> Result<(), ResolveError> {
         self.author.resolve(storage)?;
         self.comment.resolve(storage)?;
@@ -142,7 +158,7 @@ impl Cob for Issue {
     }
 
     fn from_history(history: &History) -> Result<Self, anyhow::Error> {
-        let doc = history.traverse(Automerge::new(), |mut doc, entry| {
+        let mut doc = history.traverse(Automerge::new(), |mut doc, entry| {
             match entry.contents() {
                 EntryContents::Automerge(bytes) => {
                     match automerge::Change::from_bytes(bytes.clone()) {
@@ -157,25 +173,23 @@ impl Cob for Issue {
             }
             ControlFlow::Continue(doc)
         });
+        shared::migrate(&mut doc, &TYPENAME)?;
         let issue = Issue::try_from(doc)?;
 
         Ok(issue)
     }
-}
-
-impl TryFrom<&History> for Issue {
-    type Error = anyhow::Error;
 
-    fn try_from(history: &History) -> Result<Self, Self::Error> {
-        Issue::from_history(history)
+    fn from_history_authorized(
+        history: &History,
+        ctx: &policy::Context,
+    ) -> Result<(Self, Vec<policy::Violation>), anyhow::Error> {
+        policy::replay(history, ctx)
     }
 }
 
-impl TryFrom<Automerge> for Issue {
-    type Error = DocumentError;
-
-    fn try_from(doc: Automerge) -> Result<Self, Self::Error> {
-        let doc = Document::new(&doc);
+impl policy::Authorize for Issue {
+    fn from_doc(doc: &Automerge) -> Result<Self, DocumentError> {
+        let doc = Document::new(doc);
         let (_obj, obj_id) = doc.get(automerge::ObjId::Root, "issue")?;
         let title = doc.val(&obj_id, "title")?;
         let (_, comment_id) = doc.get(&obj_id, "comment")?;
@@ -189,6 +203,8 @@ impl TryFrom<Automerge> for Issue {
         let comment = shared::lookup::comment(doc, &comment_id)?;
         let discussion: Discussion = doc.list(&obj_id, "discussion", shared::lookup::thread)?;
         let labels: HashSet<Label> = doc.keys(&obj_id, "labels")?;
+        // Older issues don't record who changed their state.
+        let state_change = doc.lookup_opt(&obj_id, "stateChange", shared::lookup::state_change)?;
 
         Ok(Self {
             title,
@@ -198,8 +214,55 @@ impl TryFrom<Automerge> for Issue {
             discussion,
             labels,
             timestamp,
+            state_change,
         })
     }
+
+    fn authorize(
+        old: &Self,
+        new: &Self,
+        actor: &Urn,
+        ctx: &policy::Context,
+    ) -> Result<(), policy::Action> {
+        let author = old.author.urn();
+
+        // Rules that depend on the author would be meaningless if it could be changed.
+        if old.author.urn() != new.author.urn() || old.author.peer != new.author.peer {
+            return Err(policy::Action::Author);
+        }
+        if old.state != new.state && !ctx.policy.issue_state.allows(actor, author, ctx) {
+            return Err(policy::Action::IssueState);
+        }
+        // A state change can only be recorded by whoever made it.
+        if old.state_change != new.state_change
+            && new
+                .state_change
+                .as_ref()
+                .map_or(false, |c| c.author.urn() != actor)
+        {
+            return Err(policy::Action::IssueState);
+        }
+        if old.labels != new.labels && !ctx.policy.issue_labels.allows(actor, author, ctx) {
+            return Err(policy::Action::IssueLabels);
+        }
+        Ok(())
+    }
+}
+
+impl TryFrom<&History> for Issue {
+    type Error = anyhow::Error;
+
+    fn try_from(history: &History) -> Result<Self, Self::Error> {
+        Issue::from_history(history)
+    }
+}
+
+impl TryFrom<Automerge> for Issue {
+    type Error = DocumentError;
+
+    fn try_from(doc: Automerge) -> Result<Self, Self::Error> {
+        <Self as policy::Authorize>::from_doc(&doc)
+    }
 }
 
 pub struct IssueStore<'a> {
@@ -229,8 +292,16 @@ impl<'a> IssueStore<'a> {
         let author = self.author();
         let timestamp = Timestamp::now();
         let history = events::create(&author, title, description, timestamp, labels)?;
+        let id = cobs::create(history, project, &self.whoami, self.store)?;
+        self.store.reindex::<Issue>(project, &id);
+
+        self.store.record(user::Event::IssueOpened {
+            project: project.clone(),
+            issue: id,
+            title: title.trim().to_owned(),
+        });
 
-        cobs::create(history, project, &self.whoami, self.store)
+        Ok(id)
     }
 
     pub fn remove(&self, _project: &Urn, _issue_id: &IssueId) -> Result<(), Error> {
@@ -255,14 +326,20 @@ impl<'a> IssueStore<'a> {
                 },
             )
             .unwrap();
+        self.store.reindex::<Issue>(project, issue_id);
+
+        self.store.record(user::Event::IssueCommented {
+            project: project.clone(),
+            issue: *issue_id,
+        });
 
         Ok(*cob.id()) // TODO: Return something other than doc id.
     }
 
     pub fn lifecycle(&self, project: &Urn, issue_id: &IssueId, state: State) -> Result<(), Error> {
-        let author = self.whoami.urn();
+        let change = StateChange::new(self.author(), Timestamp::now());
         let mut issue = self.get_raw(project, issue_id)?.unwrap();
-        let changes = events::lifecycle(&mut issue, &author, state)?;
+        let changes = events::lifecycle(&mut issue, state, &change)?;
         let _cob = self
             .store
             .update(
@@ -276,6 +353,7 @@ impl<'a> IssueStore<'a> {
                 },
             )
             .unwrap();
+        self.store.reindex::<Issue>(project, issue_id);
 
         Ok(())
     }
@@ -297,6 +375,7 @@ impl<'a> IssueStore<'a> {
                 },
             )
             .unwrap();
+        self.store.reindex::<Issue>(project, issue_id);
 
         Ok(())
     }
@@ -324,6 +403,7 @@ impl<'a> IssueStore<'a> {
                 },
             )
             .unwrap();
+        self.store.reindex::<Issue>(project, issue_id);
 
         Ok(())
     }
@@ -352,27 +432,30 @@ impl<'a> IssueStore<'a> {
                 },
             )
             .unwrap();
+        self.store.reindex::<Issue>(project, issue_id);
 
         Ok(())
     }
 
     pub fn all(&self, project: &Urn) -> Result<Vec<(IssueId, Issue)>, Error> {
-        let cobs = self.store.list(project, &TYPENAME)?;
+        let mut issues = self.store.all::<Issue>(project)?;
+        issues.sort_by_key(|(_, i)| i.timestamp);
 
-        let mut issues = Vec::new();
-        for cob in cobs {
-            let issue: Result<Issue, _> = cob.history().try_into();
-            issues.push((*cob.id(), issue.unwrap()));
-        }
+        Ok(issues)
+    }
+
+    /// Get the issues in the given state, oldest first.
+    pub fn filter(&self, project: &Urn, state: State) -> Result<Vec<(IssueId, Issue)>, Error> {
+        let mut issues = self
+            .store
+            .filter::<Issue, _>(project, |i| i.state() == state)?;
         issues.sort_by_key(|(_, i)| i.timestamp);
 
         Ok(issues)
     }
 
     pub fn count(&self, project: &Urn) -> Result<usize, Error> {
-        let cobs = self.store.list(project, &TYPENAME)?;
-
-        Ok(cobs.len())
+        self.store.count::<Issue>(project)
     }
 
     pub fn get(&self, namespace: &Urn, id: &ObjectId) -> anyhow::Result<Option<Issue>> {
@@ -452,6 +535,7 @@ mod events {
                 |_| CommitOptions::default().with_message("Create issue".to_owned()),
                 |tx| {
                     let issue = tx.put_object(ObjId::Root, "issue", ObjType::Map)?;
+                    tx.put(ObjId::Root, VERSION_KEY, MIGRATIONS.latest())?;
 
                     tx.put(&issue, "title", title)?;
                     tx.put(&issue, "author", author.urn().to_string())?;
@@ -519,8 +603,8 @@ mod events {
 
     pub fn lifecycle(
         issue: &mut Automerge,
-        _author: &Urn,
         state: State,
+        change: &StateChange,
     ) -> Result<EntryContents, AutomergeError> {
         issue
             .transact_with::<_, _, AutomergeError, _, ()>(
@@ -529,7 +613,7 @@ mod events {
                     let (_, obj_id) = tx.get(ObjId::Root, "issue")?.unwrap();
                     tx.put(&obj_id, "state", state)?;
 
-                    // TODO: Record who changed the state of the issue.
+                    change.put(tx, &obj_id)?;
 
                     Ok(())
                 },
@@ -670,6 +754,102 @@ mod test {
         assert!(issue.timestamp() >= timestamp);
     }
 
+    #[test]
+    fn test_issue_migrate_unversioned() {
+        use automerge::transaction::{CommitOptions, Transactable};
+
+        let (storage, profile, whoami, project) = test::setup::profile();
+        let cobs = Store::new(whoami, profile.paths(), &storage);
+        let issues = cobs.issues();
+        let issue_id = issues
+            .create(&project.urn(), "My first issue", "Blah blah blah.", &[])
+            .unwrap();
+        let mut doc = issues.get_raw(&project.urn(), &issue_id).unwrap().unwrap();
+
+        assert_eq!(shared::version(&doc).unwrap(), MIGRATIONS.latest());
+
+        // Simulate a document created before versioning.
+        doc.transact_with::<_, _, AutomergeError, _, ()>(
+            |_| CommitOptions::default(),
+            |tx| tx.delete(automerge::ObjId::Root, VERSION_KEY),
+        )
+        .unwrap();
+        assert_eq!(shared::version(&doc).unwrap(), 0);
+        assert_eq!(shared::migrate(&mut doc, &TYPENAME).unwrap(), 0);
+        assert_eq!(shared::version(&doc).unwrap(), MIGRATIONS.latest());
+
+        let issue = Issue::try_from(doc).unwrap();
+        assert_eq!(issue.title(), "My first issue");
+    }
+
+    #[test]
+    fn test_issue_migrate_fixtures() {
+        // Issues created before versioning.
+        let mut doc = test::fixture("issue-v0.bin");
+
+        assert_eq!(shared::version(&doc).unwrap(), 0);
+        assert_eq!(shared::migrate(&mut doc, &TYPENAME).unwrap(), 0);
+        assert_eq!(shared::version(&doc).unwrap(), MIGRATIONS.latest());
+
+        let issue = Issue::try_from(doc).unwrap();
+        assert_eq!(issue.title(), "My first issue");
+        assert_eq!(issue.description(), "Blah blah blah.");
+        assert_eq!(issue.state(), State::Open);
+        assert_eq!(issue.comments().len(), 1);
+        assert_eq!(issue.comments()[0].body, "Ho ho ho.");
+        assert!(issue.labels().contains(&Label::new("bug").unwrap()));
+    }
+
+    #[test]
+    fn test_issue_unauthorized_state() {
+        let (storage, profile, whoami, project) = test::setup::profile();
+        let author = whoami.urn();
+        let cobs = Store::new(whoami, profile.paths(), &storage);
+        let issues = cobs.issues();
+        let issue_id = issues
+            .create(&project.urn(), "My first issue", "Blah blah blah.", &[])
+            .unwrap();
+
+        issues
+            .lifecycle(
+                &project.urn(),
+                &issue_id,
+                State::Closed {
+                    reason: CloseReason::Other,
+                },
+            )
+            .unwrap();
+
+        let cob = cobs
+            .retrieve(&project.urn(), &TYPENAME, &issue_id)
+            .unwrap()
+            .unwrap();
+
+        // The author may close their own issue by default.
+        let ctx = policy::Context::default();
+        let (issue, violations) = Issue::from_history_authorized(cob.history(), &ctx).unwrap();
+        assert!(violations.is_empty());
+        assert!(matches!(issue.state(), State::Closed { .. }));
+
+        // Only delegates may change the state, and there are none.
+        let ctx = policy::Context {
+            policy: policy::Policy {
+                issue_state: policy::Rule::Delegates,
+                ..policy::Policy::default()
+            },
+            delegates: HashSet::new(),
+        };
+        let (issue, violations) = Issue::from_history_authorized(cob.history(), &ctx).unwrap();
+        assert_eq!(issue.state(), State::Open);
+        assert_eq!(
+            violations,
+            vec![policy::Violation {
+                actor: author,
+                action: policy::Action::IssueState,
+            }]
+        );
+    }
+
     #[test]
     fn test_issue_create_and_change_state() {
         let (storage, profile, whoami, project) = test::setup::profile();
@@ -914,4 +1094,181 @@ mod test {
         assert_eq!(issues[1].1.title(), "My second issue");
         assert_eq!(issues[2].1.title(), "My third issue");
     }
+
+    #[test]
+    fn test_issue_filter() {
+        let (storage, profile, whoami, project) = test::setup::profile();
+        let cobs = Store::new(whoami, profile.paths(), &storage);
+        let issues = cobs.issues();
+        let project = project.urn();
+
+        let open = issues
+            .create(&project, "My open issue", "Blah blah blah.", &[])
+            .unwrap();
+        let solved = issues
+            .create(&project, "My solved issue", "Blah blah blah.", &[])
+            .unwrap();
+        issues
+            .lifecycle(
+                &project,
+                &solved,
+                State::Closed {
+                    reason: CloseReason::Solved,
+                },
+            )
+            .unwrap();
+
+        let filtered = issues.filter(&project, State::Open).unwrap();
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered[0].0, open);
+
+        let filtered = issues
+            .filter(
+                &project,
+                State::Closed {
+                    reason: CloseReason::Solved,
+                },
+            )
+            .unwrap();
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered[0].0, solved);
+
+        let filtered = issues
+            .filter(
+                &project,
+                State::Closed {
+                    reason: CloseReason::Other,
+                },
+            )
+            .unwrap();
+        assert!(filtered.is_empty());
+    }
+
+    #[test]
+    fn test_issue_unauthorized_author() {
+        use automerge::transaction::{CommitOptions, Transactable};
+
+        let (storage, profile, whoami, project) = test::setup::profile();
+        let cobs = Store::new(whoami, profile.paths(), &storage);
+        let issues = cobs.issues();
+        let project = project.urn();
+        let other = Urn::from_str("rad:git:hnrkyghsrokxzxpy9pww69xr11dr9q7edbxfo").unwrap();
+
+        // An issue opened by someone else.
+        let issue_id = cobs::create(
+            events::create(
+                &Author::new(other.clone(), *storage.peer_id()),
+                "My first issue",
+                "Blah blah blah.",
+                Timestamp::now(),
+                &[],
+            )
+            .unwrap(),
+            &project,
+            &cobs.whoami,
+            issues.store,
+        )
+        .unwrap();
+
+        // Claim the issue, then close it.
+        let mut doc = issues.get_raw(&project, &issue_id).unwrap().unwrap();
+        doc.transact_with::<_, _, AutomergeError, _, ()>(
+            |_| CommitOptions::default(),
+            |tx| {
+                let (_, obj_id) = tx.get(automerge::ObjId::Root, "issue")?.unwrap();
+                tx.put(&obj_id, "author", cobs.whoami.urn().to_string())
+            },
+        )
+        .unwrap();
+        let change = doc.get_last_local_change().unwrap().raw_bytes().to_vec();
+        cobs.update(
+            &cobs.whoami,
+            &project,
+            UpdateObjectSpec {
+                object_id: issue_id,
+                typename: TYPENAME.clone(),
+                message: Some("Claim issue".to_owned()),
+                changes: EntryContents::Automerge(change),
+            },
+        )
+        .unwrap();
+        issues
+            .lifecycle(
+                &project,
+                &issue_id,
+                State::Closed {
+                    reason: CloseReason::Other,
+                },
+            )
+            .unwrap();
+
+        let cob = cobs
+            .retrieve(&project, &TYPENAME, &issue_id)
+            .unwrap()
+            .unwrap();
+        let ctx = policy::Context::default();
+        let (issue, violations) = Issue::from_history_authorized(cob.history(), &ctx).unwrap();
+
+        // The author can't be changed, and the close depends on that change.
+        assert_eq!(issue.author().urn(), &other);
+        assert_eq!(issue.state(), State::Open);
+        assert_eq!(
+            violations,
+            vec![policy::Violation {
+                actor: cobs.whoami.urn(),
+                action: policy::Action::Author,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_issue_malformed_change() {
+        use automerge::transaction::{CommitOptions, Transactable};
+
+        let (storage, profile, whoami, project) = test::setup::profile();
+        let cobs = Store::new(whoami, profile.paths(), &storage);
+        let issues = cobs.issues();
+        let project = project.urn();
+        let issue_id = issues
+            .create(&project, "My first issue", "Blah blah blah.", &[])
+            .unwrap();
+
+        // A change that leaves the issue without a valid title.
+        let mut doc = issues.get_raw(&project, &issue_id).unwrap().unwrap();
+        doc.transact_with::<_, _, AutomergeError, _, ()>(
+            |_| CommitOptions::default(),
+            |tx| {
+                let (_, obj_id) = tx.get(automerge::ObjId::Root, "issue")?.unwrap();
+                tx.put(&obj_id, "title", 42)
+            },
+        )
+        .unwrap();
+        let change = doc.get_last_local_change().unwrap().raw_bytes().to_vec();
+        cobs.update(
+            &cobs.whoami,
+            &project,
+            UpdateObjectSpec {
+                object_id: issue_id,
+                typename: TYPENAME.clone(),
+                message: Some("Break issue".to_owned()),
+                changes: EntryContents::Automerge(change),
+            },
+        )
+        .unwrap();
+
+        // The change is skipped, and the issue still loads.
+        let (issue, violations) = cobs
+            .get_authorized::<Issue>(&project, &issue_id)
+            .unwrap()
+            .unwrap();
+        assert_eq!(issue.title(), "My first issue");
+        assert_eq!(
+            violations,
+            vec![policy::Violation {
+                actor: cobs.whoami.urn(),
+                action: policy::Action::Malformed,
+            }]
+        );
+        assert_eq!(issues.all(&project).unwrap().len(), 1);
+    }
 }
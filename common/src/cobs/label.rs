@@ -16,12 +16,20 @@ use librad::git::Storage;
 use librad::git::Urn;
 use librad::paths::Paths;
 
+use crate::cobs::shared;
 use crate::cobs::shared::*;
 
 lazy_static! {
     pub static ref TYPENAME: TypeName = FromStr::from_str("xyz.radicle.label").unwrap();
 }
 
+/// Schema migrations of label documents. The latest version is written on creation.
+pub static MIGRATIONS: Migrations = Migrations::new(&[Migration {
+    from: 0,
+    description: "Add version marker",
+    migrate: shared::unversioned,
+}]);
+
 /// Identifier for a label.
 pub type LabelId = ObjectId;
 
@@ -37,7 +45,7 @@ impl TryFrom<&History> for Label {
     type Error = anyhow::Error;
 
     fn try_from(history: &History) -> Result<Self, Self::Error> {
-        let doc = history.traverse(Automerge::new(), |mut doc, entry| {
+        let mut doc = history.traverse(Automerge::new(), |mut doc, entry| {
             match entry.contents() {
                 EntryContents::Automerge(bytes) => {
                     match automerge::Change::from_bytes(bytes.clone()) {
@@ -52,25 +60,33 @@ impl TryFrom<&History> for Label {
             }
             ControlFlow::Continue(doc)
         });
+        shared::migrate(&mut doc, &TYPENAME)?;
         let label = Label::try_from(doc)?;
 
         Ok(label)
     }
 }
 
+impl Cob for Label {
+    fn type_name() -> &'static TypeName {
+        &TYPENAME
+    }
+
+    fn from_history(history: &History) -> Result<Self, anyhow::Error> {
+        Label::try_from(history)
+    }
+}
+
 impl TryFrom<Automerge> for Label {
-    type Error = AutomergeError;
+    type Error = DocumentError;
 
     fn try_from(doc: Automerge) -> Result<Self, Self::Error> {
-        let (_, obj_id) = doc.get(automerge::ObjId::Root, "label")?.unwrap();
-        let (name, _) = doc.get(&obj_id, "name")?.unwrap();
-        let (description, _) = doc.get(&obj_id, "description")?.unwrap();
-        let (color, _) = doc.get(&obj_id, "color")?.unwrap();
-
-        let name = name.into_string().unwrap();
-        let description = description.into_string().unwrap();
-        let color = color.into_string().unwrap();
-        let color = Color::from_str(&color).unwrap();
+        let doc = Document::new(&doc);
+        let (_, obj_id) = doc.get(automerge::ObjId::Root, "label")?;
+        let name = doc.val(&obj_id, "name")?;
+        let description = doc.val(&obj_id, "description")?;
+        let color: String = doc.val(&obj_id, "color")?;
+        let color = Color::from_str(&color).map_err(|_| DocumentError::Property)?;
 
         Ok(Self {
             name,
@@ -128,7 +144,8 @@ impl<'a> Labels<'a> {
             .map_err(|e| Error::Retrieve(e.to_string()))?;
 
         if let Some(cob) = cob {
-            let label = Label::try_from(cob.history()).unwrap();
+            let label =
+                Label::try_from(cob.history()).map_err(|e| Error::Retrieve(e.to_string()))?;
             Ok(Some(label))
         } else {
             Ok(None)
@@ -185,6 +202,7 @@ mod events {
             |_| CommitOptions::default().with_message("Create label".to_owned()),
             |tx| {
                 let label = tx.put_object(ObjId::Root, "label", ObjType::Map)?;
+                tx.put(ObjId::Root, VERSION_KEY, MIGRATIONS.latest())?;
 
                 tx.put(&label, "name", name)?;
                 tx.put(&label, "description", description)?;
@@ -222,4 +240,25 @@ mod test {
         assert_eq!(label.description, "Something that doesn't work");
         assert_eq!(label.color.to_string(), "#ff0000");
     }
+
+    #[test]
+    fn test_label_malformed() {
+        use automerge::transaction::{CommitOptions, Transactable};
+
+        let mut doc = Automerge::new();
+        doc.transact_with::<_, _, AutomergeError, _, ()>(
+            |_| CommitOptions::default(),
+            |tx| {
+                let label = tx.put_object(automerge::ObjId::Root, "label", ObjType::Map)?;
+                tx.put(&label, "name", "bug")?;
+                tx.put(&label, "description", "Something that doesn't work")?;
+                tx.put(&label, "color", "red")?;
+
+                Ok(())
+            },
+        )
+        .unwrap();
+
+        assert!(matches!(Label::try_from(doc), Err(DocumentError::Property)));
+    }
 }
@@ -1,6 +1,6 @@
 #![allow(clippy::too_many_arguments)]
-use std::collections::{HashMap, HashSet};
-use std::convert::{TryFrom, TryInto};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::convert::TryFrom;
 use std::fmt;
 use std::ops::{ControlFlow, Deref, RangeInclusive};
 use std::str::FromStr;
@@ -23,13 +23,29 @@ use librad::PeerId;
 
 use radicle_git_ext as git;
 
+use crate::cobs::policy;
 use crate::cobs::shared;
 use crate::cobs::shared::*;
+use crate::cobs::user;
 
 lazy_static! {
     pub static ref TYPENAME: TypeName = FromStr::from_str("xyz.radicle.patch").unwrap();
 }
 
+/// Schema migrations of patch documents. The latest version is written on creation.
+pub static MIGRATIONS: Migrations = Migrations::new(&[
+    Migration {
+        from: 0,
+        description: "Add version marker",
+        migrate: shared::unversioned,
+    },
+    Migration {
+        from: 1,
+        description: "Add revision checks",
+        migrate: migrations::checks,
+    },
+]);
+
 /// Identifier for a patch.
 pub type PatchId = ObjectId;
 
@@ -40,7 +56,7 @@ pub type RevisionId = uuid::Uuid;
 pub type RevisionIx = usize;
 
 /// Where a patch is intended to be merged.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum MergeTarget {
     /// Intended for the default branch of the project delegates.
@@ -74,7 +90,7 @@ impl<'a> FromValue<'a> for MergeTarget {
     }
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Patch<T = (), P = PeerId>
 where
     T: Clone,
@@ -144,6 +160,68 @@ impl Cob for Patch {
     fn from_history(history: &History) -> Result<Self, anyhow::Error> {
         Patch::try_from(history)
     }
+
+    fn from_history_authorized(
+        history: &History,
+        ctx: &policy::Context,
+    ) -> Result<(Self, Vec<policy::Violation>), anyhow::Error> {
+        policy::replay(history, ctx)
+    }
+}
+
+impl policy::Authorize for Patch {
+    fn from_doc(doc: &Automerge) -> Result<Self, DocumentError> {
+        Patch::try_from(Document::new(doc))
+    }
+
+    fn authorize(
+        old: &Self,
+        new: &Self,
+        actor: &Urn,
+        ctx: &policy::Context,
+    ) -> Result<(), policy::Action> {
+        let author = old.author.urn();
+        let policy = &ctx.policy;
+
+        // Rules that depend on the author would be meaningless if it could be changed.
+        if old.author.urn() != new.author.urn() || old.author.peer != new.author.peer {
+            return Err(policy::Action::Author);
+        }
+        if old.state != new.state && !policy.patch_state.allows(actor, author, ctx) {
+            return Err(policy::Action::PatchState);
+        }
+        if old.labels != new.labels && !policy.patch_labels.allows(actor, author, ctx) {
+            return Err(policy::Action::PatchLabels);
+        }
+        // Revisions can't be removed, only added by whoever may add revisions.
+        if new.revisions.len() < old.revisions.len()
+            && !policy.patch_revision.allows(actor, author, ctx)
+        {
+            return Err(policy::Action::PatchRevision);
+        }
+        for (ix, revision) in new.revisions.iter().enumerate() {
+            let previous = old.revisions.get(ix);
+
+            // Added revisions are compared against an empty revision, so that they
+            // can't come with merges or checks that the actor couldn't record.
+            let revised = previous.map_or(true, |r| !r.is_same(revision));
+            let merged =
+                previous.map_or(!revision.merges.is_empty(), |r| r.merges != revision.merges);
+            let checked =
+                previous.map_or(!revision.checks.is_empty(), |r| r.checks != revision.checks);
+
+            if revised && !policy.patch_revision.allows(actor, author, ctx) {
+                return Err(policy::Action::PatchRevision);
+            }
+            if merged && !policy.patch_merge.allows(actor, author, ctx) {
+                return Err(policy::Action::PatchMerge);
+            }
+            if checked && !policy.patch_check.allows(actor, author, ctx) {
+                return Err(policy::Action::PatchCheck);
+            }
+        }
+        Ok(())
+    }
 }
 
 impl TryFrom<Document<'_>> for Patch {
@@ -179,7 +257,7 @@ impl TryFrom<&History> for Patch {
     type Error = anyhow::Error;
 
     fn try_from(history: &History) -> Result<Self, Self::Error> {
-        let doc = history.traverse(Automerge::new(), |mut doc, entry| {
+        let mut doc = history.traverse(Automerge::new(), |mut doc, entry| {
             match entry.contents() {
                 EntryContents::Automerge(bytes) => {
                     match automerge::Change::from_bytes(bytes.clone()) {
@@ -194,6 +272,7 @@ impl TryFrom<&History> for Patch {
             }
             ControlFlow::Continue(doc)
         });
+        shared::migrate(&mut doc, &TYPENAME)?;
         let patch = Patch::try_from(Document::new(&doc))?;
 
         Ok(patch)
@@ -246,8 +325,16 @@ impl<'a> PatchStore<'a> {
             timestamp,
         );
         let history = events::create(&author, title, &revision, target, timestamp, labels)?;
+        let id = cobs::create(history, project, &self.whoami, self.store)?;
+        self.store.reindex::<Patch>(project, &id);
+
+        self.store.record(user::Event::PatchProposed {
+            project: project.clone(),
+            patch: id,
+            title: title.trim().to_owned(),
+        });
 
-        cobs::create(history, project, &self.whoami, self.store)
+        Ok(id)
     }
 
     pub fn comment(
@@ -274,6 +361,13 @@ impl<'a> PatchStore<'a> {
                 },
             )
             .unwrap();
+        self.store.reindex::<Patch>(project, patch_id);
+
+        self.store.record(user::Event::PatchCommented {
+            project: project.clone(),
+            patch: *patch_id,
+            revision: revision_ix,
+        });
 
         Ok(*cob.id()) // TODO: Return something other than doc id.
     }
@@ -308,6 +402,13 @@ impl<'a> PatchStore<'a> {
             &self.whoami,
             self.store,
         )?;
+        self.store.reindex::<Patch>(project, patch_id);
+
+        self.store.record(user::Event::PatchUpdated {
+            project: project.clone(),
+            patch: *patch_id,
+            revision: revision_ix,
+        });
 
         Ok(revision_ix)
     }
@@ -344,6 +445,7 @@ impl<'a> PatchStore<'a> {
                 },
             )
             .unwrap();
+        self.store.reindex::<Patch>(project, patch_id);
 
         Ok(())
     }
@@ -371,6 +473,14 @@ impl<'a> PatchStore<'a> {
             &self.whoami,
             self.store,
         )?;
+        self.store.reindex::<Patch>(project, patch_id);
+
+        self.store.record(user::Event::PatchReviewed {
+            project: project.clone(),
+            patch: *patch_id,
+            revision: revision_ix,
+            verdict,
+        });
 
         Ok(())
     }
@@ -426,32 +536,69 @@ impl<'a> PatchStore<'a> {
             &self.whoami,
             self.store,
         )?;
+        self.store.reindex::<Patch>(project, patch_id);
+
+        self.store.record(user::Event::PatchMerged {
+            project: project.clone(),
+            patch: *patch_id,
+            revision: revision_ix,
+        });
 
         Ok(merge)
     }
 
-    pub fn count(&self, project: &Urn) -> Result<usize, Error> {
-        let cobs = self.store.list(project, &TYPENAME)?;
+    /// Attach the result of a check to a revision.
+    pub fn check(
+        &self,
+        project: &Urn,
+        patch_id: &PatchId,
+        revision_ix: RevisionIx,
+        name: &str,
+        status: CheckStatus,
+        summary: impl Into<String>,
+    ) -> Result<Check, Error> {
+        let check = Check {
+            name: name.trim().to_owned(),
+            status,
+            summary: summary.into().trim().to_owned(),
+            runner: self.peer_id,
+            timestamp: Timestamp::now(),
+        };
+
+        let mut patch = self.get_raw(project, patch_id)?.unwrap();
+        let changes = events::check(&mut patch, revision_ix, &check)?;
+
+        cobs::update(
+            *patch_id,
+            project,
+            "Add check",
+            changes,
+            &self.whoami,
+            self.store,
+        )?;
+        self.store.reindex::<Patch>(project, patch_id);
+
+        Ok(check)
+    }
 
-        Ok(cobs.len())
+    pub fn count(&self, project: &Urn) -> Result<usize, Error> {
+        self.store.count::<Patch>(project)
     }
 
     pub fn all(&self, project: &Urn) -> Result<Vec<(PatchId, Patch)>, Error> {
-        let mut patches = Vec::new();
-        let cobs = self.store.list(project, &TYPENAME)?;
-        for cob in cobs {
-            let patch: Result<Patch, _> = cob.history().try_into();
-            patches.push((*cob.id(), patch.unwrap()));
-        }
+        let mut patches = self.store.all::<Patch>(project)?;
         patches.sort_by_key(|(_, p)| p.timestamp);
 
         Ok(patches)
     }
 
     pub fn proposed(&self, project: &Urn) -> Result<impl Iterator<Item = (PatchId, Patch)>, Error> {
-        let all = self.all(project)?;
+        let mut proposed = self
+            .store
+            .filter::<Patch, _>(project, |p| p.is_proposed())?;
+        proposed.sort_by_key(|(_, p)| p.timestamp);
 
-        Ok(all.into_iter().filter(|(_, p)| p.is_proposed()))
+        Ok(proposed.into_iter())
     }
 
     pub fn proposed_by(
@@ -465,7 +612,7 @@ impl<'a> PatchStore<'a> {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum State {
     Draft,
@@ -497,7 +644,7 @@ impl<'a> FromValue<'a> for State {
 }
 
 /// A patch revision.
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Revision<T = (), P = PeerId> {
     /// Unique revision ID. This is useful in case of conflicts, eg.
     /// a user published a revision from two devices by mistake.
@@ -516,6 +663,8 @@ pub struct Revision<T = (), P = PeerId> {
     pub reviews: HashMap<Urn, Review>,
     /// Merges of this revision into other repositories.
     pub merges: Vec<Merge<P>>,
+    /// Results of checks run against this revision, eg. by CI, keyed by name.
+    pub checks: BTreeMap<String, Check<P>>,
     /// Code changeset for this revision.
     pub changeset: T,
     /// When this revision was created.
@@ -523,6 +672,20 @@ pub struct Revision<T = (), P = PeerId> {
 }
 
 impl Revision {
+    /// Check whether two revisions have the same code and cover letter, ignoring
+    /// anything that can be added to a revision after it was published, such as
+    /// comments, reviews, merges and checks.
+    pub fn is_same(&self, other: &Self) -> bool {
+        self.id == other.id
+            && self.peer == other.peer
+            && self.base == other.base
+            && self.oid == other.oid
+            && self.comment.author.urn() == other.comment.author.urn()
+            && self.comment.body == other.comment.body
+            && self.comment.timestamp == other.comment.timestamp
+            && self.timestamp == other.timestamp
+    }
+
     pub fn new(
         author: Author,
         peer: PeerId,
@@ -540,6 +703,7 @@ impl Revision {
             discussion: Discussion::default(),
             reviews: HashMap::default(),
             merges: Vec::default(),
+            checks: BTreeMap::default(),
             changeset: (),
             timestamp,
         }
@@ -549,6 +713,18 @@ impl Revision {
         &self.comment.body
     }
 
+    /// Overall status of the checks of this revision: failing if any check
+    /// failed, and `None` if there are no checks.
+    pub fn check_status(&self) -> Option<CheckStatus> {
+        if self.checks.is_empty() {
+            None
+        } else if self.checks.values().any(|c| c.status == CheckStatus::Fail) {
+            Some(CheckStatus::Fail)
+        } else {
+            Some(CheckStatus::Pass)
+        }
+    }
+
     /// Put this object into an automerge document.
     fn put(
         &self,
@@ -567,6 +743,10 @@ impl Revision {
             self.discussion.is_empty(),
             "Cannot put revision with non-empty discussion"
         );
+        assert!(
+            self.checks.is_empty(),
+            "Cannot put revision with non-empty checks"
+        );
 
         tx.put(&id, "id", self.id.to_string())?;
         tx.put(&id, "peer", self.peer.to_string())?;
@@ -578,6 +758,7 @@ impl Revision {
         tx.put_object(&id, "discussion", ObjType::List)?;
         tx.put_object(&id, "reviews", ObjType::Map)?;
         tx.put_object(&id, "merges", ObjType::List)?;
+        tx.put_object(&id, "checks", ObjType::Map)?;
         tx.put(&id, "timestamp", self.timestamp)?;
 
         Ok(())
@@ -597,7 +778,7 @@ impl Revision {
 }
 
 /// A merged patch revision.
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Merge<P = PeerId> {
     /// Peer id of repository that this patch was merged into.
     pub peer: P,
@@ -607,6 +788,66 @@ pub struct Merge<P = PeerId> {
     pub timestamp: Timestamp,
 }
 
+/// Status of a check.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    /// The check passed.
+    Pass,
+    /// The check failed.
+    Fail,
+}
+
+impl fmt::Display for CheckStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Pass => write!(f, "pass"),
+            Self::Fail => write!(f, "fail"),
+        }
+    }
+}
+
+impl FromStr for CheckStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pass" => Ok(Self::Pass),
+            "fail" => Ok(Self::Fail),
+            _ => Err(format!("invalid check status '{}'", s)),
+        }
+    }
+}
+
+impl From<CheckStatus> for ScalarValue {
+    fn from(status: CheckStatus) -> Self {
+        let s = serde_json::to_string(&status).unwrap(); // Cannot fail.
+        ScalarValue::from(s)
+    }
+}
+
+impl<'a> FromValue<'a> for CheckStatus {
+    fn from_value(value: Value) -> Result<Self, ValueError> {
+        let status = value.to_str().ok_or(ValueError::InvalidType)?;
+        serde_json::from_str(status).map_err(|e| ValueError::Other(Arc::new(e)))
+    }
+}
+
+/// The result of a check run against a patch revision, eg. a build or test suite.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Check<P = PeerId> {
+    /// Check name, eg. "build".
+    pub name: String,
+    /// Check status.
+    pub status: CheckStatus,
+    /// Summary of the check output.
+    pub summary: String,
+    /// Peer that ran the check.
+    pub runner: P,
+    /// When the check was run.
+    pub timestamp: Timestamp,
+}
+
 /// A patch review verdict.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -641,7 +882,7 @@ impl<'a> FromValue<'a> for Verdict {
 }
 
 /// Code location, used for attaching comments.
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CodeLocation {
     /// Line number commented on.
     pub lines: RangeInclusive<usize>,
@@ -652,16 +893,16 @@ pub struct CodeLocation {
 }
 
 /// Comment on code.
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CodeComment {
     /// Code location of the comment.
-    location: CodeLocation,
+    pub location: CodeLocation,
     /// Comment.
-    comment: Comment,
+    pub comment: Comment,
 }
 
 /// A patch review on a revision.
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Review {
     /// Review author.
     pub author: Author,
@@ -752,6 +993,11 @@ mod lookup {
         let discussion: Discussion =
             doc.list(&revision_id, "discussion", shared::lookup::thread)?;
         let merges: Vec<Merge> = doc.list(&revision_id, "merges", self::merge)?;
+        // Revisions added concurrently by peers that don't know about checks yet
+        // don't have a `checks` map, even once the document is migrated.
+        let checks = doc
+            .lookup_opt(&revision_id, "checks", self::checks)?
+            .unwrap_or_default();
 
         // Reviews.
         let mut reviews: HashMap<Urn, Review> = HashMap::new();
@@ -771,11 +1017,39 @@ mod lookup {
             discussion,
             reviews,
             merges,
+            checks,
             changeset: (),
             timestamp,
         })
     }
 
+    pub fn checks(
+        doc: Document,
+        obj_id: &automerge::ObjId,
+    ) -> Result<BTreeMap<String, Check>, DocumentError> {
+        let mut checks = BTreeMap::new();
+
+        for name in (*doc).keys(obj_id) {
+            let (_, check_id) = doc.get(obj_id, name.as_str())?;
+            let status = doc.val(&check_id, "status")?;
+            let summary = doc.val(&check_id, "summary")?;
+            let runner = doc.val(&check_id, "runner")?;
+            let timestamp = doc.val(&check_id, "timestamp")?;
+
+            checks.insert(
+                name.clone(),
+                Check {
+                    name,
+                    status,
+                    summary,
+                    runner,
+                    timestamp,
+                },
+            );
+        }
+        Ok(checks)
+    }
+
     pub fn merge(doc: Document, obj_id: &automerge::ObjId) -> Result<Merge, DocumentError> {
         let peer = doc.val(&obj_id, "peer")?;
         let commit = doc.val(&obj_id, "commit")?;
@@ -806,6 +1080,39 @@ mod lookup {
     }
 }
 
+mod migrations {
+    use super::*;
+    use automerge::transaction::Transaction;
+    use automerge::ObjId;
+
+    /// Add an empty `checks` map to all revisions.
+    ///
+    /// Documents that don't have the expected layout are left as they are; they
+    /// fail to decode later, like any other malformed document.
+    pub fn checks(tx: &mut Transaction) -> Result<(), AutomergeError> {
+        let obj_id = match tx.get(ObjId::Root, "patch")? {
+            Some((Value::Object(ObjType::Map), id)) => id,
+            _ => return Ok(()),
+        };
+        let revisions_id = match tx.get(&obj_id, "revisions")? {
+            Some((Value::Object(ObjType::List), id)) => id,
+            _ => return Ok(()),
+        };
+
+        for ix in 0..tx.length(&revisions_id) {
+            let revision_id = match tx.get(&revisions_id, ix)? {
+                Some((Value::Object(ObjType::Map), id)) => id,
+                _ => continue,
+            };
+
+            if tx.get(&revision_id, "checks")?.is_none() {
+                tx.put_object(&revision_id, "checks", ObjType::Map)?;
+            }
+        }
+        Ok(())
+    }
+}
+
 mod cobs {
     use super::*;
 
@@ -878,6 +1185,7 @@ mod events {
                 |_| CommitOptions::default().with_message("Create patch".to_owned()),
                 |tx| {
                     let patch_id = tx.put_object(ObjId::Root, "patch", ObjType::Map)?;
+                    tx.put(ObjId::Root, VERSION_KEY, MIGRATIONS.latest())?;
 
                     tx.put(&patch_id, "title", title)?;
                     tx.put(&patch_id, "author", author.urn().to_string())?;
@@ -1067,6 +1375,42 @@ mod events {
 
         Ok(EntryContents::Automerge(change))
     }
+
+    pub fn check(
+        patch: &mut Automerge,
+        revision_ix: RevisionIx,
+        check: &Check,
+    ) -> Result<EntryContents, AutomergeError> {
+        patch
+            .transact_with::<_, _, AutomergeError, _, ()>(
+                |_| CommitOptions::default().with_message("Add check".to_owned()),
+                |tx| {
+                    let (_, obj_id) = tx.get(ObjId::Root, "patch")?.unwrap();
+                    let (_, revisions_id) = tx.get(&obj_id, "revisions")?.unwrap();
+                    let (_, revision_id) = tx.get(&revisions_id, revision_ix)?.unwrap();
+                    // Documents created before checks were introduced don't have
+                    // a `checks` map.
+                    let checks_id = match tx.get(&revision_id, "checks")? {
+                        Some((_, id)) => id,
+                        None => tx.put_object(&revision_id, "checks", ObjType::Map)?,
+                    };
+                    // Nb. A check replaces earlier results with the same name.
+                    let check_id = tx.put_object(&checks_id, check.name.as_str(), ObjType::Map)?;
+
+                    tx.put(&check_id, "status", check.status)?;
+                    tx.put(&check_id, "summary", check.summary.as_str())?;
+                    tx.put(&check_id, "runner", check.runner.to_string())?;
+                    tx.put(&check_id, "timestamp", check.timestamp)?;
+
+                    Ok(())
+                },
+            )
+            .map_err(|failure| failure.error)?;
+
+        let change = patch.get_last_local_change().unwrap().raw_bytes().to_vec();
+
+        Ok(EntryContents::Automerge(change))
+    }
 }
 
 #[cfg(test)]
@@ -1143,6 +1487,59 @@ mod test {
         assert_eq!(merges[0].commit, base);
     }
 
+    #[test]
+    fn test_patch_check() {
+        let (storage, profile, whoami, project) = test::setup::profile();
+        let cobs = Store::new(whoami, profile.paths(), &storage);
+        let patches = cobs.patches();
+        let oid = git::Oid::from(git2::Oid::zero());
+        let base = git::Oid::from_str("cb18e95ada2bb38aadd8e6cef0963ce37a87add3").unwrap();
+        let patch_id = patches
+            .create(
+                &project.urn(),
+                "My first patch",
+                "Blah blah blah.",
+                MergeTarget::Upstream,
+                base,
+                oid,
+                &[],
+            )
+            .unwrap();
+
+        let patch = patches.get(&project.urn(), &patch_id).unwrap().unwrap();
+        assert_eq!(patch.revisions.head.check_status(), None);
+
+        patches
+            .check(&project.urn(), &patch_id, 0, "build", CheckStatus::Pass, "")
+            .unwrap();
+        patches
+            .check(
+                &project.urn(),
+                &patch_id,
+                0,
+                "test",
+                CheckStatus::Fail,
+                "2 failed",
+            )
+            .unwrap();
+
+        let patch = patches.get(&project.urn(), &patch_id).unwrap().unwrap();
+        let revision = &patch.revisions.head;
+        assert_eq!(revision.checks.len(), 2);
+        assert_eq!(revision.checks["test"].summary, "2 failed");
+        assert_eq!(revision.checks["test"].runner, *storage.peer_id());
+        assert_eq!(revision.check_status(), Some(CheckStatus::Fail));
+
+        // Re-running a check replaces its result.
+        patches
+            .check(&project.urn(), &patch_id, 0, "test", CheckStatus::Pass, "")
+            .unwrap();
+
+        let patch = patches.get(&project.urn(), &patch_id).unwrap().unwrap();
+        assert_eq!(patch.revisions.head.checks.len(), 2);
+        assert_eq!(patch.revisions.head.check_status(), Some(CheckStatus::Pass));
+    }
+
     #[test]
     fn test_patch_review() {
         let (storage, profile, whoami, project) = test::setup::profile();
@@ -1221,4 +1618,249 @@ mod test {
         assert_eq!(revision.oid, rev1_oid);
         assert_eq!(revision.description(), "I've made changes.");
     }
+
+    #[test]
+    fn test_patch_migrate_fixtures() {
+        // Patches created before versioning, and before revisions had checks.
+        for (fixture, version) in [("patch-v0.bin", 0), ("patch-v1.bin", 1)] {
+            let mut doc = test::fixture(fixture);
+
+            assert_eq!(shared::version(&doc).unwrap(), version);
+            assert_eq!(shared::migrate(&mut doc, &TYPENAME).unwrap(), version);
+            assert_eq!(shared::version(&doc).unwrap(), MIGRATIONS.latest());
+
+            let patch = Patch::try_from(Document::new(&doc)).unwrap();
+            let (_, revision) = patch.latest();
+
+            assert_eq!(patch.title, "My first patch");
+            assert_eq!(patch.state, State::Proposed);
+            assert_eq!(patch.revisions.len(), 1);
+            assert_eq!(revision.comment.body, "Blah blah blah.");
+            assert_eq!(revision.discussion.len(), 1);
+            assert!(revision.checks.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_patch_revision_without_checks() {
+        // Decode the revision as it was published, without migrating it.
+        let doc = test::fixture("patch-v1.bin");
+        let patch = Patch::try_from(Document::new(&doc)).unwrap();
+
+        assert!(patch.latest().1.checks.is_empty());
+    }
+
+    #[test]
+    fn test_patch_migrate_malformed() {
+        use automerge::transaction::CommitOptions;
+
+        // A version `1` patch whose revisions aren't a list of maps.
+        for revisions in [None, Some(ObjType::Map), Some(ObjType::List)] {
+            let mut doc = Automerge::new();
+            doc.transact_with::<_, _, AutomergeError, _, ()>(
+                |_| CommitOptions::default(),
+                |tx| {
+                    let patch = tx.put_object(automerge::ObjId::Root, "patch", ObjType::Map)?;
+                    tx.put(automerge::ObjId::Root, VERSION_KEY, 1)?;
+
+                    match revisions {
+                        Some(ObjType::List) => {
+                            let list = tx.put_object(&patch, "revisions", ObjType::List)?;
+                            tx.insert(&list, 0, "not a revision")?;
+                        }
+                        Some(ty) => {
+                            tx.put_object(&patch, "revisions", ty)?;
+                        }
+                        None => {
+                            tx.put(&patch, "revisions", 42)?;
+                        }
+                    }
+                    Ok(())
+                },
+            )
+            .unwrap();
+
+            // The document is migrated without panicking, but still fails to decode.
+            assert_eq!(shared::migrate(&mut doc, &TYPENAME).unwrap(), 1);
+            assert!(Patch::try_from(Document::new(&doc)).is_err());
+        }
+    }
+
+    /// Create a patch authored by someone else, as published by the local peer.
+    fn create_as(patches: &PatchStore, project: &Urn, author: &Urn) -> PatchId {
+        let author = Author::new(author.clone(), patches.peer_id);
+        let base = git::Oid::from_str("cb18e95ada2bb38aadd8e6cef0963ce37a87add3").unwrap();
+        let oid = git::Oid::from_str("518d5069f94c03427f694bb494ac1cd7d1339380").unwrap();
+        let timestamp = Timestamp::now();
+        let revision = Revision::new(
+            author.clone(),
+            patches.peer_id,
+            base,
+            oid,
+            "Blah blah blah.".to_owned(),
+            timestamp,
+        );
+        let history = events::create(
+            &author,
+            "My first patch",
+            &revision,
+            MergeTarget::Upstream,
+            timestamp,
+            &[],
+        )
+        .unwrap();
+
+        cobs::create(history, project, &patches.whoami, patches.store).unwrap()
+    }
+
+    /// Make a raw change to a patch, without publishing it.
+    fn change(
+        patches: &PatchStore,
+        project: &Urn,
+        patch_id: &PatchId,
+        f: impl FnOnce(
+            &mut automerge::transaction::Transaction,
+            &automerge::ObjId,
+        ) -> Result<(), AutomergeError>,
+    ) -> EntryContents {
+        use automerge::transaction::CommitOptions;
+
+        let mut doc = patches.get_raw(project, patch_id).unwrap().unwrap();
+        doc.transact_with::<_, _, AutomergeError, _, ()>(
+            |_| CommitOptions::default(),
+            |tx| {
+                let (_, obj_id) = tx.get(automerge::ObjId::Root, "patch")?.unwrap();
+                f(tx, &obj_id)
+            },
+        )
+        .unwrap();
+
+        EntryContents::Automerge(doc.get_last_local_change().unwrap().raw_bytes().to_vec())
+    }
+
+    fn publish(patches: &PatchStore, project: &Urn, patch_id: &PatchId, changes: EntryContents) {
+        cobs::update(
+            *patch_id,
+            project,
+            "Change patch",
+            changes,
+            &patches.whoami,
+            patches.store,
+        )
+        .unwrap();
+    }
+
+    fn replay(
+        patches: &PatchStore,
+        project: &Urn,
+        patch_id: &PatchId,
+    ) -> (Patch, Vec<policy::Violation>) {
+        let cob = patches
+            .retrieve(project, &TYPENAME, patch_id)
+            .unwrap()
+            .unwrap();
+
+        Patch::from_history_authorized(cob.history(), &policy::Context::default()).unwrap()
+    }
+
+    #[test]
+    fn test_patch_unauthorized_author() {
+        let (storage, profile, whoami, project) = test::setup::profile();
+        let cobs = Store::new(whoami.clone(), profile.paths(), &storage);
+        let patches = cobs.patches();
+        let project = project.urn();
+        let other = Urn::from_str("rad:git:hnrkyghsrokxzxpy9pww69xr11dr9q7edbxfo").unwrap();
+        let patch_id = create_as(&patches, &project, &other);
+
+        // Claim the patch, then push a revision.
+        let claim = change(&patches, &project, &patch_id, |tx, obj_id| {
+            tx.put(obj_id, "author", whoami.urn().to_string())
+        });
+        publish(&patches, &project, &patch_id, claim);
+        patches
+            .update(
+                &project,
+                &patch_id,
+                "Blah.",
+                git::Oid::from(git2::Oid::zero()),
+                git::Oid::from(git2::Oid::zero()),
+            )
+            .unwrap();
+
+        // The author can't be changed, and the revision depends on that change.
+        let (patch, violations) = replay(&patches, &project, &patch_id);
+        assert_eq!(patch.author.urn(), &other);
+        assert_eq!(patch.revisions.len(), 1);
+        assert_eq!(
+            violations,
+            vec![policy::Violation {
+                actor: whoami.urn(),
+                action: policy::Action::Author,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_patch_unauthorized_revision_edit() {
+        let (storage, profile, whoami, project) = test::setup::profile();
+        let cobs = Store::new(whoami, profile.paths(), &storage);
+        let patches = cobs.patches();
+        let project = project.urn();
+        let other = Urn::from_str("rad:git:hnrkyghsrokxzxpy9pww69xr11dr9q7edbxfo").unwrap();
+        let patch_id = create_as(&patches, &project, &other);
+        let (original, _) = replay(&patches, &project, &patch_id);
+
+        // Concurrent changes to the existing revision, by someone who isn't the author
+        // or a delegate.
+        let edit = change(&patches, &project, &patch_id, |tx, obj_id| {
+            let (_, revisions_id) = tx.get(obj_id, "revisions")?.unwrap();
+            let (_, revision_id) = tx.get(&revisions_id, 0)?.unwrap();
+
+            tx.put(&revision_id, "oid", git2::Oid::zero().to_string())
+        });
+        let merge = events::merge(
+            &mut patches.get_raw(&project, &patch_id).unwrap().unwrap(),
+            0,
+            &Merge {
+                peer: patches.peer_id,
+                commit: git::Oid::from(git2::Oid::zero()),
+                timestamp: Timestamp::now(),
+            },
+        )
+        .unwrap();
+        let check = events::check(
+            &mut patches.get_raw(&project, &patch_id).unwrap().unwrap(),
+            0,
+            &Check {
+                name: "build".to_owned(),
+                status: CheckStatus::Pass,
+                summary: String::new(),
+                runner: patches.peer_id,
+                timestamp: Timestamp::now(),
+            },
+        )
+        .unwrap();
+
+        for changes in [edit, merge, check] {
+            publish(&patches, &project, &patch_id, changes);
+        }
+
+        let (patch, violations) = replay(&patches, &project, &patch_id);
+        let (_, revision) = patch.latest();
+
+        assert!(revision.is_same(original.latest().1));
+        assert!(revision.merges.is_empty());
+        assert!(revision.checks.is_empty());
+
+        let mut actions = violations.iter().map(|v| v.action).collect::<Vec<_>>();
+        actions.sort_by_key(|a| a.to_string());
+        assert_eq!(
+            actions,
+            vec![
+                policy::Action::PatchRevision,
+                policy::Action::PatchMerge,
+                policy::Action::PatchCheck,
+            ]
+        );
+    }
 }
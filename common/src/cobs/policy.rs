@@ -0,0 +1,368 @@
+//! Authorization of collaborative object changes.
+//!
+//! Any peer can publish changes to any object, so the changes that make up an
+//! object's history are checked against a project-wide [`Policy`] while the history
+//! is replayed. Changes that aren't allowed are skipped, and reported as [`Violation`]s.
+//!
+//! The policy is stored as an extension of the project identity payload, so that
+//! all peers of a project agree on it. Projects without a policy use the default one.
+use std::collections::{BTreeSet, HashSet};
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::ControlFlow;
+
+use automerge::Automerge;
+use serde::{Deserialize, Serialize};
+
+use librad::collaborative_objects::{EntryContents, History};
+use librad::git::storage::ReadOnly;
+use librad::git::Urn;
+use librad::identities::payload::HasNamespace;
+
+use crate::cobs::shared::{self, Cob, DocumentError, MigrationError};
+use crate::project;
+
+lazy_static::lazy_static! {
+    static ref POLICY_NAMESPACE: url::Url = "https://radicle.xyz/cob/policy/v1"
+        .parse()
+        .expect("static URL malformed");
+}
+
+/// Who is allowed to perform an action.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Rule {
+    /// Any peer.
+    Anyone,
+    /// The author of the object.
+    Author,
+    /// The project delegates.
+    Delegates,
+    /// The author of the object, or the project delegates.
+    AuthorOrDelegates,
+}
+
+impl Rule {
+    /// Check whether `actor` is allowed to act on an object authored by `author`.
+    pub fn allows(&self, actor: &Urn, author: &Urn, ctx: &Context) -> bool {
+        match self {
+            Self::Anyone => true,
+            Self::Author => actor == author,
+            Self::Delegates => ctx.is_delegate(actor),
+            Self::AuthorOrDelegates => actor == author || ctx.is_delegate(actor),
+        }
+    }
+}
+
+/// Project COB policy.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Policy {
+    /// Who may open and close issues.
+    pub issue_state: Rule,
+    /// Who may change issue labels.
+    pub issue_labels: Rule,
+    /// Who may change the state of a patch, eg. to archive it.
+    pub patch_state: Rule,
+    /// Who may change patch labels.
+    pub patch_labels: Rule,
+    /// Who may add patch revisions.
+    pub patch_revision: Rule,
+    /// Who may record patch merges.
+    pub patch_merge: Rule,
+    /// Who may record check results on patch revisions.
+    pub patch_check: Rule,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self {
+            issue_state: Rule::AuthorOrDelegates,
+            issue_labels: Rule::AuthorOrDelegates,
+            patch_state: Rule::AuthorOrDelegates,
+            patch_labels: Rule::AuthorOrDelegates,
+            patch_revision: Rule::Author,
+            patch_merge: Rule::Delegates,
+            patch_check: Rule::AuthorOrDelegates,
+        }
+    }
+}
+
+impl HasNamespace for Policy {
+    fn namespace() -> &'static url::Url {
+        &POLICY_NAMESPACE
+    }
+}
+
+/// An action that is subject to authorization.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Action {
+    /// Change the author of an object. This is never allowed.
+    Author,
+    /// Publish a change that leaves the object undecodable. This is never allowed.
+    Malformed,
+    IssueState,
+    IssueLabels,
+    PatchState,
+    PatchLabels,
+    PatchRevision,
+    PatchMerge,
+    PatchCheck,
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Author => write!(f, "change the author"),
+            Self::Malformed => write!(f, "make the object undecodable"),
+            Self::IssueState => write!(f, "change issue state"),
+            Self::IssueLabels => write!(f, "change issue labels"),
+            Self::PatchState => write!(f, "change patch state"),
+            Self::PatchLabels => write!(f, "change patch labels"),
+            Self::PatchRevision => write!(f, "change patch revisions"),
+            Self::PatchMerge => write!(f, "merge patch"),
+            Self::PatchCheck => write!(f, "record patch checks"),
+        }
+    }
+}
+
+/// A change that was skipped because it wasn't allowed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Violation {
+    /// Author of the change.
+    #[serde(deserialize_with = "project::deserialize_urn")]
+    pub actor: Urn,
+    /// What the change attempted.
+    pub action: Action,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} is not allowed to {}",
+            self.actor.encode_id(),
+            self.action
+        )
+    }
+}
+
+/// Everything needed to authorize changes to the objects of a project.
+#[derive(Debug, Clone, Default)]
+pub struct Context {
+    /// The project policy.
+    pub policy: Policy,
+    /// Personal identities of the project delegates.
+    pub delegates: HashSet<Urn>,
+}
+
+impl Context {
+    /// Load the policy context of the given namespace. Namespaces that aren't
+    /// projects get the default policy, with no delegates.
+    pub fn load<S: AsRef<ReadOnly>>(storage: &S, namespace: &Urn) -> anyhow::Result<Self> {
+        let proj = match lnk_identities::project::get(storage, namespace)? {
+            Some(proj) => proj,
+            None => return Ok(Self::default()),
+        };
+        let policy = proj.payload().get_ext::<Policy>()?.unwrap_or_default();
+        let meta = project::Metadata::try_from(proj)?;
+
+        let mut delegates = HashSet::new();
+        for delegate in &meta.delegates {
+            match delegate {
+                project::Delegate::Indirect { urn, .. } => {
+                    delegates.insert(urn.clone());
+                }
+                // Changes are authored by personal identities, so we look up the
+                // identity published by the delegate key.
+                project::Delegate::Direct { id } => {
+                    if let Some(person) = project::person(storage, namespace.clone(), id)? {
+                        delegates.insert(person.urn());
+                    }
+                }
+            }
+        }
+        Ok(Self { policy, delegates })
+    }
+
+    /// Check whether the given identity is a project delegate.
+    pub fn is_delegate(&self, urn: &Urn) -> bool {
+        self.delegates.contains(urn)
+    }
+
+    /// A string that changes whenever the outcome of authorization may change.
+    pub fn fingerprint(&self) -> String {
+        let delegates: BTreeSet<String> = self.delegates.iter().map(|d| d.to_string()).collect();
+        let policy = serde_json::to_string(&self.policy).unwrap_or_default();
+
+        format!(
+            "{}:{}",
+            policy,
+            delegates.into_iter().collect::<Vec<_>>().join(",")
+        )
+    }
+}
+
+/// Objects whose changes are subject to authorization.
+pub trait Authorize: Cob {
+    /// Decode an object from a document.
+    fn from_doc(doc: &Automerge) -> Result<Self, DocumentError>;
+    /// Check that `actor` is allowed to change the object from `old` to `new`.
+    fn authorize(old: &Self, new: &Self, actor: &Urn, ctx: &Context) -> Result<(), Action>;
+}
+
+/// Replay an object history, skipping unauthorized changes.
+///
+/// Changes that leave the object undecodable are skipped too, so that a single
+/// malformed change can't prevent the object from loading.
+///
+/// Nb. Changes that causally depend on a skipped change can't be applied either,
+/// and are dropped along with it.
+pub fn replay<T: Authorize>(
+    history: &History,
+    ctx: &Context,
+) -> Result<(T, Vec<Violation>), anyhow::Error> {
+    let replay = history.traverse(Replay::default(), |mut replay, entry| {
+        match entry.contents() {
+            EntryContents::Automerge(bytes) => replay.apply(bytes, entry.author_urn(), ctx),
+        }
+        ControlFlow::Continue(replay)
+    });
+    match (replay.current, replay.unsupported) {
+        (Some(obj), _) => Ok((obj, replay.violations)),
+        (None, Some(err)) => Err(err.into()),
+        (None, None) => Err(anyhow::anyhow!("object history has no valid state")),
+    }
+}
+
+/// State of a history replay.
+struct Replay<T> {
+    /// Document with all accepted changes applied.
+    doc: Automerge,
+    /// Accepted changes, used to rebuild the document when a change is rejected.
+    accepted: Vec<Vec<u8>>,
+    /// The object, as decoded after the last accepted change.
+    current: Option<T>,
+    /// Rejected changes.
+    violations: Vec<Violation>,
+    /// Set if the object was created by a newer version, and can't be decoded.
+    unsupported: Option<MigrationError>,
+}
+
+impl<T> Default for Replay<T> {
+    fn default() -> Self {
+        Self {
+            doc: Automerge::new(),
+            accepted: Vec::new(),
+            current: None,
+            violations: Vec::new(),
+            unsupported: None,
+        }
+    }
+}
+
+impl<T: Authorize> Replay<T> {
+    /// Apply a change, keeping it only if it is authorized.
+    ///
+    /// The change is applied in place, and the document is decoded once. Since rejected
+    /// changes are rare, the document is only rebuilt when a change is rejected.
+    fn apply(&mut self, bytes: &[u8], actor: &Urn, ctx: &Context) {
+        let change = match automerge::Change::from_bytes(bytes.to_vec()) {
+            Ok(change) => change,
+            Err(_) => return,
+        };
+        if self.doc.apply_changes([change]).is_err() {
+            self.rebuild();
+            return;
+        }
+
+        let verdict = match decode::<T>(&self.doc) {
+            // The first decodable state is the creation of the object.
+            Ok(new) => match &self.current {
+                None => Ok(new),
+                Some(old) => T::authorize(old, &new, actor, ctx).map(|()| new),
+            },
+            Err(err @ MigrationError::Unsupported { .. }) if self.current.is_none() => {
+                self.unsupported = Some(err);
+                Err(Action::Malformed)
+            }
+            Err(_) => Err(Action::Malformed),
+        };
+
+        match verdict {
+            Ok(new) => {
+                self.accepted.push(bytes.to_vec());
+                self.current = Some(new);
+            }
+            Err(action) => {
+                self.violations.push(Violation {
+                    actor: actor.clone(),
+                    action,
+                });
+                self.rebuild();
+            }
+        }
+    }
+
+    /// Rebuild the document from the accepted changes only.
+    fn rebuild(&mut self) {
+        let mut doc = Automerge::new();
+
+        for bytes in &self.accepted {
+            if let Ok(change) = automerge::Change::from_bytes(bytes.clone()) {
+                doc.apply_changes([change]).ok();
+            }
+        }
+        self.doc = doc;
+    }
+}
+
+/// Decode a document. Documents that aren't at the latest version are migrated on a
+/// copy first, since migrations must not end up in the replayed document.
+fn decode<T: Authorize>(doc: &Automerge) -> Result<T, MigrationError> {
+    let latest = shared::migrations(T::type_name()).map_or(0, |m| m.latest());
+
+    if shared::version(doc)? == latest {
+        return T::from_doc(doc).map_err(MigrationError::from);
+    }
+    let mut doc = doc.clone();
+    shared::migrate(&mut doc, T::type_name())?;
+
+    T::from_doc(&doc).map_err(MigrationError::from)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_rule_allows() {
+        let author = Urn::from_str("rad:git:hnrkyghsrokxzxpy9pww69xr11dr9q7edbxfo").unwrap();
+        let delegate = Urn::from_str("rad:git:hnrkqdpm9ub19oc8dccx44echy76hf4bpfxro").unwrap();
+        let other = Urn::from_str("rad:git:hnrkbjg7r54q48sqsaho1n4qfxhi4nbmdh51y").unwrap();
+        let ctx = Context {
+            policy: Policy::default(),
+            delegates: vec![delegate.clone()].into_iter().collect(),
+        };
+
+        assert!(Rule::Anyone.allows(&other, &author, &ctx));
+        assert!(Rule::Author.allows(&author, &author, &ctx));
+        assert!(!Rule::Author.allows(&delegate, &author, &ctx));
+        assert!(Rule::Delegates.allows(&delegate, &author, &ctx));
+        assert!(!Rule::Delegates.allows(&author, &author, &ctx));
+        assert!(Rule::AuthorOrDelegates.allows(&author, &author, &ctx));
+        assert!(Rule::AuthorOrDelegates.allows(&delegate, &author, &ctx));
+        assert!(!Rule::AuthorOrDelegates.allows(&other, &author, &ctx));
+    }
+
+    #[test]
+    fn test_policy_defaults() {
+        let policy: Policy = serde_json::from_str(r#"{ "patchMerge": "anyone" }"#).unwrap();
+
+        assert_eq!(policy.patch_merge, Rule::Anyone);
+        assert_eq!(policy.patch_revision, Rule::Author);
+        assert_eq!(policy.issue_state, Rule::AuthorOrDelegates);
+    }
+}
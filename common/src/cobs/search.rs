@@ -0,0 +1,391 @@
+//! Full-text search over collaborative objects.
+use std::fmt;
+
+use serde::Serialize;
+
+use librad::collaborative_objects::ObjectId;
+
+use crate::cobs::issue::Issue;
+use crate::cobs::patch::Patch;
+use crate::cobs::shared::{Author, Timestamp};
+
+/// Number of characters of context to show around a match.
+pub const SNIPPET_CONTEXT: usize = 32;
+
+/// Kind of object a search hit was found in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    Issue,
+    Patch,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Issue => write!(f, "issue"),
+            Self::Patch => write!(f, "patch"),
+        }
+    }
+}
+
+/// Part of an object a search hit was found in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Field {
+    Title,
+    Description,
+    Comment,
+    Reply,
+    Review,
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Title => write!(f, "title"),
+            Self::Description => write!(f, "description"),
+            Self::Comment => write!(f, "comment"),
+            Self::Reply => write!(f, "reply"),
+            Self::Review => write!(f, "review"),
+        }
+    }
+}
+
+/// Text surrounding a match.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Snippet {
+    /// Text before the match.
+    pub before: String,
+    /// The matched text, as found in the original.
+    pub matched: String,
+    /// Text after the match.
+    pub after: String,
+}
+
+impl fmt::Display for Snippet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}{}", self.before, self.matched, self.after)
+    }
+}
+
+/// A search hit.
+#[derive(Debug, Clone, Serialize)]
+pub struct Hit {
+    /// Object kind.
+    pub kind: Kind,
+    /// Object id.
+    pub id: ObjectId,
+    /// Where in the object the query was found.
+    pub field: Field,
+    /// Author of the matching text.
+    pub author: Author,
+    /// Matching text, with context.
+    pub snippet: Snippet,
+    /// When the matching text was written.
+    pub timestamp: Timestamp,
+}
+
+/// Search an issue for the given query.
+pub fn issue(id: &ObjectId, issue: &Issue, query: &str) -> Vec<Hit> {
+    let mut hits = Vec::new();
+    let mut search = |field, author: &Author, text: &str, timestamp| {
+        if let Some(snippet) = snippet(text, query, SNIPPET_CONTEXT) {
+            hits.push(Hit {
+                kind: Kind::Issue,
+                id: *id,
+                field,
+                author: author.clone(),
+                snippet,
+                timestamp,
+            });
+        }
+    };
+
+    search(Field::Title, &issue.author, &issue.title, issue.timestamp);
+    search(
+        Field::Description,
+        &issue.comment.author,
+        &issue.comment.body,
+        issue.comment.timestamp,
+    );
+    for comment in &issue.discussion {
+        search(
+            Field::Comment,
+            &comment.author,
+            &comment.body,
+            comment.timestamp,
+        );
+        for reply in &comment.replies {
+            search(Field::Reply, &reply.author, &reply.body, reply.timestamp);
+        }
+    }
+    hits
+}
+
+/// Search a patch for the given query. All revisions are searched.
+pub fn patch(id: &ObjectId, patch: &Patch, query: &str) -> Vec<Hit> {
+    let mut hits = Vec::new();
+    let mut search = |field, author: &Author, text: &str, timestamp| {
+        if let Some(snippet) = snippet(text, query, SNIPPET_CONTEXT) {
+            hits.push(Hit {
+                kind: Kind::Patch,
+                id: *id,
+                field,
+                author: author.clone(),
+                snippet,
+                timestamp,
+            });
+        }
+    };
+
+    search(Field::Title, &patch.author, &patch.title, patch.timestamp);
+
+    for revision in patch.revisions.iter() {
+        let comment = &revision.comment;
+        search(
+            Field::Description,
+            &comment.author,
+            &comment.body,
+            comment.timestamp,
+        );
+
+        let threads = revision
+            .discussion
+            .iter()
+            .map(|c| (Field::Comment, c))
+            .chain(
+                revision
+                    .reviews
+                    .values()
+                    .map(|r| (Field::Review, &r.comment)),
+            );
+
+        for (field, comment) in threads {
+            search(field, &comment.author, &comment.body, comment.timestamp);
+
+            for reply in &comment.replies {
+                search(Field::Reply, &reply.author, &reply.body, reply.timestamp);
+            }
+        }
+        for review in revision.reviews.values() {
+            for inline in &review.inline {
+                let comment = &inline.comment;
+                search(
+                    Field::Review,
+                    &comment.author,
+                    &comment.body,
+                    comment.timestamp,
+                );
+            }
+        }
+    }
+    hits
+}
+
+/// Find the first case-insensitive occurence of `query` in `text`, and return
+/// it along with up to `context` characters on either side.
+pub fn snippet(text: &str, query: &str, context: usize) -> Option<Snippet> {
+    let query: Vec<char> = query.chars().flat_map(char::to_lowercase).collect();
+    if query.is_empty() {
+        return None;
+    }
+    // Nb. We compare character by character instead of lower-casing the whole text,
+    // since lower-casing can change the byte length of the text.
+    let chars: Vec<char> = text.chars().collect();
+    let start = (0..chars.len()).find(|&i| {
+        let mut haystack = chars[i..].iter().flat_map(|c| c.to_lowercase());
+        query.iter().all(|q| haystack.next() == Some(*q))
+    })?;
+
+    // Find how many characters of the text the match spans.
+    let mut end = start;
+    let mut matched = 0;
+    while matched < query.len() && end < chars.len() {
+        matched += chars[end].to_lowercase().count();
+        end += 1;
+    }
+
+    let flatten = |s: &[char]| -> String {
+        s.iter()
+            .map(|c| if c.is_whitespace() { ' ' } else { *c })
+            .collect()
+    };
+    let from = start.saturating_sub(context);
+    let to = (end + context).min(chars.len());
+
+    let mut before = flatten(&chars[from..start]);
+    let mut after = flatten(&chars[end..to]);
+
+    if from > 0 {
+        before.insert(0, '…');
+    }
+    if to < chars.len() {
+        after.push('…');
+    }
+
+    Some(Snippet {
+        before,
+        matched: flatten(&chars[start..end]),
+        after,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::str::FromStr;
+
+    use radicle_git_ext as git;
+
+    use crate::cobs::patch::{CodeComment, CodeLocation, MergeTarget, Verdict};
+    use crate::cobs::shared::{Comment, CommentId, Store};
+    use crate::test;
+
+    fn fields(hits: &[Hit]) -> Vec<Field> {
+        hits.iter().map(|h| h.field).collect()
+    }
+
+    #[test]
+    fn test_search_issue() {
+        let (storage, profile, whoami, project) = test::setup::profile();
+        let cobs = Store::new(whoami, profile.paths(), &storage);
+        let issues = cobs.issues();
+        let project = project.urn();
+        let issue_id = issues
+            .create(&project, "QUIC timeout", "Connections time out.", &[])
+            .unwrap();
+
+        issues
+            .comment(&project, &issue_id, "Still seeing the quic timeout.")
+            .unwrap();
+        issues
+            .reply(
+                &project,
+                &issue_id,
+                CommentId::root(),
+                "Same QUIC issue here.",
+            )
+            .unwrap();
+
+        let issue = issues.get(&project, &issue_id).unwrap().unwrap();
+        let hits = super::issue(&issue_id, &issue, "quic");
+
+        assert_eq!(
+            fields(&hits),
+            vec![Field::Title, Field::Comment, Field::Reply]
+        );
+        assert!(hits
+            .iter()
+            .all(|h| h.kind == Kind::Issue && h.id == issue_id));
+        assert_eq!(hits[0].snippet.matched, "QUIC");
+        assert_eq!(hits[1].snippet.matched, "quic");
+
+        let hits = super::issue(&issue_id, &issue, "time out");
+        assert_eq!(fields(&hits), vec![Field::Description]);
+
+        assert!(super::issue(&issue_id, &issue, "tcp").is_empty());
+    }
+
+    #[test]
+    fn test_search_patch() {
+        let (storage, profile, whoami, project) = test::setup::profile();
+        let cobs = Store::new(whoami.clone(), profile.paths(), &storage);
+        let patches = cobs.patches();
+        let project = project.urn();
+        let base = git::Oid::from_str("cb18e95ada2bb38aadd8e6cef0963ce37a87add3").unwrap();
+        let oid = git::Oid::from_str("518d5069f94c03427f694bb494ac1cd7d1339380").unwrap();
+        let patch_id = patches
+            .create(
+                &project,
+                "Fix QUIC timeout",
+                "Raise the idle timeout.",
+                MergeTarget::Upstream,
+                base,
+                oid,
+                &[],
+            )
+            .unwrap();
+
+        patches
+            .comment(&project, &patch_id, 0, "Does this fix the quic hang?")
+            .unwrap();
+        patches
+            .reply(
+                &project,
+                &patch_id,
+                0,
+                CommentId::root(),
+                "It fixes the QUIC hang.",
+            )
+            .unwrap();
+        patches
+            .review(
+                &project,
+                &patch_id,
+                0,
+                Some(Verdict::Accept),
+                "QUIC changes look good.",
+                vec![],
+            )
+            .unwrap();
+
+        let mut patch = patches.get(&project, &patch_id).unwrap().unwrap();
+        let hits = super::patch(&patch_id, &patch, "quic");
+
+        assert_eq!(
+            fields(&hits),
+            vec![Field::Title, Field::Comment, Field::Reply, Field::Review]
+        );
+        assert!(hits
+            .iter()
+            .all(|h| h.kind == Kind::Patch && h.id == patch_id));
+
+        let hits = super::patch(&patch_id, &patch, "idle");
+        assert_eq!(fields(&hits), vec![Field::Description]);
+
+        // Inline review comments are searched too.
+        let review = patch.revisions.head.reviews.get_mut(&whoami.urn()).unwrap();
+        review.inline.push(CodeComment {
+            location: CodeLocation {
+                lines: 1..=2,
+                commit: oid,
+                blob: base,
+            },
+            comment: Comment::new(
+                review.author.clone(),
+                String::from("Keep this idle timeout short."),
+                review.timestamp,
+            ),
+        });
+
+        let hits = super::patch(&patch_id, &patch, "idle");
+        assert_eq!(fields(&hits), vec![Field::Description, Field::Review]);
+        assert_eq!(hits[1].snippet.after, " timeout short.");
+    }
+
+    #[test]
+    fn test_snippet() {
+        let s = snippet("We hit the QUIC timeout again.", "quic timeout", 4).unwrap();
+        assert_eq!(s.before, "…the ");
+        assert_eq!(s.matched, "QUIC timeout");
+        assert_eq!(s.after, " aga…");
+
+        let s = snippet("QUIC", "quic", 4).unwrap();
+        assert_eq!(s.to_string(), "QUIC");
+
+        let s = snippet("Line one\nline two", "one", 10).unwrap();
+        assert_eq!(s.to_string(), "Line one line two");
+
+        assert!(snippet("Nothing to see here", "quic", 4).is_none());
+        assert!(snippet("Nothing to see here", "", 4).is_none());
+    }
+
+    #[test]
+    fn test_snippet_unicode() {
+        let s = snippet("Über die Brücke", "BRÜCKE", 2).unwrap();
+        assert_eq!(s.before, "…e ");
+        assert_eq!(s.matched, "Brücke");
+        assert_eq!(s.after, "");
+    }
+}
@@ -13,7 +13,7 @@ use std::time::{SystemTime, UNIX_EPOCH};
 use automerge::transaction::Transactable;
 use automerge::{Automerge, AutomergeError, ObjType, ScalarValue, Value};
 use chrono::TimeZone;
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 use librad::collaborative_objects;
 use librad::collaborative_objects::{CollaborativeObjects, History, ObjectId, TypeName};
@@ -26,7 +26,8 @@ use librad::profile::Profile;
 use librad::PeerId;
 use radicle_git_ext as git;
 
-use crate::cobs::{issue, patch, user};
+use crate::cobs::{cache, issue, label, patch, policy, user};
+use crate::petname::Petnames;
 use crate::{person, project};
 
 #[derive(Debug, thiserror::Error)]
@@ -39,6 +40,10 @@ pub enum Error {
     Retrieve(#[from] collaborative_objects::error::Retrieve),
     #[error(transparent)]
     Automerge(#[from] AutomergeError),
+    #[error("cache error: {0}")]
+    Cache(#[from] cache::Error),
+    #[error("policy error: {0}")]
+    Policy(anyhow::Error),
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -81,6 +86,16 @@ impl FromStr for Identifier {
     }
 }
 
+impl Identifier {
+    /// Check whether the given object id is identified by this identifier.
+    pub fn matches(&self, id: &ObjectId) -> bool {
+        match self {
+            Self::Full(full) => full == id,
+            Self::Prefix(prefix) => id.to_string().starts_with(prefix.as_str()),
+        }
+    }
+}
+
 impl fmt::Display for Identifier {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -90,12 +105,37 @@ impl fmt::Display for Identifier {
     }
 }
 
+/// Type names of all known objects that live under project namespaces.
+pub fn typenames() -> [&'static TypeName; 3] {
+    [&*issue::TYPENAME, &*patch::TYPENAME, &*label::TYPENAME]
+}
+
+/// Short, human-readable name of an object type, eg. `issue` for `xyz.radicle.issue`.
+pub fn kind(typename: &TypeName) -> String {
+    let name = typename.to_string();
+
+    match name.rsplit('.').next() {
+        Some(kind) => kind.to_owned(),
+        None => name,
+    }
+}
+
 /// A collaborative object. Objects of this type can be turned into rust types.
 pub trait Cob: Sized {
     /// The object type name.
     fn type_name() -> &'static TypeName;
     /// Create an object from a history.
     fn from_history(history: &History) -> Result<Self, anyhow::Error>;
+    /// Create an object from a history, skipping the changes that aren't allowed
+    /// under the given policy. Returns the object along with the skipped changes.
+    ///
+    /// By default, all changes are allowed.
+    fn from_history_authorized(
+        history: &History,
+        _ctx: &policy::Context,
+    ) -> Result<(Self, Vec<policy::Violation>), anyhow::Error> {
+        Self::from_history(history).map(|obj| (obj, vec![]))
+    }
 }
 
 pub struct Store<'a> {
@@ -103,6 +143,8 @@ pub struct Store<'a> {
     pub peer_id: PeerId,
 
     store: CollaborativeObjects<'a>,
+    storage: &'a Storage,
+    cache: cache::Cache,
 }
 
 impl<'a> Deref for Store<'a> {
@@ -117,14 +159,21 @@ impl<'a> Store<'a> {
     pub fn new(whoami: LocalIdentity, paths: &Paths, storage: &'a Storage) -> Self {
         let store = storage.collaborative_objects(Some(paths.cob_cache_dir().to_path_buf()));
         let peer_id = *storage.peer_id();
+        let cache = cache::Cache::open(paths);
 
         Self {
             store,
+            storage,
             whoami,
             peer_id,
+            cache,
         }
     }
 
+    pub fn cache(&self) -> &cache::Cache {
+        &self.cache
+    }
+
     pub fn author(&self) -> Author {
         Author::new(self.whoami.urn(), self.peer_id)
     }
@@ -141,12 +190,43 @@ impl<'a> Store<'a> {
         user::UserStore::new(self)
     }
 
+    /// Record an event in the local user's activity feed. Since the feed is only
+    /// informational, failures are logged and otherwise ignored.
+    pub fn record(&self, event: user::Event) {
+        if let Err(err) = self.users().record(event) {
+            log::warn!("Failed to record user activity: {}", err);
+        }
+    }
+
+    /// Get the authorization context of a namespace.
+    pub fn policy(&self, namespace: &Urn) -> anyhow::Result<policy::Context> {
+        policy::Context::load(self.storage, namespace)
+    }
+
     pub fn get<T: Cob>(&self, namespace: &Urn, id: &ObjectId) -> anyhow::Result<Option<T>> {
+        let obj = self.get_authorized::<T>(namespace, id)?;
+
+        Ok(obj.map(|(obj, violations)| {
+            for v in violations {
+                log::warn!("Skipped change to {} {}: {}", T::type_name(), id, v);
+            }
+            obj
+        }))
+    }
+
+    /// Get an object, along with the changes that were skipped because they were
+    /// not authorized.
+    pub fn get_authorized<T: Cob>(
+        &self,
+        namespace: &Urn,
+        id: &ObjectId,
+    ) -> anyhow::Result<Option<(T, Vec<policy::Violation>)>> {
         let cob = self.store.retrieve(namespace, T::type_name(), id)?;
 
         if let Some(cob) = cob {
+            let ctx = self.policy(namespace)?;
             let history = cob.history();
-            let obj = T::from_history(history)?;
+            let obj = T::from_history_authorized(history, &ctx)?;
 
             Ok(Some(obj))
         } else {
@@ -154,6 +234,67 @@ impl<'a> Store<'a> {
         }
     }
 
+    /// Get all objects of the given type, using the on-disk index.
+    pub fn all<T>(&self, namespace: &Urn) -> Result<Vec<(ObjectId, T)>, Error>
+    where
+        T: Cob + Clone + Serialize + DeserializeOwned,
+    {
+        let ctx = self.policy(namespace).map_err(Error::Policy)?;
+
+        self.cache
+            .all::<T>(&self.store, namespace, &ctx)
+            .map_err(Error::from)
+    }
+
+    /// Get the objects of the given type that match a predicate, using the on-disk index.
+    pub fn filter<T, P>(&self, namespace: &Urn, predicate: P) -> Result<Vec<(ObjectId, T)>, Error>
+    where
+        T: Cob + Clone + Serialize + DeserializeOwned,
+        P: Fn(&T) -> bool,
+    {
+        let ctx = self.policy(namespace).map_err(Error::Policy)?;
+
+        self.cache
+            .filter::<T, P>(&self.store, namespace, &ctx, predicate)
+            .map_err(Error::from)
+    }
+
+    /// Update the index entry of an object after it was written to. Since the index
+    /// is only a cache, failures are logged and otherwise ignored.
+    pub fn reindex<T>(&self, namespace: &Urn, id: &ObjectId)
+    where
+        T: Cob + Serialize + DeserializeOwned,
+    {
+        let result = self.policy(namespace).and_then(|ctx| {
+            self.cache
+                .update::<T>(&self.store, namespace, id, &ctx)
+                .map_err(anyhow::Error::from)
+        });
+
+        if let Err(err) = result {
+            log::warn!("Failed to index {} {}: {}", T::type_name(), id, err);
+        }
+    }
+
+    /// Bring the index of a namespace up to date, eg. after changes were fetched
+    /// from other peers. Failures are logged and otherwise ignored.
+    pub fn refresh(&self, namespace: &Urn) {
+        if let Err(err) = self.all::<issue::Issue>(namespace) {
+            log::warn!("Failed to index issues of {}: {}", namespace, err);
+        }
+        if let Err(err) = self.all::<patch::Patch>(namespace) {
+            log::warn!("Failed to index patches of {}: {}", namespace, err);
+        }
+        if let Err(err) = self.all::<label::Label>(namespace) {
+            log::warn!("Failed to index labels of {}: {}", namespace, err);
+        }
+    }
+
+    /// Count the objects of the given type, without loading them.
+    pub fn count<T: Cob>(&self, namespace: &Urn) -> Result<usize, Error> {
+        self.cache.count::<T>(namespace).map_err(Error::from)
+    }
+
     pub fn resolve<T: Cob>(
         &self,
         namespace: &Urn,
@@ -168,6 +309,43 @@ impl<'a> Store<'a> {
         }
     }
 
+    /// Resolve an identifier to an object of any known type. Fails if the identifier
+    /// matches more than one object, even if they are of different types.
+    pub fn resolve_any(
+        &self,
+        namespace: &Urn,
+        identifier: &Identifier,
+    ) -> anyhow::Result<Option<(TypeName, ObjectId)>> {
+        let mut matches = Vec::new();
+
+        for typename in typenames() {
+            let ids = self.cache.ids_of(namespace, typename)?;
+
+            matches.extend(
+                ids.into_iter()
+                    .filter(|id| identifier.matches(id))
+                    .map(|id| (typename.clone(), id)),
+            );
+        }
+
+        match matches.as_slice() {
+            [] => Ok(None),
+            [(typename, id)] => Ok(Some((typename.clone(), *id))),
+            [..] => {
+                let candidates = matches
+                    .iter()
+                    .map(|(typename, id)| format!("{} {}", kind(typename), id))
+                    .collect::<Vec<_>>();
+
+                anyhow::bail!(
+                    "object id `{}` is ambiguous, it could be any of: {}",
+                    identifier,
+                    candidates.join(", ")
+                );
+            }
+        }
+    }
+
     pub fn resolve_id<T: Cob>(
         &self,
         project: &Urn,
@@ -176,11 +354,10 @@ impl<'a> Store<'a> {
         match identifier {
             Identifier::Full(id) => Ok(Some(*id)),
             Identifier::Prefix(prefix) => {
-                let cobs = self.store.list(project, T::type_name())?;
+                let ids = self.cache.ids::<T>(project)?;
 
-                let matches = cobs
+                let matches = ids
                     .into_iter()
-                    .map(|c| *c.id())
                     .filter(|id| id.to_string().starts_with(prefix))
                     .collect::<Vec<_>>();
 
@@ -337,6 +514,9 @@ impl<'a> Deserialize<'a> for Color {
 pub struct AuthorProfile {
     pub name: String,
     pub ens: Option<person::Ens>,
+    /// Devices of the author, see [`person::verified_devices`].
+    #[serde(default)]
+    pub devices: Vec<PeerId>,
 }
 
 /// Author.
@@ -357,10 +537,19 @@ impl Author {
         }
     }
 
-    pub fn name(&self) -> String {
-        self.profile
+    /// Name of the author: the petname of the author's peer, or of one of the
+    /// author's other devices, if any. Otherwise, the name of its identity, or its URN.
+    pub fn name(&self, petnames: &Petnames) -> String {
+        let devices = self
+            .profile
             .as_ref()
-            .map(|p| p.name.clone())
+            .map(|p| p.devices.as_slice())
+            .unwrap_or_default();
+
+        petnames
+            .of_person(&self.peer, devices)
+            .map(ToOwned::to_owned)
+            .or_else(|| self.profile.as_ref().map(|p| p.name.clone()))
             .unwrap_or_else(|| self.urn.encode_id())
     }
 
@@ -379,6 +568,7 @@ impl Author {
             self.profile = Some(AuthorProfile {
                 name: identity.name,
                 ens: identity.ens,
+                devices: identity.devices,
             });
         }
         Ok(self)
@@ -417,6 +607,10 @@ pub type Replies = Vec<Comment>;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Comment<R = ()> {
+    /// Id of the document object the comment is stored in. Unlike the position of
+    /// the comment, it doesn't change when comments are added concurrently. Empty
+    /// for comments that weren't read from a document.
+    pub id: String,
     pub author: Author,
     pub body: String,
     pub reactions: HashMap<Reaction, usize>,
@@ -427,6 +621,7 @@ pub struct Comment<R = ()> {
 impl<R: Default> Comment<R> {
     pub fn new(author: Author, body: String, timestamp: Timestamp) -> Self {
         Self {
+            id: String::new(),
             author,
             body,
             reactions: HashMap::default(),
@@ -501,6 +696,43 @@ impl Comment<Replies> {
     }
 }
 
+/// A change of an object's state, eg. an issue being closed or re-opened.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateChange {
+    /// Id of the document object the state change is stored in. Empty for state
+    /// changes that weren't read from a document.
+    pub id: String,
+    /// Who changed the state.
+    pub author: Author,
+    /// When the state was changed.
+    pub timestamp: Timestamp,
+}
+
+impl StateChange {
+    pub fn new(author: Author, timestamp: Timestamp) -> Self {
+        Self {
+            id: String::new(),
+            author,
+            timestamp,
+        }
+    }
+
+    /// Put this state change into an automerge document, replacing the previous one.
+    pub(super) fn put(
+        &self,
+        tx: &mut automerge::transaction::Transaction,
+        id: &automerge::ObjId,
+    ) -> Result<(), AutomergeError> {
+        let change_id = tx.put_object(&id, "stateChange", ObjType::Map)?;
+
+        tx.put(&change_id, "author", self.author.urn().to_string())?;
+        tx.put(&change_id, "peer", self.author.peer.default_encoding())?;
+        tx.put(&change_id, "timestamp", self.timestamp)?;
+
+        Ok(())
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialOrd, PartialEq, Ord, Eq, Serialize, Deserialize)]
 #[serde(transparent)]
 pub struct Timestamp {
@@ -621,6 +853,19 @@ impl<'a> FromValue<'a> for git::OneLevel {
     }
 }
 
+impl<'a> FromValue<'a> for u64 {
+    fn from_value(val: Value<'a>) -> Result<u64, ValueError> {
+        if let Value::Scalar(scalar) = &val {
+            match scalar.borrow() {
+                ScalarValue::Uint(n) => return Ok(*n),
+                ScalarValue::Int(n) if *n >= 0 => return Ok(*n as u64),
+                _ => {}
+            }
+        }
+        Err(ValueError::InvalidValue(val.to_string()))
+    }
+}
+
 impl<'a> FromValue<'a> for String {
     fn from_value(val: Value) -> Result<String, ValueError> {
         val.into_string().map_err(|_| ValueError::InvalidType)
@@ -673,6 +918,32 @@ impl<'a> Document<'a> {
         lookup(*self, &obj_id)
     }
 
+    /// Like [`Document::lookup`], for properties that may be missing, eg. because they
+    /// were added to the layout later.
+    pub fn lookup_opt<V, O: AsRef<automerge::ObjId>, P: Into<automerge::Prop>>(
+        &self,
+        id: O,
+        prop: P,
+        lookup: fn(Document, &automerge::ObjId) -> Result<V, DocumentError>,
+    ) -> Result<Option<V>, DocumentError> {
+        match self.doc.get(id.as_ref(), prop.into())? {
+            Some((_, obj_id)) => lookup(*self, &obj_id).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Like [`Document::val`], for properties that may be missing.
+    pub fn val_opt<O: AsRef<automerge::ObjId>, P: Into<automerge::Prop>, V: FromValue<'a>>(
+        &self,
+        id: O,
+        prop: P,
+    ) -> Result<Option<V>, DocumentError> {
+        match self.doc.get(id.as_ref(), prop.into())? {
+            Some((val, _)) => V::from_value(val).map(Some).map_err(DocumentError::from),
+            None => Ok(None),
+        }
+    }
+
     pub fn list<V, O: AsRef<automerge::ObjId>, P: Into<automerge::Prop>>(
         &self,
         id: O,
@@ -686,7 +957,9 @@ impl<'a> Document<'a> {
             .get(id, prop.clone())?
             .ok_or_else(|| DocumentError::PropertyNotFound(prop.to_string()))?;
 
-        assert_eq!(list.to_objtype(), Some(ObjType::List));
+        if list.to_objtype() != Some(ObjType::List) {
+            return Err(DocumentError::Property);
+        }
 
         let mut objs: Vec<V> = Vec::new();
         for i in 0..self.length(&list_id) {
@@ -720,7 +993,9 @@ impl<'a> Document<'a> {
             .get(id, prop.clone())?
             .ok_or_else(|| DocumentError::PropertyNotFound(prop.to_string()))?;
 
-        assert_eq!(obj.to_objtype(), Some(ObjType::Map));
+        if obj.to_objtype() != Some(ObjType::Map) {
+            return Err(DocumentError::Property);
+        }
 
         let mut map = HashMap::new();
         for key in self.doc.keys(&obj_id) {
@@ -751,7 +1026,9 @@ impl<'a> Document<'a> {
             .get(id, prop.clone())?
             .ok_or_else(|| DocumentError::PropertyNotFound(prop.to_string()))?;
 
-        assert_eq!(obj.to_objtype(), Some(ObjType::List));
+        if obj.to_objtype() != Some(ObjType::List) {
+            return Err(DocumentError::Property);
+        }
 
         let mut acc = T::default();
         for i in 0..self.doc.length(&obj_id) {
@@ -779,7 +1056,9 @@ impl<'a> Document<'a> {
             .get(id, prop.clone())?
             .ok_or_else(|| DocumentError::PropertyNotFound(prop.to_string()))?;
 
-        assert_eq!(obj.to_objtype(), Some(ObjType::Map));
+        if obj.to_objtype() != Some(ObjType::Map) {
+            return Err(DocumentError::Property);
+        }
 
         let mut keys = HashSet::new();
         for key in self.doc.keys(&obj_id) {
@@ -815,9 +1094,26 @@ pub enum DocumentError {
 }
 
 pub mod lookup {
-    use super::{Author, Comment, HashMap, Reaction, Replies};
+    use super::{Author, Comment, HashMap, Reaction, Replies, StateChange};
     use super::{Document, DocumentError};
 
+    pub fn state_change(
+        doc: Document,
+        obj_id: &automerge::ObjId,
+    ) -> Result<StateChange, DocumentError> {
+        let peer = doc.val(&obj_id, "peer")?;
+        let author = doc
+            .val(&obj_id, "author")
+            .map(|urn| Author::new(urn, peer))?;
+        let timestamp = doc.val(&obj_id, "timestamp")?;
+
+        Ok(StateChange {
+            id: obj_id.to_string(),
+            author,
+            timestamp,
+        })
+    }
+
     pub fn comment(doc: Document, obj_id: &automerge::ObjId) -> Result<Comment<()>, DocumentError> {
         let peer = doc.val(&obj_id, "peer")?;
         let author = doc
@@ -828,6 +1124,7 @@ pub mod lookup {
         let reactions: HashMap<Reaction, usize> = doc.map(&obj_id, "reactions", |v| *v += 1)?;
 
         Ok(Comment {
+            id: obj_id.to_string(),
             author,
             body,
             reactions,
@@ -844,6 +1141,7 @@ pub mod lookup {
         let replies = doc.list(&obj_id, "replies", self::comment)?;
 
         Ok(Comment {
+            id: comment.id,
             author: comment.author,
             body: comment.body,
             reactions: comment.reactions,
@@ -853,6 +1151,123 @@ pub mod lookup {
     }
 }
 
+/// Schema version of a COB document.
+pub type Version = u64;
+
+/// Key under which the schema version is stored, in the root of every COB document.
+/// Documents without this key were created before versioning, and are at version `0`.
+pub const VERSION_KEY: &str = "version";
+
+/// Error migrating a document.
+#[derive(thiserror::Error, Debug)]
+pub enum MigrationError {
+    #[error("document version {found} is not supported (latest is {latest}); please upgrade")]
+    Unsupported { found: Version, latest: Version },
+    #[error(transparent)]
+    Document(#[from] DocumentError),
+    #[error(transparent)]
+    Automerge(#[from] AutomergeError),
+}
+
+/// A schema migration, upgrading a document from one version to the next.
+pub struct Migration {
+    /// Version this migration upgrades from. Migrations upgrade to `from + 1`.
+    pub from: Version,
+    /// What the migration changes.
+    pub description: &'static str,
+    /// Migrate the document layout.
+    pub migrate: fn(&mut automerge::transaction::Transaction) -> Result<(), AutomergeError>,
+}
+
+/// The ordered list of migrations of a COB type. The latest version of the
+/// type is the number of migrations.
+pub struct Migrations {
+    steps: &'static [Migration],
+}
+
+impl Migrations {
+    pub const fn new(steps: &'static [Migration]) -> Self {
+        Self { steps }
+    }
+
+    /// Latest schema version.
+    pub fn latest(&self) -> Version {
+        self.steps.len() as Version
+    }
+
+    /// Upgrade a document to the latest version. Returns the previous version of the document.
+    ///
+    /// Nb. The changes made by the migration are local to the given document and should
+    /// never be published, since they are not part of the object history.
+    pub fn apply(&self, doc: &mut Automerge) -> Result<Version, MigrationError> {
+        let found = version(doc)?;
+        let latest = self.latest();
+
+        if found > latest {
+            return Err(MigrationError::Unsupported { found, latest });
+        }
+        if found == latest {
+            return Ok(found);
+        }
+
+        doc.transact_with::<_, _, AutomergeError, _, ()>(
+            |_| {
+                automerge::transaction::CommitOptions::default()
+                    .with_message(format!("Migrate from version {} to {}", found, latest))
+            },
+            |tx| {
+                for (ix, step) in self.steps.iter().enumerate().skip(found as usize) {
+                    debug_assert_eq!(step.from, ix as Version);
+                    (step.migrate)(tx)?;
+                }
+                tx.put(automerge::ObjId::Root, VERSION_KEY, latest)?;
+
+                Ok(())
+            },
+        )
+        .map_err(|failure| failure.error)?;
+
+        Ok(found)
+    }
+}
+
+/// Migration from the original, unversioned layout. Only adds the version marker.
+pub fn unversioned(_tx: &mut automerge::transaction::Transaction) -> Result<(), AutomergeError> {
+    Ok(())
+}
+
+/// Get the schema version of a document.
+pub fn version(doc: &Automerge) -> Result<Version, DocumentError> {
+    match doc.get(automerge::ObjId::Root, VERSION_KEY)? {
+        Some((val, _)) => Version::from_value(val).map_err(DocumentError::from),
+        None => Ok(0),
+    }
+}
+
+/// Get the migrations registered for a COB type.
+pub fn migrations(typename: &TypeName) -> Option<&'static Migrations> {
+    if typename == &*issue::TYPENAME {
+        Some(&issue::MIGRATIONS)
+    } else if typename == &*patch::TYPENAME {
+        Some(&patch::MIGRATIONS)
+    } else if typename == &*label::TYPENAME {
+        Some(&label::MIGRATIONS)
+    } else if typename == &*user::TYPENAME {
+        Some(&user::MIGRATIONS)
+    } else {
+        None
+    }
+}
+
+/// Upgrade a document of the given type to its latest version, if any migrations
+/// are registered for the type.
+pub fn migrate(doc: &mut Automerge, typename: &TypeName) -> Result<Version, MigrationError> {
+    match migrations(typename) {
+        Some(migrations) => migrations.apply(doc),
+        None => version(doc).map_err(MigrationError::from),
+    }
+}
+
 pub fn store<'a>(profile: &Profile, storage: &'a Storage) -> anyhow::Result<Store<'a>> {
     let whoami = person::local(storage)?;
     let cobs = Store::new(whoami, profile.paths(), storage);
@@ -883,4 +1298,90 @@ mod test {
         Color::from_str("#aa00").unwrap_err();
         Color::from_str("#abc").unwrap_err();
     }
+
+    #[test]
+    fn test_resolve_any() {
+        let (storage, profile, whoami, project) = crate::test::setup::profile();
+        let cobs = Store::new(whoami, profile.paths(), &storage);
+        let project = project.urn();
+        let issue_id = cobs
+            .issues()
+            .create(&project, "My first issue", "Blah blah blah.", &[])
+            .unwrap();
+        let patch_id = cobs
+            .patches()
+            .create(
+                &project,
+                "My first patch",
+                "Blah blah blah.",
+                patch::MergeTarget::Upstream,
+                git::Oid::from(git2::Oid::zero()),
+                git::Oid::from(git2::Oid::zero()),
+                &[],
+            )
+            .unwrap();
+
+        let (typename, id) = cobs
+            .resolve_any(&project, &Identifier::Full(issue_id))
+            .unwrap()
+            .unwrap();
+        assert_eq!(&typename, &*issue::TYPENAME);
+        assert_eq!(id, issue_id);
+
+        let prefix = Identifier::Prefix(patch_id.to_string()[..12].to_owned());
+        let (typename, id) = cobs.resolve_any(&project, &prefix).unwrap().unwrap();
+        assert_eq!(&typename, &*patch::TYPENAME);
+        assert_eq!(id, patch_id);
+
+        // The empty prefix matches everything.
+        let prefix = Identifier::Prefix(String::new());
+        assert!(cobs.resolve_any(&project, &prefix).is_err());
+
+        assert_eq!(kind(&issue::TYPENAME), "issue");
+    }
+
+    #[test]
+    fn test_migrations() {
+        fn add_labels(tx: &mut automerge::transaction::Transaction) -> Result<(), AutomergeError> {
+            tx.put_object(automerge::ObjId::Root, "labels", ObjType::Map)?;
+            Ok(())
+        }
+        static MIGRATIONS: Migrations = Migrations::new(&[
+            Migration {
+                from: 0,
+                description: "Add version marker",
+                migrate: unversioned,
+            },
+            Migration {
+                from: 1,
+                description: "Add labels",
+                migrate: add_labels,
+            },
+        ]);
+        let mut doc = Automerge::new();
+
+        assert_eq!(version(&doc).unwrap(), 0);
+        assert_eq!(MIGRATIONS.apply(&mut doc).unwrap(), 0);
+        assert_eq!(version(&doc).unwrap(), 2);
+        assert!(doc.get(automerge::ObjId::Root, "labels").unwrap().is_some());
+
+        // Migrating an up-to-date document is a no-op.
+        assert_eq!(MIGRATIONS.apply(&mut doc).unwrap(), 2);
+
+        // Documents from the future are rejected.
+        let mut doc = Automerge::new();
+        doc.transact_with::<_, _, AutomergeError, _, ()>(
+            |_| automerge::transaction::CommitOptions::default(),
+            |tx| tx.put(automerge::ObjId::Root, VERSION_KEY, 3u64),
+        )
+        .unwrap();
+
+        assert!(matches!(
+            MIGRATIONS.apply(&mut doc),
+            Err(MigrationError::Unsupported {
+                found: 3,
+                latest: 2
+            })
+        ));
+    }
 }
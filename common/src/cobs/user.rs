@@ -1,9 +1,11 @@
 use std::collections::HashSet;
 use std::convert::TryFrom;
+use std::fmt;
 use std::ops::{ControlFlow, Deref};
 use std::str::FromStr;
+use std::sync::Arc;
 
-use automerge::{Automerge, AutomergeError, ObjType};
+use automerge::{Automerge, AutomergeError, ObjType, ScalarValue, Value};
 use serde::{Deserialize, Serialize};
 
 use librad::collaborative_objects::{
@@ -13,17 +15,156 @@ use librad::collaborative_objects::{
 use librad::git::identities::local::LocalIdentity;
 use librad::git::Urn;
 
+use crate::cobs::patch::{RevisionIx, Verdict};
+use crate::cobs::shared;
 use crate::cobs::shared::*;
+use crate::project;
 
 lazy_static::lazy_static! {
     pub static ref TYPENAME: TypeName = FromStr::from_str("xyz.radicle.user").unwrap();
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Schema migrations of user documents. The latest version is written on creation.
+pub static MIGRATIONS: Migrations = Migrations::new(&[Migration {
+    from: 0,
+    description: "Add version marker",
+    migrate: shared::unversioned,
+}]);
+
+/// An event in a user's activity feed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase", tag = "type")]
-pub enum Event {}
+pub enum Event {
+    /// Opened an issue.
+    #[serde(rename_all = "camelCase")]
+    IssueOpened {
+        #[serde(deserialize_with = "project::deserialize_urn")]
+        project: Urn,
+        issue: ObjectId,
+        title: String,
+    },
+    /// Commented on an issue.
+    #[serde(rename_all = "camelCase")]
+    IssueCommented {
+        #[serde(deserialize_with = "project::deserialize_urn")]
+        project: Urn,
+        issue: ObjectId,
+    },
+    /// Proposed a patch.
+    #[serde(rename_all = "camelCase")]
+    PatchProposed {
+        #[serde(deserialize_with = "project::deserialize_urn")]
+        project: Urn,
+        patch: ObjectId,
+        title: String,
+    },
+    /// Updated a patch with a new revision.
+    #[serde(rename_all = "camelCase")]
+    PatchUpdated {
+        #[serde(deserialize_with = "project::deserialize_urn")]
+        project: Urn,
+        patch: ObjectId,
+        revision: RevisionIx,
+    },
+    /// Commented on a patch revision.
+    #[serde(rename_all = "camelCase")]
+    PatchCommented {
+        #[serde(deserialize_with = "project::deserialize_urn")]
+        project: Urn,
+        patch: ObjectId,
+        revision: RevisionIx,
+    },
+    /// Reviewed a patch revision.
+    #[serde(rename_all = "camelCase")]
+    PatchReviewed {
+        #[serde(deserialize_with = "project::deserialize_urn")]
+        project: Urn,
+        patch: ObjectId,
+        revision: RevisionIx,
+        verdict: Option<Verdict>,
+    },
+    /// Merged a patch revision.
+    #[serde(rename_all = "camelCase")]
+    PatchMerged {
+        #[serde(deserialize_with = "project::deserialize_urn")]
+        project: Urn,
+        patch: ObjectId,
+        revision: RevisionIx,
+    },
+}
 
-#[derive(Debug, Clone, Serialize)]
+impl Event {
+    /// The project this event happened in.
+    pub fn project(&self) -> &Urn {
+        match self {
+            Self::IssueOpened { project, .. }
+            | Self::IssueCommented { project, .. }
+            | Self::PatchProposed { project, .. }
+            | Self::PatchUpdated { project, .. }
+            | Self::PatchCommented { project, .. }
+            | Self::PatchReviewed { project, .. }
+            | Self::PatchMerged { project, .. } => project,
+        }
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use crate::fmt::cob;
+
+        match self {
+            Self::IssueOpened { issue, title, .. } => {
+                write!(f, "Opened issue {} \"{}\"", cob(issue), title)
+            }
+            Self::IssueCommented { issue, .. } => {
+                write!(f, "Commented on issue {}", cob(issue))
+            }
+            Self::PatchProposed { patch, title, .. } => {
+                write!(f, "Proposed patch {} \"{}\"", cob(patch), title)
+            }
+            Self::PatchUpdated {
+                patch, revision, ..
+            } => write!(f, "Updated patch {} to R{}", cob(patch), revision),
+            Self::PatchCommented {
+                patch, revision, ..
+            } => write!(f, "Commented on patch {} R{}", cob(patch), revision),
+            Self::PatchReviewed {
+                patch,
+                revision,
+                verdict,
+                ..
+            } => match verdict {
+                Some(verdict) => write!(
+                    f,
+                    "Reviewed patch {} R{} ({})",
+                    cob(patch),
+                    revision,
+                    verdict
+                ),
+                None => write!(f, "Reviewed patch {} R{}", cob(patch), revision),
+            },
+            Self::PatchMerged {
+                patch, revision, ..
+            } => write!(f, "Merged patch {} R{}", cob(patch), revision),
+        }
+    }
+}
+
+impl From<&Event> for ScalarValue {
+    fn from(event: &Event) -> Self {
+        let s = serde_json::to_string(event).unwrap(); // Cannot fail.
+        ScalarValue::from(s)
+    }
+}
+
+impl<'a> FromValue<'a> for Event {
+    fn from_value(value: Value) -> Result<Self, ValueError> {
+        let event = value.to_str().ok_or(ValueError::InvalidType)?;
+        serde_json::from_str(event).map_err(|e| ValueError::Other(Arc::new(e)))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Activity {
     #[serde(flatten)]
@@ -57,7 +198,7 @@ impl TryFrom<Document<'_>> for User {
         let urn = doc.val(&obj_id, "urn")?;
         let timestamp = doc.val(&obj_id, "timestamp")?;
         let projects = doc.keys(&obj_id, "projects")?;
-        let activity = vec![];
+        let activity = doc.list(&obj_id, "activity", lookup::activity)?;
 
         Ok(Self {
             urn,
@@ -72,7 +213,7 @@ impl TryFrom<&History> for User {
     type Error = anyhow::Error;
 
     fn try_from(history: &History) -> Result<Self, Self::Error> {
-        let doc = history.traverse(Automerge::new(), |mut doc, entry| {
+        let mut doc = history.traverse(Automerge::new(), |mut doc, entry| {
             match entry.contents() {
                 EntryContents::Automerge(bytes) => {
                     match automerge::Change::from_bytes(bytes.clone()) {
@@ -87,6 +228,7 @@ impl TryFrom<&History> for User {
             }
             ControlFlow::Continue(doc)
         });
+        shared::migrate(&mut doc, &TYPENAME)?;
         let user = User::try_from(Document::new(&doc))?;
 
         Ok(user)
@@ -150,6 +292,37 @@ impl<'a> UserStore<'a> {
         Ok(Some((*cob.id(), doc)))
     }
 
+    /// Append an event to the local user's activity feed. Creates the user object if
+    /// it doesn't exist yet.
+    pub fn record(&self, event: Event) -> Result<(), Error> {
+        let urn = self.whoami.urn();
+        let (user_id, mut user) = match self.local_raw(&urn)? {
+            Some(user) => user,
+            None => {
+                self.create()?;
+                self.local_raw(&urn)?
+                    .expect("UserStore::record: user must exist after creation")
+            }
+        };
+        let activity = Activity {
+            event,
+            timestamp: Timestamp::now(),
+        };
+        let changes = events::activity(&mut user, &activity)?;
+        let _cob = self.store.update(
+            &self.whoami,
+            &urn,
+            UpdateObjectSpec {
+                object_id: user_id,
+                typename: TYPENAME.clone(),
+                message: Some("Record activity".to_owned()),
+                changes,
+            },
+        )?;
+
+        Ok(())
+    }
+
     pub fn add_project(&mut self, project: Urn) -> Result<(), Error> {
         let urn = self.whoami.urn();
         let (user_id, mut user) = self.local_raw(&urn)?.unwrap();
@@ -206,6 +379,7 @@ mod events {
             |_| CommitOptions::default().with_message("Create user".to_owned()),
             |tx| {
                 let user = tx.put_object(ObjId::Root, "user", ObjType::Map)?;
+                tx.put(ObjId::Root, VERSION_KEY, MIGRATIONS.latest())?;
 
                 tx.put(&user, "urn", urn.to_string())?;
                 tx.put(&user, "timestamp", timestamp)?;
@@ -238,6 +412,43 @@ mod events {
 
         Ok(EntryContents::Automerge(change))
     }
+
+    pub fn activity(
+        user: &mut Automerge,
+        activity: &Activity,
+    ) -> Result<EntryContents, AutomergeError> {
+        user.transact_with::<_, _, AutomergeError, _, ()>(
+            |_| CommitOptions::default().with_message("Record activity".to_owned()),
+            |tx| {
+                let (_obj, obj_id) = tx.get(ObjId::Root, "user")?.unwrap();
+                let (_, activity_id) = tx.get(&obj_id, "activity")?.unwrap();
+
+                let length = tx.length(&activity_id);
+                let item_id = tx.insert_object(&activity_id, length, ObjType::Map)?;
+
+                tx.put(&item_id, "event", &activity.event)?;
+                tx.put(&item_id, "timestamp", activity.timestamp)?;
+
+                Ok(())
+            },
+        )
+        .map_err(|failure| failure.error)?;
+
+        let change = user.get_last_local_change().unwrap().raw_bytes().to_vec();
+
+        Ok(EntryContents::Automerge(change))
+    }
+}
+
+mod lookup {
+    use super::*;
+
+    pub fn activity(doc: Document, obj_id: &automerge::ObjId) -> Result<Activity, DocumentError> {
+        let event = doc.val(&obj_id, "event")?;
+        let timestamp = doc.val(&obj_id, "timestamp")?;
+
+        Ok(Activity { event, timestamp })
+    }
 }
 
 #[cfg(test)]
@@ -274,4 +485,57 @@ mod test {
         assert!(user.projects.contains(&project2));
         assert_eq!(user.projects.len(), 2);
     }
+
+    #[test]
+    fn test_activity() {
+        let (storage, profile, whoami, project) = test::setup::profile();
+        let cobs = Store::new(whoami, profile.paths(), &storage);
+        let issue = cobs
+            .issues()
+            .create(&project.urn(), "My first issue", "Blah blah blah.", &[])
+            .unwrap();
+        cobs.issues()
+            .comment(&project.urn(), &issue, "Ho ho ho.")
+            .unwrap();
+
+        let user = cobs.users().local().unwrap().unwrap();
+        let events = user
+            .activity
+            .iter()
+            .map(|a| a.event.clone())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            events,
+            vec![
+                Event::IssueOpened {
+                    project: project.urn(),
+                    issue,
+                    title: String::from("My first issue"),
+                },
+                Event::IssueCommented {
+                    project: project.urn(),
+                    issue,
+                },
+            ]
+        );
+        assert_eq!(events[0].project(), &project.urn());
+    }
+
+    #[test]
+    fn test_user_migrate_fixtures() {
+        // Users created before versioning.
+        let mut doc = test::fixture("user-v0.bin");
+
+        assert_eq!(shared::version(&doc).unwrap(), 0);
+        assert_eq!(shared::migrate(&mut doc, &TYPENAME).unwrap(), 0);
+        assert_eq!(shared::version(&doc).unwrap(), MIGRATIONS.latest());
+
+        let user = User::try_from(Document::new(&doc)).unwrap();
+        let project = Urn::from_str("rad:git:hnrkbtw9t1of4ykjy6er4qqwxtc54k9943eto").unwrap();
+
+        assert!(user.projects.contains(&project));
+        assert_eq!(user.projects.len(), 1);
+        assert!(user.activity.is_empty());
+    }
 }
@@ -5,8 +5,11 @@ use std::io;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
+use librad::git::storage::ReadOnly;
+use librad::git::Urn;
 use librad::profile::Profile;
 use librad::PeerId;
+use lnk_clib::seed::store::FileStore;
 use serde::{Deserialize, Serialize};
 use url::{Host, Url};
 
@@ -14,6 +17,7 @@ use crate::seed::{
     Address, Protocol, DEFAULT_SEED_API_PORT, DEFAULT_SEED_GIT_PORT, DEFAULT_SEED_P2P_PORT,
 };
 use crate::sync::Seed;
+use crate::{project, tracking};
 
 pub const DEFAULT_SEEDS: &[(&str, &str)] = &[
     (
@@ -45,6 +49,57 @@ pub struct SeedConfig {
     pub git: Url,
     /// HTTP API URL.
     pub api: Url,
+    /// Time allowed for each fetch or push, in seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+}
+
+impl SeedConfig {
+    /// Create a seed configuration from a seed address, using the default ports
+    /// for the git and API URLs.
+    pub fn new(seed: &Seed<String>, name: Option<String>) -> Result<Self, anyhow::Error> {
+        let url = Url::parse(&format!("rad://{}@{}", seed.peer, seed.addrs))?;
+        let host = url
+            .host()
+            .ok_or_else(|| anyhow::anyhow!("seed address '{}' is missing a host", seed.addrs))?
+            .to_owned();
+        let addr = Address {
+            port: url.port(),
+            ..Address::new(
+                host.clone(),
+                Protocol::Link {
+                    peer: Some(seed.peer),
+                },
+            )
+        };
+        let mut git: Url = Address::new(host.clone(), Protocol::Git { local: false }).into();
+        let mut api: Url = Address::new(host, Protocol::Api { local: false }).into();
+
+        git.set_port(Some(DEFAULT_SEED_GIT_PORT)).ok();
+        api.set_port(Some(DEFAULT_SEED_API_PORT)).ok();
+
+        Ok(Self {
+            name,
+            p2p: addr.into(),
+            git,
+            api,
+            timeout: None,
+        })
+    }
+
+    /// Peer id of the seed, if configured.
+    pub fn peer(&self) -> Option<PeerId> {
+        Address::try_from(self.p2p.clone())
+            .ok()
+            .and_then(|addr| addr.peer())
+    }
+
+    /// Check whether the seed is identified by the given name, peer id or host.
+    pub fn matches(&self, query: &str) -> bool {
+        self.name.as_deref() == Some(query)
+            || self.peer().map_or(false, |p| p.to_string() == query)
+            || self.p2p.host_str() == Some(query)
+    }
 }
 
 impl TryFrom<SeedConfig> for Seed<String> {
@@ -58,9 +113,35 @@ impl TryFrom<SeedConfig> for Seed<String> {
     }
 }
 
+/// Configuration scope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    /// The working copy, ie. `Radicle.toml`.
+    Local,
+    /// The profile, ie. `config.toml`.
+    Profile,
+}
+
+impl Scope {
+    pub fn path(&self, profile: &Profile) -> PathBuf {
+        match self {
+            Self::Local => PathBuf::from(FILE_NAME_LOCAL),
+            Self::Profile => Config::path(profile),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Config {
+    #[serde(default)]
     pub seed: Vec<SeedConfig>,
+    /// Tracking policy, only used in the local scope. See [`tracking`].
+    #[serde(default, skip_serializing_if = "tracking::Policy::is_default")]
+    pub tracking: tracking::Policy,
+    /// Whether the seeds of the `lnk` seeds file were merged into this configuration,
+    /// only used in the profile scope. See [`Config::migrate`].
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub migrated: bool,
 }
 
 impl Default for Config {
@@ -90,14 +171,26 @@ impl Default for Config {
                         p2p,
                         git,
                         api,
+                        timeout: None,
                     }
                 })
                 .collect(),
+            tracking: tracking::Policy::default(),
+            migrated: false,
         }
     }
 }
 
 impl Config {
+    /// A configuration without seeds.
+    pub fn empty() -> Self {
+        Self {
+            seed: Vec::new(),
+            tracking: tracking::Policy::default(),
+            migrated: false,
+        }
+    }
+
     pub fn read<P: AsRef<Path>>(path: P) -> Result<Self, io::Error> {
         let content = fs::read_to_string(path)?;
         let config = toml::from_str(&content)?;
@@ -105,8 +198,95 @@ impl Config {
         Ok(config)
     }
 
-    pub fn load(profile: &Profile) -> Result<Self, io::Error> {
-        Self::local().or_else(|_| Self::profile(profile))
+    /// Resolve the seed configuration, see [`Resolution`].
+    pub fn resolve(profile: &Profile) -> Resolution {
+        Self::resolve_from(
+            (Source::Local(Scope::Local.path(profile)), Self::local()),
+            profile,
+        )
+    }
+
+    /// Resolve the seed configuration of a project, which may not be the one checked
+    /// out in the current directory. See [`Config::of_project`].
+    pub fn resolve_project<S: AsRef<ReadOnly>>(
+        profile: &Profile,
+        storage: &S,
+        urn: &Urn,
+    ) -> Resolution {
+        Self::resolve_from(Self::of_project(storage, urn), profile)
+    }
+
+    fn resolve_from(project: (Source, Result<Self, io::Error>), profile: &Profile) -> Resolution {
+        let config = Self::profile(profile);
+        let migrated = matches!(&config, Ok(config) if config.migrated);
+        let mut candidates = vec![
+            project,
+            (Source::Profile(Scope::Profile.path(profile)), config),
+        ];
+
+        // Once imported into the profile, the `lnk` seeds are no longer used directly,
+        // so that seeds removed from the profile aren't brought back.
+        if !migrated {
+            candidates.push((
+                Source::Lnk(profile.paths().seeds_file().to_path_buf()),
+                Self::lnk(profile),
+            ));
+        }
+        Resolution::new(candidates)
+    }
+
+    /// Get the configuration of a project. If the current directory is a working
+    /// copy of the project, its `Radicle.toml` is used. Otherwise, the `Radicle.toml`
+    /// committed to the local head of the project's default branch is used.
+    pub fn of_project<S: AsRef<ReadOnly>>(
+        storage: &S,
+        urn: &Urn,
+    ) -> (Source, Result<Self, io::Error>) {
+        if matches!(project::cwd(), Ok((cwd, _)) if &cwd == urn) {
+            return (Source::Local(PathBuf::from(FILE_NAME_LOCAL)), Self::local());
+        }
+        let source = Source::Project(urn.clone());
+        let not_found = || {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} not found in {}", FILE_NAME_LOCAL, urn),
+            )
+        };
+        let head = project::get(storage, urn).ok().flatten().and_then(|meta| {
+            project::get_local_head(storage, urn, &meta.default_branch)
+                .ok()
+                .flatten()
+        });
+        let result = match head {
+            Some(head) => git2::Repository::open_bare(storage.as_ref().path())
+                .map_err(|err| io::Error::new(io::ErrorKind::Other, err))
+                .and_then(|repo| Self::committed(&repo, head))
+                .and_then(|config| config.ok_or_else(not_found)),
+            None => Err(not_found()),
+        };
+        (source, result)
+    }
+
+    /// Read the `Radicle.toml` committed at the given commit, if any.
+    pub fn committed(
+        repo: &git2::Repository,
+        commit: git2::Oid,
+    ) -> Result<Option<Self>, io::Error> {
+        let to_io = |err: git2::Error| io::Error::new(io::ErrorKind::Other, err);
+        let tree = repo
+            .find_commit(commit)
+            .and_then(|c| c.tree())
+            .map_err(to_io)?;
+        let entry = match tree.get_name(FILE_NAME_LOCAL) {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        let blob = repo.find_blob(entry.id()).map_err(to_io)?;
+        let content = std::str::from_utf8(blob.content())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        let config = toml::from_str(content)?;
+
+        Ok(Some(config))
     }
 
     pub fn local() -> Result<Self, io::Error> {
@@ -117,8 +297,32 @@ impl Config {
         Self::read(Self::path(profile))
     }
 
+    /// Read the seeds of the `lnk` seeds file as a configuration.
+    pub fn lnk(profile: &Profile) -> Result<Self, io::Error> {
+        let path = profile.paths().seeds_file();
+        if !path.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} not found", path.display()),
+            ));
+        }
+        lnk_seeds(profile)
+            .and_then(|seeds| Self::from_seeds(&seeds))
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))
+    }
+
+    /// Create the profile configuration. Seeds found in the `lnk` seeds file
+    /// are imported, otherwise the default seeds are used.
     pub fn init(profile: &Profile) -> Result<Self, anyhow::Error> {
-        let config = Self::default();
+        let seeds = lnk_seeds(profile)?;
+        let config = if seeds.is_empty() {
+            Self::default()
+        } else {
+            Self {
+                migrated: true,
+                ..Self::from_seeds(&seeds)?
+            }
+        };
         let path = Self::path(profile);
 
         config.write(path)?;
@@ -126,6 +330,63 @@ impl Config {
         Ok(config)
     }
 
+    /// Import the seeds of the `lnk` seeds file into the profile configuration.
+    /// If the configuration exists, seeds it doesn't have yet are added to it. This
+    /// only happens once, so that seeds removed afterwards aren't brought back.
+    /// Returns the number of seeds imported, or `None` if there was nothing to migrate.
+    ///
+    /// Since this writes the profile configuration, it's only run before the
+    /// configuration is modified. Until then, the `lnk` seeds are used as a fallback.
+    pub fn migrate(profile: &Profile) -> Result<Option<usize>, anyhow::Error> {
+        let path = Self::path(profile);
+        let seeds = lnk_seeds(profile)?;
+
+        if seeds.is_empty() {
+            return Ok(None);
+        }
+        if !path.exists() {
+            let config = Self::init(profile)?;
+
+            return Ok(Some(config.seed.len()));
+        }
+        let mut config = Self::read(&path)?;
+        if config.migrated {
+            return Ok(None);
+        }
+        let imported = config.merge_seeds(&seeds)?;
+
+        config.migrated = true;
+        config.write(&path)?;
+
+        Ok(Some(imported))
+    }
+
+    /// Add the given seeds in the `lnk` format, unless a seed with the same peer id
+    /// is already configured. Returns the number of seeds added.
+    pub fn merge_seeds(&mut self, seeds: &[Seed<String>]) -> Result<usize, anyhow::Error> {
+        let mut added = 0;
+
+        for seed in seeds {
+            if self.seeds().any(|s| s.peer() == Some(seed.peer)) {
+                continue;
+            }
+            self.seed.push(SeedConfig::new(seed, seed.label.clone())?);
+            added += 1;
+        }
+        Ok(added)
+    }
+
+    /// Create a configuration from seeds in the `lnk` format, using seed labels
+    /// as names.
+    pub fn from_seeds(seeds: &[Seed<String>]) -> Result<Self, anyhow::Error> {
+        let mut config = Self::empty();
+
+        for seed in seeds {
+            config.add_seed(SeedConfig::new(seed, seed.label.clone())?);
+        }
+        Ok(config)
+    }
+
     pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<(), anyhow::Error> {
         let contents = toml::to_string_pretty(self)?;
         fs::write(path, contents)?;
@@ -145,4 +406,301 @@ impl Config {
     pub fn seeds(&self) -> impl Iterator<Item = &SeedConfig> {
         self.seed.iter()
     }
+
+    /// Read the configuration of the given scope. A missing file results in an
+    /// empty configuration.
+    pub fn scope(scope: Scope, profile: &Profile) -> Result<Self, io::Error> {
+        match Self::read(scope.path(profile)) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::empty()),
+            result => result,
+        }
+    }
+
+    /// Add a seed, replacing any seed with the same peer id.
+    pub fn add_seed(&mut self, seed: SeedConfig) {
+        let peer = seed.peer();
+
+        self.seed.retain(|s| peer.is_none() || s.peer() != peer);
+        self.seed.push(seed);
+    }
+
+    /// Remove the seeds matching the given name, peer id or host. Returns the
+    /// number of seeds removed.
+    pub fn remove_seed(&mut self, query: &str) -> usize {
+        let len = self.seed.len();
+        self.seed.retain(|s| !s.matches(query));
+
+        len - self.seed.len()
+    }
+}
+
+/// A seed configuration source, in order of precedence.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    /// The working copy configuration.
+    Local(PathBuf),
+    /// The configuration committed to a project, when syncing it outside of its
+    /// working copy.
+    Project(Urn),
+    /// The profile configuration.
+    Profile(PathBuf),
+    /// The `lnk` seeds file.
+    Lnk(PathBuf),
+    /// The built-in default seeds.
+    Default,
+}
+
+impl std::fmt::Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Local(path) => write!(f, "local ({})", path.display()),
+            Self::Project(urn) => write!(f, "project ({} of {})", FILE_NAME_LOCAL, urn),
+            Self::Profile(path) => write!(f, "profile ({})", path.display()),
+            Self::Lnk(path) => write!(f, "lnk ({})", path.display()),
+            Self::Default => write!(f, "defaults"),
+        }
+    }
+}
+
+/// Status of a source during resolution.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Status {
+    /// The source was used.
+    Used,
+    /// The source has seeds, but a source with higher precedence was used.
+    Shadowed,
+    /// The source exists but has no seeds.
+    Empty,
+    /// The source doesn't exist.
+    Missing,
+    /// The source couldn't be read.
+    Invalid(String),
+}
+
+impl std::fmt::Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Used => write!(f, "used"),
+            Self::Shadowed => write!(f, "shadowed"),
+            Self::Empty => write!(f, "no seeds"),
+            Self::Missing => write!(f, "not found"),
+            Self::Invalid(err) => write!(f, "invalid: {}", err),
+        }
+    }
+}
+
+/// The resolved seed configuration. Seeds are taken from the first source that
+/// has any, in order: working copy or project, profile, defaults. Until they are
+/// imported into the profile, the seeds of the `lnk` seeds file come before the
+/// defaults.
+#[derive(Debug)]
+pub struct Resolution {
+    /// The configuration used.
+    pub config: Config,
+    /// Where the configuration was taken from.
+    pub source: Source,
+    /// All sources considered, with their status.
+    pub sources: Vec<(Source, Status)>,
+}
+
+impl Resolution {
+    /// Resolve from the given sources, in order of precedence. The defaults
+    /// are used if none of the sources have seeds.
+    pub fn new(candidates: Vec<(Source, Result<Config, io::Error>)>) -> Self {
+        let mut resolved: Option<(Source, Config)> = None;
+        let mut sources = Vec::new();
+
+        for (source, result) in candidates {
+            let status = match result {
+                Ok(config) if config.seed.is_empty() => Status::Empty,
+                Ok(_) if resolved.is_some() => Status::Shadowed,
+                Ok(config) => {
+                    resolved = Some((source.clone(), config));
+                    Status::Used
+                }
+                Err(err) if err.kind() == io::ErrorKind::NotFound => Status::Missing,
+                Err(err) => Status::Invalid(err.to_string()),
+            };
+            sources.push((source, status));
+        }
+
+        let (source, config) = match resolved {
+            Some(resolved) => {
+                sources.push((Source::Default, Status::Shadowed));
+                resolved
+            }
+            None => {
+                sources.push((Source::Default, Status::Used));
+                (Source::Default, Config::default())
+            }
+        };
+
+        Self {
+            config,
+            source,
+            sources,
+        }
+    }
+}
+
+/// Get the seeds of the profile's `lnk` seeds file. This is the seed format used
+/// by `lnk`, which predates the radicle configuration.
+fn lnk_seeds(profile: &Profile) -> Result<Vec<Seed<String>>, anyhow::Error> {
+    let path = profile.paths().seeds_file();
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let store = FileStore::<String>::new(path)?;
+    let seeds = store.iter()?.collect::<Result<_, _>>()?;
+
+    Ok(seeds)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_add_remove_seed() {
+        let mut config = Config::empty();
+        let seed = Seed {
+            peer: PeerId::from_str(DEFAULT_SEEDS[0].1).unwrap(),
+            addrs: String::from("pine.radicle.garden:8776"),
+            label: None,
+        };
+        let cfg = SeedConfig::new(&seed, Some(String::from("pine"))).unwrap();
+
+        assert_eq!(cfg.peer(), Some(seed.peer));
+        assert_eq!(cfg.api.port(), Some(DEFAULT_SEED_API_PORT));
+        assert_eq!(cfg.p2p.host_str(), Some("pine.radicle.garden"));
+        assert_eq!(
+            Seed::<String>::try_from(cfg.clone()).unwrap().peer,
+            seed.peer
+        );
+
+        config.add_seed(cfg.clone());
+        config.add_seed(cfg);
+        assert_eq!(config.seed.len(), 1);
+
+        assert_eq!(config.remove_seed("willow"), 0);
+        assert_eq!(config.remove_seed("pine"), 1);
+        assert!(config.seed.is_empty());
+    }
+
+    #[test]
+    fn test_resolution() {
+        let local = Source::Local(PathBuf::from(FILE_NAME_LOCAL));
+        let profile = Source::Profile(PathBuf::from(FILE_NAME_PROFILE));
+        let missing = || io::Error::new(io::ErrorKind::NotFound, "not found");
+        let seed = Seed {
+            peer: PeerId::from_str(DEFAULT_SEEDS[1].1).unwrap(),
+            addrs: String::from("seed.example.com:8776"),
+            label: Some(String::from("example")),
+        };
+        let config = Config::from_seeds(&[seed.clone()]).unwrap();
+        assert_eq!(config.seed[0].name.as_deref(), Some("example"));
+
+        let resolution = Resolution::new(vec![
+            (local.clone(), Err(missing())),
+            (profile.clone(), Ok(config)),
+        ]);
+        assert_eq!(resolution.source, profile);
+        assert_eq!(resolution.config.seed.len(), 1);
+        assert_eq!(
+            resolution.sources,
+            vec![
+                (local.clone(), Status::Missing),
+                (profile.clone(), Status::Used),
+                (Source::Default, Status::Shadowed),
+            ]
+        );
+
+        let resolution = Resolution::new(vec![
+            (local.clone(), Ok(Config::empty())),
+            (profile.clone(), Err(missing())),
+        ]);
+        assert_eq!(resolution.source, Source::Default);
+        assert_eq!(resolution.config.seed.len(), DEFAULT_SEEDS.len());
+        assert_eq!(resolution.sources[0].1, Status::Empty);
+
+        // A profile without seeds falls back to the `lnk` seeds.
+        let lnk = Source::Lnk(PathBuf::from("seeds"));
+        let resolution = Resolution::new(vec![
+            (local, Err(missing())),
+            (profile, Ok(Config::empty())),
+            (
+                lnk.clone(),
+                Config::from_seeds(&[seed]).map_err(|_| missing()),
+            ),
+        ]);
+        assert_eq!(resolution.source, lnk);
+        assert_eq!(resolution.config.seed.len(), 1);
+    }
+
+    #[test]
+    fn test_committed() {
+        let path = std::env::temp_dir().join("rad-config-committed");
+        fs::remove_dir_all(&path).ok();
+
+        let repo = git2::Repository::init_bare(&path).unwrap();
+        let sig = git2::Signature::now("radicle", "radicle@localhost").unwrap();
+        let commit = |files: &[(&str, &str)]| {
+            let mut tree = repo.treebuilder(None).unwrap();
+            for (name, content) in files {
+                let blob = repo.blob(content.as_bytes()).unwrap();
+                tree.insert(name, blob, 0o100644).unwrap();
+            }
+            let tree = repo.find_tree(tree.write().unwrap()).unwrap();
+
+            repo.commit(None, &sig, &sig, "Initial commit", &tree, &[])
+                .unwrap()
+        };
+
+        let oid = commit(&[(
+            FILE_NAME_LOCAL,
+            r#"
+            [[seed]]
+            name = "example"
+            p2p = "rad://hyd7wpd8p5aqnm9htsfoatxkckmw6ingnsdudns9code5xq17h1rhw@seed.example.com:8776"
+            git = "https://seed.example.com"
+            api = "https://seed.example.com:8777"
+
+            [tracking]
+            delegates = true
+            "#,
+        )]);
+        let config = Config::committed(&repo, oid).unwrap().unwrap();
+        assert_eq!(config.seed[0].name.as_deref(), Some("example"));
+        assert!(config.tracking.delegates);
+
+        let oid = commit(&[("README", "Hello")]);
+        assert!(Config::committed(&repo, oid).unwrap().is_none());
+
+        fs::remove_dir_all(&path).ok();
+    }
+
+    #[test]
+    fn test_merge_seeds() {
+        let pine = Seed {
+            peer: PeerId::from_str(DEFAULT_SEEDS[0].1).unwrap(),
+            addrs: String::from("pine.radicle.garden:8776"),
+            label: None,
+        };
+        let example = Seed {
+            peer: PeerId::from_str(DEFAULT_SEEDS[1].1).unwrap(),
+            addrs: String::from("seed.example.com:8776"),
+            label: Some(String::from("example")),
+        };
+        let mut config = Config::from_seeds(&[pine.clone()]).unwrap();
+
+        assert_eq!(config.merge_seeds(&[pine, example]).unwrap(), 1);
+        assert_eq!(config.seed.len(), 2);
+        assert_eq!(config.seed[1].name.as_deref(), Some("example"));
+
+        // The marker is only written when set.
+        assert!(!toml::to_string(&config).unwrap().contains("migrated"));
+        config.migrated = true;
+        let config: Config = toml::from_str(&toml::to_string(&config).unwrap()).unwrap();
+        assert!(config.migrated);
+    }
 }
@@ -1,11 +1,13 @@
 //! Git-related functions and types.
 use std::collections::HashSet;
-use std::fs::{File, OpenOptions};
+use std::env;
+use std::fs::{self, File, OpenOptions};
 use std::io;
 use std::io::Write;
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{self, Command};
 use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use anyhow::anyhow;
 use anyhow::Context as _;
@@ -252,6 +254,30 @@ pub fn remotes(repo: &git2::Repository) -> anyhow::Result<Vec<(String, PeerId)>>
     Ok(remotes)
 }
 
+/// Return the remote-tracking refs of the given repository that don't belong to
+/// any configured remote, eg. because the remote was removed without them.
+pub fn orphaned_remote_refs(repo: &git2::Repository) -> Result<Vec<String>, git2::Error> {
+    let prefixes = repo
+        .remotes()?
+        .iter()
+        .flatten()
+        .map(|name| format!("refs/remotes/{}/", name))
+        .collect::<Vec<_>>();
+    let mut orphans = Vec::new();
+
+    for r in repo.references_glob("refs/remotes/*")? {
+        let r = r?;
+        if let Some(name) = r.name() {
+            if !prefixes.iter().any(|p| name.starts_with(p)) {
+                orphans.push(name.to_owned());
+            }
+        }
+    }
+    orphans.sort();
+
+    Ok(orphans)
+}
+
 /// Get the repository's "rad" remote.
 pub fn rad_remote(repo: &Repository) -> anyhow::Result<Remote<LocalUrl>> {
     match Remote::<LocalUrl>::find(repo, reflike!("rad")) {
@@ -339,6 +365,37 @@ pub fn check_version() -> Result<Version, anyhow::Error> {
     Ok(git_version)
 }
 
+/// A scratch repository that can read the objects of another repository, but
+/// doesn't write to it, eg. to verify objects before they are stored. Removed
+/// when dropped.
+pub(crate) struct Scratch {
+    pub repo: git2::Repository,
+    pub path: PathBuf,
+}
+
+impl Scratch {
+    /// Create a scratch repository for `parent`, in a temporary directory whose name
+    /// starts with `prefix`.
+    pub fn new(parent: &git2::Repository, prefix: &str) -> Result<Self, git2::Error> {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos());
+        let path = env::temp_dir().join(format!("{}-{}-{}", prefix, process::id(), nanos));
+        let repo = git2::Repository::init_bare(&path)?;
+        let objects = parent.path().join("objects");
+
+        repo.odb()?.add_disk_alternate(&objects.to_string_lossy())?;
+
+        Ok(Self { repo, path })
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        fs::remove_dir_all(&self.path).ok();
+    }
+}
+
 /// Parse a remote refspec into a peer id and ref.
 pub fn parse_remote(refspec: &str) -> Option<(PeerId, &str)> {
     refspec
@@ -487,4 +544,33 @@ mod test {
 
         assert!(Version::from_str("2.34").is_err());
     }
+
+    #[test]
+    fn test_orphaned_remote_refs() {
+        let tmp = std::env::temp_dir().join("rad-git-orphans");
+        std::fs::remove_dir_all(&tmp).ok();
+        let repo = git2::Repository::init_bare(&tmp).unwrap();
+
+        let sig = git2::Signature::now("anonymous", "anonymous@radicle.xyz").unwrap();
+        let tree = repo.treebuilder(None).unwrap().write().unwrap();
+        let tree = repo.find_tree(tree).unwrap();
+        let oid = repo.commit(None, &sig, &sig, "init", &tree, &[]).unwrap();
+
+        repo.remote("alice", "https://example.com/alice.git")
+            .unwrap();
+        repo.reference("refs/remotes/alice/master", oid, false, "")
+            .unwrap();
+        repo.reference("refs/remotes/alice-old/master", oid, false, "")
+            .unwrap();
+        repo.reference("refs/remotes/bob/master", oid, false, "")
+            .unwrap();
+
+        assert_eq!(
+            orphaned_remote_refs(&repo).unwrap(),
+            vec![
+                String::from("refs/remotes/alice-old/master"),
+                String::from("refs/remotes/bob/master")
+            ]
+        );
+    }
 }
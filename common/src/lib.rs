@@ -1,6 +1,7 @@
 //! Common radicle utilities.
 #![allow(clippy::or_fun_call)]
 pub mod args;
+pub mod ci;
 pub mod cobs;
 pub mod config;
 pub mod git;
@@ -9,12 +10,14 @@ pub mod keys;
 pub mod logger;
 pub mod patch;
 pub mod person;
+pub mod petname;
 pub mod profile;
 pub mod project;
 pub mod seed;
 pub mod signer;
 pub mod sync;
 pub mod test;
+pub mod tracking;
 
 #[cfg(feature = "ethereum")]
 pub mod ethereum;
@@ -36,7 +39,8 @@ pub use url::Url;
 pub mod fmt {
     use librad::{collaborative_objects::ObjectId, PeerId};
 
-    /// Format a peer id to be more compact.
+    /// Format a peer id to be more compact. To show peers by their petname, see
+    /// [`super::petname::Petnames::peer`].
     pub fn peer(peer: &PeerId) -> String {
         let peer = peer.default_encoding();
         let start = peer.chars().take(7).collect::<String>();
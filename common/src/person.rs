@@ -4,12 +4,15 @@ use anyhow::{Context, Error, Result};
 use librad::{canonical::Cstring, git::identities::local::LocalIdentity};
 
 use librad::git::identities::Person;
-use librad::git::storage::Storage;
+use librad::git::storage::{ReadOnly, Storage};
+use librad::git::Urn;
 
 use librad::crypto::BoxedSigner;
+use librad::identities::git::Identities;
 use librad::identities::payload;
 use librad::identities::payload::HasNamespace;
 use librad::profile::Profile;
+use librad::{PeerId, PublicKey};
 
 use lnk_identities::{self, local, person};
 
@@ -76,16 +79,21 @@ pub fn local(storage: &Storage) -> Result<LocalIdentity, local::Error> {
     local::default(storage)
 }
 
+/// Get the payload extensions of a person.
+fn exts(person: &Person) -> Vec<payload::Ext<serde_json::Value>> {
+    person
+        .payload()
+        .exts()
+        .map(|(namespace, val)| (namespace.clone(), val.clone()))
+        .map(|(namespace, val)| payload::Ext { namespace, val })
+        .collect()
+}
+
 /// Set an ENS payload for the local identity.
 /// Returns the updated person.
 pub fn set_ens_payload(ens: Ens, storage: &Storage) -> Result<Person> {
     let id = local::default(storage)?;
-    let payload = id.payload();
-    let mut exts = payload
-        .exts()
-        .map(|(namespace, val)| (namespace.clone(), val.clone()))
-        .map(|(namespace, val)| payload::Ext { namespace, val })
-        .collect::<Vec<_>>();
+    let mut exts = exts(&id);
 
     let namespace = Ens::namespace().clone();
     let val = serde_json::to_value(ens)?;
@@ -104,3 +112,316 @@ pub fn set_ens_payload(ens: Ens, storage: &Storage) -> Result<Person> {
 
     Ok(new)
 }
+
+/// Get the devices of a person, ie. the peers of its delegation keys.
+pub fn devices(person: &Person) -> Vec<PeerId> {
+    person
+        .delegations()
+        .iter()
+        .map(|key| PeerId::from(*key))
+        .collect()
+}
+
+/// Whether the given device's own identity is `person`, ie. the device published
+/// `person` as its identity, and it verifies with the device as one of its
+/// delegates. Unlike [`devices`], this can't be claimed by someone else.
+pub fn is_device<S: AsRef<ReadOnly>>(storage: &S, person: &Urn, device: &PeerId) -> bool {
+    let storage = storage.as_ref();
+    let name = if device == storage.peer_id() {
+        format!("refs/namespaces/{}/refs/rad/id", person.encode_id())
+    } else {
+        format!(
+            "refs/namespaces/{}/refs/remotes/{}/rad/id",
+            person.encode_id(),
+            device
+        )
+    };
+    let repo = match git2::Repository::open_bare(storage.path()) {
+        Ok(repo) => repo,
+        Err(_) => return false,
+    };
+    let head = match repo.refname_to_id(&name) {
+        Ok(head) => head,
+        Err(_) => return false,
+    };
+
+    match Identities::<Person>::from(&repo).verify(head) {
+        Ok(verified) => {
+            &verified.urn() == person
+                && verified
+                    .delegations()
+                    .iter()
+                    .any(|key| &PeerId::from(*key) == device)
+        }
+        Err(_) => false,
+    }
+}
+
+/// Get the devices of a person that published it as their own identity, see
+/// [`is_device`].
+pub fn verified_devices<S: AsRef<ReadOnly>>(storage: &S, person: &Person) -> Vec<PeerId> {
+    let urn = person.urn();
+
+    devices(person)
+        .into_iter()
+        .filter(|device| is_device(storage, &urn, device))
+        .collect()
+}
+
+/// Get the devices whose signature the given revision of a person is waiting on.
+///
+/// Like link, other peers only verify a revision once it is signed by a quorum of
+/// its own devices, and by a quorum of the devices of the revision it replaces. Until
+/// then, they keep using the previous revision. When both quorums are reached, no
+/// signatures are pending. Otherwise, the devices of either revision that haven't
+/// signed are, including devices that the revision removes.
+pub fn pending<S: AsRef<ReadOnly>>(storage: &S, person: &Person) -> Result<Vec<PeerId>> {
+    let own = person.delegations().iter().copied().collect::<Vec<_>>();
+    let previous = match parent(storage, person)? {
+        Some(parent) => parent.delegations().iter().copied().collect::<Vec<_>>(),
+        None => Vec::new(),
+    };
+    let signed = |keys: &[PublicKey]| {
+        keys.iter()
+            .filter(|key| person.signatures.contains_key(*key))
+            .count()
+    };
+    let quorum = |keys: &[PublicKey]| keys.is_empty() || signed(keys) > keys.len() / 2;
+
+    if quorum(&own) && quorum(&previous) {
+        return Ok(Vec::new());
+    }
+    let mut pending = Vec::new();
+    for key in own.iter().chain(previous.iter()) {
+        let peer = PeerId::from(*key);
+
+        if !person.signatures.contains_key(key) && !pending.contains(&peer) {
+            pending.push(peer);
+        }
+    }
+    Ok(pending)
+}
+
+/// Get the revision of a person that the given revision replaces, if any.
+fn parent<S: AsRef<ReadOnly>>(storage: &S, person: &Person) -> Result<Option<Person>> {
+    let replaces = match person.doc.replaces {
+        Some(replaces) => replaces,
+        None => return Ok(None),
+    };
+    let repo = git2::Repository::open_bare(storage.as_ref().path())?;
+    let identities = Identities::<Person>::from(&repo);
+    let mut commit = repo.find_commit(*person.content_id)?;
+
+    // Updates that only add signatures keep the revision, so the replaced revision
+    // isn't necessarily the parent commit.
+    while let Ok(parent) = commit.parent(0) {
+        let identity = identities.get(parent.id())?;
+
+        if identity.revision == replaces {
+            return Ok(Some(identity));
+        }
+        commit = parent;
+    }
+    Err(anyhow::anyhow!(
+        "revision {} replaced by {} not found",
+        replaces,
+        person.revision
+    ))
+}
+
+/// Set the devices of the local identity. The update is signed by this device,
+/// and has to be signed by the other devices before it takes effect, see [`pending`].
+/// Returns the updated person.
+pub fn set_devices(storage: &Storage, devices: &[PeerId]) -> Result<Person> {
+    if devices.is_empty() {
+        anyhow::bail!("a personal identity must have at least one device");
+    }
+    let id = local::default(storage)?;
+    let delegations = devices.iter().map(|peer| *peer.as_public_key());
+
+    let new = person::update(
+        storage,
+        &id.urn(),
+        Some(id.urn()),
+        None,
+        exts(&id),
+        Some(delegations),
+    )?;
+
+    Ok(new)
+}
+
+/// Add a device to the local identity. Returns the updated person.
+pub fn add_device(storage: &Storage, device: PeerId) -> Result<Person> {
+    let mut devices = devices(&local(storage)?);
+
+    if devices.contains(&device) {
+        anyhow::bail!("device {} is already part of your identity", device);
+    }
+    devices.push(device);
+
+    set_devices(storage, &devices)
+}
+
+/// Remove a device from the local identity. Since the removed device is one of the
+/// devices of the replaced revision, its signature may be needed for the update to
+/// take effect, see [`pending`]. Returns the updated person.
+pub fn remove_device(storage: &Storage, device: &PeerId) -> Result<Person> {
+    let mut devices = devices(&local(storage)?);
+
+    if device == storage.peer_id() {
+        anyhow::bail!("the current device can't be removed from your identity");
+    }
+    if !devices.contains(device) {
+        anyhow::bail!("device {} is not part of your identity", device);
+    }
+    devices.retain(|d| d != device);
+
+    set_devices(storage, &devices)
+}
+
+/// Adopt the personal identity of another device, signing its latest revision
+/// with this device's key, and use it as the local identity. If the revision
+/// removes this device, it is only signed, see [`pending`].
+pub fn accept_device(storage: &Storage, urn: &librad::git::Urn, peer: PeerId) -> Result<Person> {
+    let person = person::merge(storage, urn, peer)?;
+
+    if !devices(&person).contains(storage.peer_id()) {
+        if let Some(parent) = parent(storage, &person)? {
+            if devices(&parent).contains(storage.peer_id()) {
+                return Ok(person);
+            }
+        }
+        anyhow::bail!(
+            "this device is not part of identity {}, run `rad self device add {}` on device {}",
+            urn,
+            storage.peer_id(),
+            peer
+        );
+    }
+    set_local(storage, &person)?;
+
+    Ok(person)
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::test;
+
+    /// Fetch the identity of `peer` from the storage of one profile into another.
+    fn fetch_id(from: &Profile, to: &Profile, urn: &Urn, peer: &PeerId) {
+        let repo = git2::Repository::open_bare(to.paths().git_dir()).unwrap();
+        let spec = format!(
+            "+refs/namespaces/{0}/refs/rad/id:refs/namespaces/{0}/refs/remotes/{1}/rad/id",
+            urn.encode_id(),
+            peer
+        );
+        repo.remote_anonymous(&from.paths().git_dir().to_string_lossy())
+            .unwrap()
+            .fetch(&[spec.as_str()], None, None)
+            .unwrap();
+    }
+
+    /// Verify the identity of `peer`, as fetched with [`fetch_id`].
+    fn verify_remote(profile: &Profile, urn: &Urn, peer: &PeerId) -> Person {
+        let repo = git2::Repository::open_bare(profile.paths().git_dir()).unwrap();
+        let head = repo
+            .refname_to_id(&format!(
+                "refs/namespaces/{}/refs/remotes/{}/rad/id",
+                urn.encode_id(),
+                peer
+            ))
+            .unwrap();
+
+        Identities::<Person>::from(&repo)
+            .verify(head)
+            .unwrap()
+            .into_inner()
+    }
+
+    #[test]
+    fn test_devices() {
+        let (storage_a, profile_a, whoami, _) = test::setup::profile();
+        let (storage_b, profile_b, _, _) = test::setup::profile();
+        let a = *storage_a.peer_id();
+        let b = *storage_b.peer_id();
+        let urn = whoami.urn();
+        let unknown =
+            PeerId::from_str("hyd1to75dyfpizchxp43rdwhisp8nbr76g5pxa5f4y7jh4pa6jjzns").unwrap();
+
+        assert!(add_device(&storage_a, a).is_err());
+
+        // The new device has yet to sign the revision that adds it.
+        let person = add_device(&storage_a, b).unwrap();
+        let mut added = devices(&person);
+        added.sort_by_key(|p| p.default_encoding());
+        let mut expected = vec![a, b];
+        expected.sort_by_key(|p| p.default_encoding());
+
+        assert_eq!(added, expected);
+        assert_eq!(pending(&storage_a, &person).unwrap(), vec![b]);
+
+        // The new device adopts the identity once it has fetched it.
+        fetch_id(&profile_a, &profile_b, &urn, &a);
+
+        assert!(accept_device(&storage_b, &urn, unknown).is_err());
+        let person = accept_device(&storage_b, &urn, a).unwrap();
+
+        assert!(pending(&storage_b, &person).unwrap().is_empty());
+        assert_eq!(local(&storage_b).unwrap().urn(), urn);
+        assert!(is_device(&storage_b, &urn, &a));
+        assert!(is_device(&storage_b, &urn, &b));
+        assert!(!is_device(&storage_b, &urn, &unknown));
+
+        // The current device can't be removed, and only known devices can.
+        assert!(remove_device(&storage_a, &a).is_err());
+        assert!(remove_device(&storage_a, &unknown).is_err());
+    }
+
+    #[test]
+    fn test_remove_device() {
+        let (storage_a, profile_a, whoami, _) = test::setup::profile();
+        let (storage_b, profile_b, _, _) = test::setup::profile();
+        let a = *storage_a.peer_id();
+        let b = *storage_b.peer_id();
+        let urn = whoami.urn();
+
+        // Setup an identity with two devices, signed by both.
+        add_device(&storage_a, b).unwrap();
+        fetch_id(&profile_a, &profile_b, &urn, &a);
+        accept_device(&storage_b, &urn, a).unwrap();
+        fetch_id(&profile_b, &profile_a, &urn, &b);
+        let person = accept_device(&storage_a, &urn, b).unwrap();
+
+        assert_eq!(devices(&person).len(), 2);
+        assert!(pending(&storage_a, &person).unwrap().is_empty());
+
+        // With two devices, both have to sign the removal of one of them.
+        let removed = remove_device(&storage_a, &b).unwrap();
+
+        assert_eq!(devices(&removed), vec![a]);
+        assert_eq!(pending(&storage_a, &removed).unwrap(), vec![b]);
+
+        // Until then, the other storage keeps using the previous revision.
+        fetch_id(&profile_a, &profile_b, &urn, &a);
+        let verified = verify_remote(&profile_b, &urn, &a);
+
+        assert_eq!(verified.revision, person.revision);
+        assert!(devices(&verified).contains(&b));
+
+        // Once the removed device signs, the removal verifies.
+        let signed = accept_device(&storage_b, &urn, a).unwrap();
+
+        assert_eq!(signed.revision, removed.revision);
+        assert!(pending(&storage_b, &signed).unwrap().is_empty());
+
+        fetch_id(&profile_b, &profile_a, &urn, &b);
+        let verified = verify_remote(&profile_a, &urn, &b);
+
+        assert_eq!(verified.revision, removed.revision);
+        assert_eq!(devices(&verified), vec![a]);
+    }
+}
@@ -0,0 +1,179 @@
+//! Peer petnames.
+//!
+//! Petnames are names given to peers locally, eg. with `rad peer name` or
+//! `rad remote add`. They are kept per profile, next to the profile configuration,
+//! and take precedence over the names peers give themselves in their identity.
+//!
+//! Commands open the petnames of their profile once, and pass them to the functions
+//! that display peers, eg. [`Petnames::peer`].
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use librad::profile::Profile;
+use librad::PeerId;
+
+/// Name of the petnames file, in the profile directory.
+pub const PETNAMES_FILE: &str = "petnames.json";
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("i/o error: {0}")]
+    Io(#[from] io::Error),
+    #[error("petnames decoding error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("invalid petname '{0}'")]
+    InvalidName(String),
+}
+
+/// The petnames of a profile, by peer id. The default has no petnames, and isn't
+/// backed by a file.
+#[derive(Debug, Default)]
+pub struct Petnames {
+    path: PathBuf,
+    names: BTreeMap<String, String>,
+}
+
+impl Petnames {
+    /// Open the petnames of the given profile.
+    pub fn open(profile: &Profile) -> Result<Self, Error> {
+        Self::load(profile.paths().seeds_file().with_file_name(PETNAMES_FILE))
+    }
+
+    /// Load petnames from the given path. A missing file has no petnames.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref().to_path_buf();
+        let names = match fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(err) => return Err(err.into()),
+        };
+
+        Ok(Self { path, names })
+    }
+
+    /// Write the petnames to disk.
+    pub fn save(&self) -> Result<(), Error> {
+        fs::write(&self.path, serde_json::to_vec_pretty(&self.names)?)?;
+
+        Ok(())
+    }
+
+    /// Get the petname of a peer.
+    pub fn get(&self, peer: &PeerId) -> Option<&str> {
+        self.names.get(&peer.default_encoding()).map(|s| s.as_str())
+    }
+
+    /// Find the peer with the given petname.
+    pub fn find(&self, name: &str) -> Option<PeerId> {
+        self.iter().find(|(_, n)| *n == name).map(|(p, _)| p)
+    }
+
+    /// Set the petname of a peer. Returns the previous petname, if any.
+    /// Petnames must be unique, and can't be empty or contain whitespace.
+    pub fn set(&mut self, peer: PeerId, name: &str) -> Result<Option<String>, Error> {
+        if name.is_empty() || name.chars().any(char::is_whitespace) {
+            return Err(Error::InvalidName(name.to_owned()));
+        }
+        if matches!(self.find(name), Some(other) if other != peer) {
+            return Err(Error::InvalidName(name.to_owned()));
+        }
+        Ok(self.names.insert(peer.default_encoding(), name.to_owned()))
+    }
+
+    /// Remove the petname of a peer. Returns the removed petname, if any.
+    pub fn remove(&mut self, peer: &PeerId) -> Option<String> {
+        self.names.remove(&peer.default_encoding())
+    }
+
+    /// Iterate over peers and their petnames.
+    pub fn iter(&self) -> impl Iterator<Item = (PeerId, &str)> {
+        self.names
+            .iter()
+            .filter_map(|(p, n)| PeerId::from_str(p).ok().map(|p| (p, n.as_str())))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Format a peer: its petname, or else its compact peer id, see [`fmt::peer`].
+    ///
+    /// [`fmt::peer`]: crate::fmt::peer
+    pub fn peer(&self, peer: &PeerId) -> String {
+        self.get(peer)
+            .map(ToOwned::to_owned)
+            .unwrap_or_else(|| crate::fmt::peer(peer))
+    }
+
+    /// Get the petname of a peer, or else of one of the other devices of the same
+    /// person, so that naming one device of a person names them all. Since anyone can
+    /// list a device in their identity, only devices whose own identity is that
+    /// person's may be given, see [`crate::person::verified_devices`].
+    pub fn of_person(&self, peer: &PeerId, devices: &[PeerId]) -> Option<&str> {
+        self.get(peer)
+            .or_else(|| devices.iter().find_map(|device| self.get(device)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_petnames() {
+        let path = std::env::temp_dir().join("rad-petnames.json");
+        fs::remove_file(&path).ok();
+
+        let alice =
+            PeerId::from_str("hyb5to4rshftx4apgmu9s6wnsp4ddmp1mz6ijh4qqey7fb8wrpawxa").unwrap();
+        let bob =
+            PeerId::from_str("hyd7wpd8p5aqnm9htsfoatxkckmw6ingnsdudns9code5xq17h1rhw").unwrap();
+
+        let mut petnames = Petnames::load(&path).unwrap();
+        assert!(petnames.is_empty());
+
+        assert_eq!(petnames.set(alice, "alice").unwrap(), None);
+        assert!(petnames.set(bob, "alice").is_err());
+        assert!(petnames.set(bob, "bob smith").is_err());
+        assert!(petnames.set(bob, "").is_err());
+        assert_eq!(petnames.set(bob, "bob").unwrap(), None);
+        assert_eq!(
+            petnames.set(alice, "ally").unwrap().as_deref(),
+            Some("alice")
+        );
+        petnames.save().unwrap();
+
+        let mut petnames = Petnames::load(&path).unwrap();
+        assert_eq!(petnames.get(&alice), Some("ally"));
+        assert_eq!(petnames.find("bob"), Some(bob));
+        assert_eq!(petnames.remove(&bob).as_deref(), Some("bob"));
+        assert_eq!(petnames.iter().count(), 1);
+
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_of_person() {
+        let alice =
+            PeerId::from_str("hyb5to4rshftx4apgmu9s6wnsp4ddmp1mz6ijh4qqey7fb8wrpawxa").unwrap();
+        let bob =
+            PeerId::from_str("hyd7wpd8p5aqnm9htsfoatxkckmw6ingnsdudns9code5xq17h1rhw").unwrap();
+        let eve =
+            PeerId::from_str("hyd1to75dyfpizchxp43rdwhisp8nbr76g5pxa5f4y7jh4pa6jjzns").unwrap();
+
+        let mut petnames = Petnames::default();
+        petnames.set(alice, "alice").unwrap();
+        petnames.set(eve, "eve").unwrap();
+
+        assert_eq!(petnames.of_person(&eve, &[alice]), Some("eve"));
+        assert_eq!(petnames.of_person(&bob, &[eve, alice]), Some("eve"));
+        assert_eq!(petnames.of_person(&bob, &[alice]), Some("alice"));
+        assert_eq!(petnames.of_person(&bob, &[]), None);
+
+        assert_eq!(petnames.peer(&alice), "alice");
+        assert_eq!(petnames.peer(&bob), crate::fmt::peer(&bob));
+    }
+}
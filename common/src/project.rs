@@ -33,6 +33,7 @@ use lnk_identities::working_copy_dir::WorkingCopyDir;
 
 use crate as common;
 use crate::person::Ens;
+use crate::petname::Petnames;
 use crate::{git, person};
 
 /// URL scheme for radicle resources.
@@ -48,6 +49,9 @@ pub struct PeerIdentity {
     pub urn: Urn,
     pub name: String,
     pub ens: Option<Ens>,
+    /// Devices of the person, see [`person::verified_devices`].
+    #[serde(default)]
+    pub devices: Vec<PeerId>,
 }
 
 impl PeerIdentity {
@@ -67,6 +71,7 @@ impl PeerIdentity {
                 urn: person.urn(),
                 name: person.subject().name.to_string(),
                 ens,
+                devices: person::verified_devices(storage, &person),
             }));
         }
         Ok(None)
@@ -86,13 +91,24 @@ pub struct PeerInfo {
 }
 
 impl PeerInfo {
-    pub fn name(&self) -> String {
-        match &self.person {
-            Some(person) => person.name.clone(),
-            None => common::fmt::peer(&self.id),
+    /// Name of the peer: its petname, or that of another device of the same
+    /// person, if any. Otherwise, the name of its identity, or its compact peer id.
+    pub fn name(&self, petnames: &Petnames) -> String {
+        match (petnames.of_person(&self.id, self.devices()), &self.person) {
+            (Some(name), _) => name.to_owned(),
+            (None, Some(person)) => person.name.clone(),
+            (None, None) => common::fmt::peer(&self.id),
         }
     }
 
+    /// Devices of the person this peer belongs to, if known.
+    pub fn devices(&self) -> &[PeerId] {
+        self.person
+            .as_ref()
+            .map(|p| p.devices.as_slice())
+            .unwrap_or_default()
+    }
+
     pub fn get<S: AsRef<ReadOnly>>(peer_id: &PeerId, project: &Metadata, storage: &S) -> PeerInfo {
         let delegate = project.delegates.iter().any(|d| d.contains(peer_id));
         let reference = project.peer_self(peer_id, storage);
@@ -423,6 +439,29 @@ where
     Ok(reference.target())
 }
 
+/// Get the time of the most recent commit on a project remote's branches, in
+/// seconds since the epoch. Returns `None` if the remote has no branches.
+pub fn last_update<S>(storage: &S, urn: &Urn, peer: &PeerId) -> anyhow::Result<Option<i64>>
+where
+    S: AsRef<ReadOnly>,
+{
+    let repo = git::Repository::open_bare(storage.as_ref().path())?;
+    let glob = format!(
+        "refs/namespaces/{}/refs/remotes/{}/heads/*",
+        urn.encode_id(),
+        peer
+    );
+    let mut last = None;
+
+    for r in repo.references_glob(&glob)? {
+        if let Ok(commit) = r?.peel_to_commit() {
+            let time = commit.time().seconds();
+            last = Some(last.map_or(time, |t: i64| t.max(time)));
+        }
+    }
+    Ok(last)
+}
+
 /// Get project metadata.
 pub fn get<S>(storage: &S, urn: &Urn) -> anyhow::Result<Option<Metadata>>
 where
@@ -464,6 +503,26 @@ pub fn list_seed_heads(
     repo: &git::Repository,
     url: &Url,
     project: &Urn,
+) -> anyhow::Result<HashMap<PeerId, Vec<(String, git::Oid)>>> {
+    let remotes = list_seed_refs(repo, url, project)?
+        .into_iter()
+        .map(|(peer, refs)| {
+            let heads = refs
+                .into_iter()
+                .filter_map(|(r, oid)| r.strip_prefix("heads/").map(|b| (b.to_owned(), oid)))
+                .collect();
+            (peer, heads)
+        })
+        .collect();
+
+    Ok(remotes)
+}
+
+/// List project seed refs, eg. `heads/master` or `cobs/<type>/<id>`, by peer.
+pub fn list_seed_refs(
+    repo: &git::Repository,
+    url: &Url,
+    project: &Urn,
 ) -> anyhow::Result<HashMap<PeerId, Vec<(String, git::Oid)>>> {
     let url = url.join(&project.encode_id())?;
     let mut remote = repo.remote_anonymous(url.as_str())?;
@@ -473,10 +532,8 @@ pub fn list_seed_heads(
 
     for head in remote.list()? {
         if let Some((peer, r)) = git::parse_remote(head.name()) {
-            if let Some(branch) = r.strip_prefix("heads/") {
-                let value = (branch.to_owned(), head.oid());
-                remotes.entry(peer).or_insert_with(Vec::new).push(value);
-            }
+            let value = (r.to_owned(), head.oid());
+            remotes.entry(peer).or_insert_with(Vec::new).push(value);
         }
     }
     Ok(remotes)
@@ -616,3 +673,51 @@ where
     let s: String = serde::Deserialize::deserialize(deserializer)?;
     s.parse().map_err(serde::de::Error::custom)
 }
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::test;
+
+    #[test]
+    fn test_last_update() {
+        let (storage, profile, _, proj) = test::setup::profile();
+        let urn = proj.urn();
+        let repo = git::Repository::open_bare(profile.paths().git_dir()).unwrap();
+        let peer =
+            PeerId::from_str("hyd7wpd8p5aqnm9htsfoatxkckmw6ingnsdudns9code5xq17h1rhw").unwrap();
+        let tree = repo.treebuilder(None).unwrap().write().unwrap();
+        let tree = repo.find_tree(tree).unwrap();
+        let commit = |branch: &str, time: i64| {
+            let sig = git::Signature::new(
+                "anonymous",
+                "anonymous@radicle.xyz",
+                &git2::Time::new(time, 0),
+            )
+            .unwrap();
+            let name = format!(
+                "refs/namespaces/{}/refs/remotes/{}/heads/{}",
+                urn.encode_id(),
+                peer,
+                branch
+            );
+            repo.commit(Some(&name), &sig, &sig, branch, &tree, &[])
+                .unwrap();
+        };
+
+        // Peers without branches were never updated.
+        assert_eq!(last_update(&storage, &urn, &peer).unwrap(), None);
+
+        commit("master", 1_600_000_000);
+        commit("dev", 1_650_000_000);
+        commit("old", 1_500_000_000);
+
+        // The most recent commit of any branch counts.
+        assert_eq!(
+            last_update(&storage, &urn, &peer).unwrap(),
+            Some(1_650_000_000)
+        );
+    }
+}
@@ -1,4 +1,6 @@
 //! Seed-related functionality.
+pub mod api;
+
 use std::convert::TryFrom;
 use std::net;
 use std::path::Path;
@@ -10,8 +12,11 @@ use librad::git::Urn;
 use url::{Host, Url};
 
 use crate::args::Error;
+use crate::config::SeedConfig;
+use crate::git;
 use crate::sync::Seed;
-use crate::{git, project};
+
+pub use api::SeedApiClient;
 
 pub const CONFIG_SEED_KEY: &str = "rad.seed";
 pub const CONFIG_PEER_KEY: &str = "rad.peer";
@@ -20,12 +25,12 @@ pub const DEFAULT_SEED_API_PORT: u16 = 8777;
 pub const DEFAULT_SEED_P2P_PORT: u16 = 8776;
 pub const DEFAULT_SEED_GIT_PORT: u16 = 443;
 
-#[derive(serde::Deserialize)]
+#[derive(Debug, Clone, serde::Deserialize)]
 pub struct CommitHeader {
     pub summary: String,
 }
 
-#[derive(serde::Deserialize)]
+#[derive(Debug, Clone, serde::Deserialize)]
 pub struct Commit {
     pub header: CommitHeader,
 }
@@ -250,50 +255,29 @@ pub fn get_peer_seed(peer_id: &PeerId) -> Result<Url, anyhow::Error> {
     Ok(url)
 }
 
-/// Query a seed node for its [`PeerId`].
-pub fn get_seed_id(mut seed: Url) -> Result<PeerId, anyhow::Error> {
-    seed.set_port(Some(DEFAULT_SEED_API_PORT)).unwrap();
-    seed = seed.join("/v1/peer")?;
-
-    let agent = ureq::Agent::new();
-    let obj: serde_json::Value = agent.get(seed.as_str()).call()?.into_json()?;
-
-    let id = obj
-        .get("id")
-        .ok_or(anyhow!("missing 'id' in seed API response"))?
-        .as_str()
-        .ok_or(anyhow!("'id' is not a string"))?;
-    let id = PeerId::from_default_encoding(id)?;
-
-    Ok(id)
-}
-
-/// Query a seed node for a project commit.
-pub fn get_commit(
-    mut seed: Url,
-    project: &Urn,
-    commit: &git::Oid,
-) -> Result<Commit, anyhow::Error> {
-    seed.set_port(Some(DEFAULT_SEED_API_PORT)).unwrap();
-    seed = seed.join(&format!("/v1/projects/{}/commits/{}", project, commit))?;
-
-    let agent = ureq::Agent::new();
-    let val: serde_json::Value = agent.get(seed.as_str()).call()?.into_json()?;
-    let commit = serde_json::from_value(val)?;
-
-    Ok(commit)
+/// API URL of the seed at the given URL, eg. its git URL, assuming the default API port.
+/// Use [`SeedApiClient`] directly when the API URL is known, eg. from [`SeedConfig::api`].
+pub fn api_url(mut seed: Url) -> Url {
+    seed.set_port(Some(DEFAULT_SEED_API_PORT)).ok();
+    seed
 }
 
-/// Query a seed node for a project's remotes.
-pub fn get_remotes(mut seed: Url, project: &Urn) -> Result<Vec<project::PeerInfo>, anyhow::Error> {
-    seed.set_port(Some(DEFAULT_SEED_API_PORT)).unwrap();
-    seed = seed.join(&format!("/v1/projects/{}/remotes", project))?;
-
-    let agent = ureq::Agent::new();
-    let val: serde_json::Value = agent.get(seed.as_str()).call()?.into_json()?;
-    let response = serde_json::from_value(val)?;
-
-    Ok(response)
+/// Client for the API of the seed at the given URL, eg. its git URL. The API URL and
+/// timeout of the configured seed on the same host are used, if any. Otherwise, eg.
+/// for ad-hoc seed URLs, the default API port is assumed, see [`api_url`].
+pub fn api_client<'a>(
+    seed: &Url,
+    configs: impl IntoIterator<Item = &'a SeedConfig>,
+) -> SeedApiClient {
+    configs
+        .into_iter()
+        .find(|c| {
+            [&c.git, &c.api, &c.p2p]
+                .iter()
+                .any(|u| u.host() == seed.host())
+        })
+        .map(SeedApiClient::from_config)
+        .unwrap_or_else(|| SeedApiClient::new(api_url(seed.clone())))
 }
 
 #[cfg(test)]
@@ -341,4 +325,27 @@ mod test {
         assert_eq!(addr.urn, None);
         assert_eq!(addr.port(), DEFAULT_SEED_P2P_PORT);
     }
+
+    #[test]
+    fn test_api_client() {
+        let peer =
+            PeerId::from_str("hyb5to4rshftx4apgmu9s6wnsp4ddmp1mz6ijh4qqey7fb8wrpawxa").unwrap();
+        let seed = Seed::from_str(&format!("{}@willow.radicle.garden:8776", peer)).unwrap();
+        let mut config = SeedConfig::new(&seed, None).unwrap();
+        config.api = Url::parse("https://willow.radicle.garden/api").unwrap();
+
+        // Configured seeds use their API URL.
+        let url = Url::parse("https://willow.radicle.garden").unwrap();
+        assert_eq!(
+            api_client(&url, [&config]).url().as_str(),
+            "https://willow.radicle.garden/api/"
+        );
+
+        // Other seeds use the default API port.
+        let url = Url::parse("https://pine.radicle.garden").unwrap();
+        assert_eq!(
+            api_client(&url, [&config]).url().as_str(),
+            "https://pine.radicle.garden:8777/"
+        );
+    }
 }
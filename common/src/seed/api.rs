@@ -0,0 +1,258 @@
+//! Client for the seed HTTP API.
+use std::io;
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+use librad::git::Urn;
+use librad::PeerId;
+use url::Url;
+
+use super::Commit;
+use crate::config::SeedConfig;
+use crate::git;
+use crate::project::PeerInfo;
+
+/// Default time allowed for each request.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("invalid seed API URL: {0}")]
+    Url(#[from] url::ParseError),
+    #[error("{url} was not found")]
+    NotFound { url: Url },
+    #[error("request to {url} failed with status {status}")]
+    Status { url: Url, status: u16 },
+    #[error("request to {url} failed: {err}")]
+    Transport { url: Url, err: String },
+    #[error("invalid response from {url}: {err}")]
+    Decode { url: Url, err: io::Error },
+    #[error("invalid peer id returned by seed: '{0}'")]
+    PeerId(String),
+}
+
+/// Project listed by a seed.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Project {
+    pub urn: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub default_branch: Option<String>,
+    /// Head of the default branch, if known.
+    #[serde(default)]
+    pub head: Option<String>,
+}
+
+/// Collaborative object, eg. an issue or patch, served by a seed. Fields other
+/// than the id and title depend on the object type.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Cob {
+    pub id: String,
+    pub title: String,
+    #[serde(flatten)]
+    pub fields: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct PeerResponse {
+    id: String,
+}
+
+/// Client for the `/v1` endpoints of a seed's HTTP API.
+#[derive(Debug, Clone)]
+pub struct SeedApiClient {
+    url: Url,
+    agent: ureq::Agent,
+}
+
+impl SeedApiClient {
+    /// Create a client for the API at the given URL, eg. `https://seed.example.com:8777`.
+    pub fn new(url: Url) -> Self {
+        Self::with_timeout(url, DEFAULT_TIMEOUT)
+    }
+
+    /// Create a client with the given request timeout.
+    pub fn with_timeout(mut url: Url, timeout: Duration) -> Self {
+        let agent = ureq::AgentBuilder::new().timeout(timeout).build();
+
+        // Request paths are joined to the API URL, which would otherwise replace
+        // its last path segment, eg. a path prefix such as `/api`.
+        if !url.path().ends_with('/') {
+            url.set_path(&format!("{}/", url.path()));
+        }
+
+        Self { url, agent }
+    }
+
+    /// Create a client for a configured seed, using its API URL and timeout.
+    pub fn from_config(seed: &SeedConfig) -> Self {
+        let timeout = seed
+            .timeout
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_TIMEOUT);
+
+        Self::with_timeout(seed.api.clone(), timeout)
+    }
+
+    /// The API URL.
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// Get the seed's peer id.
+    pub fn peer_id(&self) -> Result<PeerId, Error> {
+        let response: PeerResponse = self.get("v1/peer")?;
+
+        PeerId::from_default_encoding(&response.id).map_err(|_| Error::PeerId(response.id))
+    }
+
+    /// List the projects hosted by the seed.
+    pub fn projects(&self) -> Result<Vec<Project>, Error> {
+        self.get("v1/projects")
+    }
+
+    /// Get a project commit.
+    pub fn commit(&self, project: &Urn, commit: &git::Oid) -> Result<Commit, Error> {
+        self.get(&format!("v1/projects/{}/commits/{}", project, commit))
+    }
+
+    /// List the remotes of a project.
+    pub fn remotes(&self, project: &Urn) -> Result<Vec<PeerInfo>, Error> {
+        self.get(&format!("v1/projects/{}/remotes", project))
+    }
+
+    /// List the issues of a project.
+    pub fn issues(&self, project: &Urn) -> Result<Vec<Cob>, Error> {
+        self.get(&format!("v1/projects/{}/issues", project))
+    }
+
+    /// Get a project issue.
+    pub fn issue(&self, project: &Urn, id: &str) -> Result<Cob, Error> {
+        self.get(&format!("v1/projects/{}/issues/{}", project, id))
+    }
+
+    /// List the patches of a project.
+    pub fn patches(&self, project: &Urn) -> Result<Vec<Cob>, Error> {
+        self.get(&format!("v1/projects/{}/patches", project))
+    }
+
+    /// Get a project patch.
+    pub fn patch(&self, project: &Urn, id: &str) -> Result<Cob, Error> {
+        self.get(&format!("v1/projects/{}/patches/{}", project, id))
+    }
+
+    /// Get the resource at the given path, relative to the API URL.
+    fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T, Error> {
+        let url = self.url.join(path)?;
+
+        match self.agent.get(url.as_str()).call() {
+            Ok(response) => response
+                .into_json()
+                .map_err(|err| Error::Decode { url, err }),
+            Err(ureq::Error::Status(404, _)) => Err(Error::NotFound { url }),
+            Err(ureq::Error::Status(status, _)) => Err(Error::Status { url, status }),
+            Err(ureq::Error::Transport(err)) => Err(Error::Transport {
+                url,
+                err: err.to_string(),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::mock;
+    use std::str::FromStr;
+
+    const PEER: &str = "hyb5to4rshftx4apgmu9s6wnsp4ddmp1mz6ijh4qqey7fb8wrpawxa";
+    const URN: &str = "rad:git:hnrkyghsrokxzxpy9pww69xr11dr9q7edbxfo";
+
+    #[test]
+    fn test_client() {
+        let urn = Urn::from_str(URN).unwrap();
+        let server = mock::Server::start(vec![
+            mock::Route::ok("/v1/peer", format!(r#"{{"id":"{}"}}"#, PEER)),
+            mock::Route::ok(
+                "/v1/projects",
+                format!(
+                    r#"[{{"urn":"{}","name":"nakamoto","defaultBranch":"master"}}]"#,
+                    URN
+                ),
+            ),
+            mock::Route::ok(
+                &format!("/v1/projects/{}/remotes", URN),
+                format!(r#"[{{"id":"{}","person":null,"delegate":true}}]"#, PEER),
+            ),
+            mock::Route::ok(
+                &format!("/v1/projects/{}/issues", URN),
+                r#"[{"id":"d4e1a2","title":"Crash on start","state":{"status":"open"}}]"#,
+            ),
+        ]);
+        let client = SeedApiClient::new(server.url());
+
+        assert_eq!(client.peer_id().unwrap().default_encoding(), PEER);
+
+        let projects = client.projects().unwrap();
+        assert_eq!(projects[0].name, "nakamoto");
+        assert_eq!(projects[0].default_branch.as_deref(), Some("master"));
+
+        let remotes = client.remotes(&urn).unwrap();
+        assert!(remotes[0].delegate);
+
+        let issues = client.issues(&urn).unwrap();
+        assert_eq!(issues[0].title, "Crash on start");
+        assert!(issues[0].fields.contains_key("state"));
+
+        assert!(matches!(client.patches(&urn), Err(Error::NotFound { .. })));
+    }
+
+    #[test]
+    fn test_client_errors() {
+        let server = mock::Server::start(vec![
+            mock::Route::ok("/v1/peer", r#"{"id":"not-a-peer"}"#),
+            mock::Route::ok("/v1/projects", "<html>"),
+            mock::Route {
+                delay: Duration::from_secs(2),
+                ..mock::Route::ok("/v1/projects/slow/remotes", "[]")
+            },
+            mock::Route {
+                status: 500,
+                ..mock::Route::ok(&format!("/v1/projects/{}/issues", URN), "")
+            },
+        ]);
+        let client = SeedApiClient::with_timeout(server.url(), Duration::from_millis(200));
+        let urn = Urn::from_str(URN).unwrap();
+
+        assert!(matches!(client.peer_id(), Err(Error::PeerId(_))));
+        assert!(matches!(client.projects(), Err(Error::Decode { .. })));
+        assert!(matches!(
+            client.issues(&urn),
+            Err(Error::Status { status: 500, .. })
+        ));
+        assert!(matches!(
+            client.get::<Vec<PeerInfo>>("v1/projects/slow/remotes"),
+            Err(Error::Transport { .. })
+        ));
+    }
+
+    #[test]
+    fn test_client_path_prefix() {
+        let server = mock::Server::start(vec![mock::Route::ok(
+            "/seed/api/v1/peer",
+            format!(r#"{{"id":"{}"}}"#, PEER),
+        )]);
+
+        for prefix in ["seed/api", "seed/api/"] {
+            let client = SeedApiClient::new(server.url().join(prefix).unwrap());
+
+            assert_eq!(client.url().path(), "/seed/api/");
+            assert_eq!(client.peer_id().unwrap().default_encoding(), PEER);
+        }
+    }
+}
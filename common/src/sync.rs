@@ -1,13 +1,23 @@
+pub mod http;
 mod push;
+pub mod queue;
+pub mod status;
+pub mod watch;
 
+use std::collections::BTreeMap;
 use std::convert::TryInto;
+use std::fmt;
+use std::io;
 use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time;
 
 use anyhow::anyhow;
+use futures::stream::{FuturesUnordered, StreamExt};
+use url::Url;
 
 use librad::crypto::BoxedSigner;
+use librad::git::storage::ReadOnly;
 use librad::git::Urn;
 use librad::net::protocol::SendOnly;
 use librad::net::{
@@ -18,9 +28,8 @@ use librad::net::{
     replication, Network,
 };
 use librad::profile::Profile;
-use librad::Signer;
+use librad::{PeerId, Signer};
 use link_async::Spawner;
-use lnk_clib::seed::store::FileStore;
 
 pub use lnk_clib::seed::{Seed, Seeds};
 pub use lnk_sync::Mode;
@@ -28,54 +37,365 @@ pub use lnk_sync::Mode;
 use crate::config;
 use crate::nonempty::NonEmpty;
 
+/// Default time allowed for each fetch or push.
+pub const DEFAULT_TIMEOUT: time::Duration = time::Duration::from_secs(9);
+/// Default number of seeds synced at the same time.
+pub const DEFAULT_PARALLELISM: usize = 4;
+
+/// Error fetching from a seed.
+#[derive(Debug, thiserror::Error)]
+pub enum FetchError {
+    #[error(transparent)]
+    Replicate(#[from] client::error::Replicate),
+    #[error("timed out")]
+    Timeout,
+}
+
 /// Sync result of a seed.
 #[derive(Debug)]
 pub struct SyncResult {
     pub seed: Seed<Vec<SocketAddr>>,
-    pub fetch: Option<Result<replication::Success, client::error::Replicate>>,
+    pub fetch: Option<Result<replication::Success, FetchError>>,
     pub push: Option<Result<push::Success, push::Error>>,
+    /// Fetch over HTTPS, with the number of refs updated. See [`http`].
+    pub https: Option<Result<usize, git2::Error>>,
+    /// Whether the seed was left out because a quorum of other seeds was reached.
+    pub cancelled: bool,
+}
+
+impl SyncResult {
+    /// Result of a seed that wasn't synced because a quorum was reached.
+    pub fn cancelled(seed: Seed<Vec<SocketAddr>>) -> Self {
+        Self {
+            seed,
+            fetch: None,
+            push: None,
+            https: None,
+            cancelled: true,
+        }
+    }
+
+    /// Whether the seed acknowledged a push, by updating at least one ref.
+    pub fn is_ack(&self) -> bool {
+        matches!(&self.push, Some(Ok(success)) if !success.refs.is_empty())
+    }
+
+    /// Summarize the outcome of the sync.
+    pub fn status(&self) -> Status {
+        if self.cancelled {
+            return Status::Cancelled;
+        }
+        match &self.push {
+            Some(Err(push::Error::Timeout)) => return Status::Timeout,
+            Some(Err(err)) => return Status::from_error(err),
+            _ => {}
+        }
+        // A fetch over HTTPS is only attempted if the link fetch failed, or
+        // wasn't attempted.
+        match (&self.https, &self.fetch) {
+            (Some(Ok(updated)), _) if self.push.is_none() => {
+                return Status::Synced {
+                    updated: Some(*updated),
+                }
+            }
+            (Some(Ok(_)), _) => {}
+            (Some(Err(err)), _) => return Status::from_error(err),
+            (None, Some(Err(FetchError::Timeout))) => return Status::Timeout,
+            (None, Some(Err(err))) => return Status::from_error(err),
+            (None, _) => {}
+        }
+        match &self.push {
+            Some(Ok(success)) if success.refs.is_empty() => Status::NotTracking,
+            Some(Ok(success)) => Status::Synced {
+                updated: Some(success.refs.len()),
+            },
+            _ => Status::Synced { updated: None },
+        }
+    }
+}
+
+/// Outcome of syncing with a seed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Status {
+    /// The sync succeeded. When pushing, holds the number of refs updated by the seed.
+    Synced { updated: Option<usize> },
+    /// The push succeeded, but no refs were updated by the seed.
+    NotTracking,
+    /// The seed didn't respond in time.
+    Timeout,
+    /// The seed refused the connection.
+    Refused,
+    /// The seed wasn't synced, because a quorum of other seeds was reached.
+    Cancelled,
+    /// Any other error.
+    Failed(String),
+}
+
+impl Status {
+    pub fn is_success(&self) -> bool {
+        matches!(self, Self::Synced { .. } | Self::NotTracking)
+    }
+
+    /// Classify a sync error, by walking its chain of sources. Only typed errors are
+    /// classified; any other error, eg. a peer id mismatch, is reported as is.
+    fn from_error(err: &(dyn std::error::Error + 'static)) -> Self {
+        let mut source = Some(err);
+
+        while let Some(e) = source {
+            // Nb. Transparent errors forward their source, so the errors they wrap
+            // have to be matched on here.
+            match e.downcast_ref() {
+                Some(FetchError::Timeout) => return Self::Timeout,
+                Some(FetchError::Replicate(client::error::Replicate::NoConnection(_))) => {
+                    return Self::Refused
+                }
+                _ => {}
+            }
+            match e.downcast_ref() {
+                Some(push::Error::Timeout) => return Self::Timeout,
+                Some(push::Error::Client(client::error::RequestPull::NoConnection(_))) => {
+                    return Self::Refused
+                }
+                _ => {}
+            }
+            if let Some(e) = e.downcast_ref::<io::Error>() {
+                match e.kind() {
+                    io::ErrorKind::ConnectionRefused => return Self::Refused,
+                    io::ErrorKind::TimedOut => return Self::Timeout,
+                    _ => {}
+                }
+            }
+            source = e.source();
+        }
+        Self::Failed(err.to_string())
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Synced { updated: Some(n) } => write!(f, "ok, {} ref(s) updated", n),
+            Self::Synced { updated: None } => write!(f, "ok"),
+            Self::NotTracking => write!(f, "ok, no refs updated (seed may not be tracking you)"),
+            Self::Timeout => write!(f, "timed out"),
+            Self::Refused => write!(f, "connection refused"),
+            Self::Cancelled => write!(f, "skipped, quorum reached"),
+            Self::Failed(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+/// Sync options.
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Time allowed for each fetch or push.
+    pub timeout: time::Duration,
+    /// Time allowed for each fetch or push, for specific seeds.
+    pub timeouts: BTreeMap<PeerId, time::Duration>,
+    /// Maximum number of seeds synced at the same time.
+    pub parallelism: usize,
+    /// When only pushing, stop once this many seeds have acknowledged the push by
+    /// updating at least one ref. Seeds that weren't synced by then are cancelled.
+    pub quorum: Option<usize>,
+    /// Transport to use.
+    pub transport: http::Transport,
+    /// Peers whose refs aren't fetched over HTTPS, eg. because they're blocked.
+    /// Over the link protocol, only tracked peers are fetched.
+    pub blocked: Vec<PeerId>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            timeout: DEFAULT_TIMEOUT,
+            timeouts: BTreeMap::new(),
+            parallelism: DEFAULT_PARALLELISM,
+            quorum: None,
+            transport: http::Transport::default(),
+            blocked: Vec::new(),
+        }
+    }
+}
+
+impl Options {
+    /// Default options, with the seed timeouts of the profile's seed configuration.
+    pub fn from_profile(profile: &Profile) -> Self {
+        Self {
+            timeouts: seed_timeouts(profile),
+            ..Self::default()
+        }
+    }
+
+    /// Time allowed for each fetch or push with the given seed.
+    pub fn timeout_for(&self, peer: &PeerId) -> time::Duration {
+        self.timeouts.get(peer).copied().unwrap_or(self.timeout)
+    }
 }
 
 /// Sync the given URN with the provided list of seeds.
+///
+/// Seeds are synced concurrently, up to the configured parallelism. There is one
+/// result per seed, in the order of the given seeds. If a quorum is set and we're
+/// only pushing, seeds that weren't synced once it was reached are cancelled, see
+/// [`SyncResult::cancelled`]. Fetches are never cancelled.
+///
+/// Progress messages are passed to `progress` along with the seed they come from.
 pub async fn sync<S, E>(
     client: &Client<S, E>,
     urn: Urn,
     seeds: Seeds,
     mode: Mode,
-    timeout: time::Duration,
+    options: Options,
+    progress: impl Fn(&PeerId, &str),
 ) -> Vec<SyncResult>
 where
     S: Signer + Clone,
     E: ConnectPeer + Clone + Send + Sync + 'static,
 {
-    let mut syncs = Vec::with_capacity(seeds.len());
     let is_push = mode.is_push();
     let is_fetch = mode.is_fetch();
     let Seeds(seeds) = seeds;
+    let progress = &progress;
+    let quorum = if is_push && !is_fetch {
+        options.quorum
+    } else {
+        None
+    };
+
+    let results = schedule(
+        seeds.clone(),
+        options.parallelism,
+        quorum,
+        |seed| {
+            let urn = urn.clone();
+            let timeout = options.timeout_for(&seed.peer);
+
+            sync_seed(client, urn, seed, is_fetch, is_push, timeout, progress)
+        },
+        SyncResult::is_ack,
+    )
+    .await;
+
+    seeds
+        .into_iter()
+        .zip(results)
+        .map(|(seed, result)| result.unwrap_or_else(|| SyncResult::cancelled(seed)))
+        .collect()
+}
+
+/// Run `task` for every item, with at most `parallelism` tasks running at the same
+/// time. There is one result per item, in the order of the items. If a quorum is set,
+/// stops once that many results are acknowledged, dropping the tasks still running:
+/// the items that didn't complete have no result.
+async fn schedule<I, T, F, Fut>(
+    items: Vec<I>,
+    parallelism: usize,
+    quorum: Option<usize>,
+    task: F,
+    is_ack: impl Fn(&T) -> bool,
+) -> Vec<Option<T>>
+where
+    F: Fn(I) -> Fut,
+    Fut: std::future::Future<Output = T>,
+{
+    let parallelism = parallelism.max(1);
+    let mut results = Vec::with_capacity(items.len());
+    results.resize_with(items.len(), || None);
+    let mut pending = items.into_iter().enumerate();
+    let mut running = FuturesUnordered::new();
+    let mut acks = 0;
+    let task = &task;
 
-    for seed in seeds {
-        let fetch = if is_fetch {
-            match tokio::time::timeout(timeout, client.replicate(seed.clone(), urn.clone(), None))
-                .await
-            {
-                Ok(result) => Some(result),
-                Err(_) => Some(Err(client::error::Replicate::NoConnection(
-                    client::error::NoConnection(seed.peer),
-                ))),
+    loop {
+        while running.len() < parallelism {
+            if let Some((ix, item)) = pending.next() {
+                running.push(async move { (ix, task(item).await) });
+            } else {
+                break;
             }
-        } else {
-            None
-        };
+        }
 
-        let push = if is_push {
-            Some(push::push(client, urn.clone(), seed.clone(), timeout).await)
+        if let Some((ix, result)) = running.next().await {
+            if is_ack(&result) {
+                acks += 1;
+            }
+            results[ix] = Some(result);
+
+            if quorum.map_or(false, |q| acks >= q) {
+                break;
+            }
         } else {
-            None
-        };
+            break;
+        }
+    }
+    results
+}
+
+/// Sync the given URN with a single seed.
+async fn sync_seed<S, E>(
+    client: &Client<S, E>,
+    urn: Urn,
+    seed: Seed<Vec<SocketAddr>>,
+    is_fetch: bool,
+    is_push: bool,
+    timeout: time::Duration,
+    progress: &impl Fn(&PeerId, &str),
+) -> SyncResult
+where
+    S: Signer + Clone,
+    E: ConnectPeer + Clone + Send + Sync + 'static,
+{
+    let peer = seed.peer;
+    let fetch = if is_fetch {
+        progress(&peer, "fetching...");
+
+        match tokio::time::timeout(timeout, client.replicate(seed.clone(), urn.clone(), None)).await
+        {
+            Ok(result) => Some(result.map_err(FetchError::from)),
+            Err(_) => Some(Err(FetchError::Timeout)),
+        }
+    } else {
+        None
+    };
+
+    let push = if is_push {
+        progress(&peer, "pushing...");
 
-        syncs.push(SyncResult { seed, fetch, push })
+        Some(
+            push::push(client, urn, seed.clone(), timeout, |msg| {
+                progress(&peer, msg)
+            })
+            .await,
+        )
+    } else {
+        None
+    };
+
+    SyncResult {
+        seed,
+        fetch,
+        push,
+        https: None,
+        cancelled: false,
     }
-    syncs
+}
+
+/// Get the seed timeouts of the profile's seed configuration.
+pub fn seed_timeouts(profile: &Profile) -> BTreeMap<PeerId, time::Duration> {
+    config::Config::resolve(profile)
+        .config
+        .seeds()
+        .filter_map(|s| Some((s.peer()?, time::Duration::from_secs(s.timeout?))))
+        .collect()
+}
+
+/// Get the git URLs of the profile's seed configuration.
+pub fn seed_git_urls(profile: &Profile) -> BTreeMap<PeerId, Url> {
+    config::Config::resolve(profile)
+        .config
+        .seeds()
+        .filter_map(|s| Some((s.peer()?, s.git.clone())))
+        .collect()
 }
 
 /// Create a sync client.
@@ -98,28 +418,228 @@ pub async fn client(
     Ok(client)
 }
 
-/// Get the seeds configured for the profile.
-/// First checks local (working copy) config, then global.
+/// Get the seeds configured for the profile, see [`config::Resolution`].
 pub fn seeds(profile: &Profile) -> anyhow::Result<NonEmpty<Seed<String>>> {
-    if let Ok(config) = config::Config::load(profile) {
-        let seeds = config
-            .seeds()
-            .cloned()
-            .map(|s| s.try_into())
-            .collect::<Result<Vec<_>, _>>()?;
-        if let Ok(seeds) = seeds.try_into() {
-            return Ok(seeds);
-        }
-    }
+    resolved_seeds(config::Config::resolve(profile), profile)
+}
 
-    // Fallback to global seeds if no local seeds are configured.
-    // Nb. These seeds are in a different format, and eventually we will consolidate the
-    // seed configurations under a unified format. For now we support both.
-    let seeds_file = profile.paths().seeds_file();
-    let store = FileStore::<String>::new(seeds_file)?;
-    let seeds: Vec<_> = store.iter()?.collect::<Result<_, _>>()?;
+/// Get the seeds configured for a project, which may not be the one checked out in
+/// the current directory. See [`config::Config::resolve_project`].
+pub fn project_seeds<S: AsRef<ReadOnly>>(
+    profile: &Profile,
+    storage: &S,
+    urn: &Urn,
+) -> anyhow::Result<NonEmpty<Seed<String>>> {
+    resolved_seeds(
+        config::Config::resolve_project(profile, storage, urn),
+        profile,
+    )
+}
+
+fn resolved_seeds(
+    resolution: config::Resolution,
+    profile: &Profile,
+) -> anyhow::Result<NonEmpty<Seed<String>>> {
+    let seeds = resolution
+        .config
+        .seeds()
+        .cloned()
+        .map(|s| s.try_into())
+        .collect::<Result<Vec<_>, _>>()?;
 
     seeds
         .try_into()
         .map_err(|_| anyhow!("No seeds configured for profile {}", profile.id()))
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::cell::Cell;
+    use std::str::FromStr;
+
+    fn result(
+        fetch: Option<Result<replication::Success, FetchError>>,
+        push: Option<Result<push::Success, push::Error>>,
+        https: Option<Result<usize, git2::Error>>,
+    ) -> SyncResult {
+        SyncResult {
+            seed: Seed {
+                peer: PeerId::from_str("hyb5to4rshftx4apgmu9s6wnsp4ddmp1mz6ijh4qqey7fb8wrpawxa")
+                    .unwrap(),
+                addrs: vec![],
+                label: None,
+            },
+            fetch,
+            push,
+            https,
+            cancelled: false,
+        }
+    }
+
+    #[test]
+    fn test_status_from_error() {
+        let refused = io::Error::new(io::ErrorKind::ConnectionRefused, "refused");
+        let timeout = io::Error::new(io::ErrorKind::TimedOut, "timed out");
+        let mismatch = io::Error::new(io::ErrorKind::Other, "remote peer id mismatch");
+        let unknown = io::Error::new(io::ErrorKind::Other, "oops");
+
+        assert_eq!(Status::from_error(&refused), Status::Refused);
+        assert_eq!(Status::from_error(&timeout), Status::Timeout);
+        // Errors are never classified by their message.
+        assert_eq!(
+            Status::from_error(&mismatch),
+            Status::Failed("remote peer id mismatch".to_owned())
+        );
+        assert_eq!(
+            Status::from_error(&unknown),
+            Status::Failed("oops".to_owned())
+        );
+        assert!(Status::NotTracking.is_success());
+        assert!(!Status::Timeout.is_success());
+    }
+
+    #[test]
+    fn test_status() {
+        let peer =
+            PeerId::from_str("hyd7wpd8p5aqnm9htsfoatxkckmw6ingnsdudns9code5xq17h1rhw").unwrap();
+
+        // Synced.
+        assert_eq!(
+            result(None, None, None).status(),
+            Status::Synced { updated: None }
+        );
+        assert_eq!(
+            result(None, None, Some(Ok(3))).status(),
+            Status::Synced { updated: Some(3) }
+        );
+
+        // Refused.
+        let no_connection = client::error::Replicate::NoConnection(peer);
+        assert_eq!(
+            result(Some(Err(no_connection.into())), None, None).status(),
+            Status::Refused
+        );
+        let no_connection = client::error::RequestPull::NoConnection(peer);
+        assert_eq!(
+            result(None, Some(Err(no_connection.into())), None).status(),
+            Status::Refused
+        );
+
+        // Timeout.
+        assert_eq!(
+            result(Some(Err(FetchError::Timeout)), None, None).status(),
+            Status::Timeout
+        );
+        assert_eq!(
+            result(None, Some(Err(push::Error::Timeout)), None).status(),
+            Status::Timeout
+        );
+
+        // Cancelled.
+        let mut cancelled = result(None, None, None);
+        cancelled.cancelled = true;
+        assert_eq!(cancelled.status(), Status::Cancelled);
+        assert!(!cancelled.status().is_success());
+
+        // Failed.
+        assert_eq!(
+            result(None, Some(Err(push::Error::NoResponse)), None).status(),
+            Status::Failed("no response".to_owned())
+        );
+        assert_eq!(
+            result(None, None, Some(Err(git2::Error::from_str("oops")))).status(),
+            Status::Failed("oops".to_owned())
+        );
+    }
+
+    /// A fake sync, that takes `yields` polls to complete. Keeps track of how many
+    /// syncs are running at the same time.
+    async fn fake(id: usize, yields: usize, running: &Cell<usize>, max: &Cell<usize>) -> usize {
+        running.set(running.get() + 1);
+        max.set(max.get().max(running.get()));
+
+        for _ in 0..yields {
+            futures_lite::future::yield_now().await;
+        }
+        running.set(running.get() - 1);
+
+        id
+    }
+
+    #[test]
+    fn test_schedule_parallelism() {
+        let (running, max) = (Cell::new(0), Cell::new(0));
+        let results = futures::executor::block_on(schedule(
+            (0..10).collect(),
+            3,
+            None,
+            |i| fake(i, i % 4, &running, &max),
+            |_| true,
+        ));
+
+        assert_eq!(results, (0..10).map(Some).collect::<Vec<_>>());
+        assert!(max.get() <= 3);
+        assert!(max.get() > 1);
+        assert_eq!(running.get(), 0);
+
+        // A parallelism of zero still makes progress.
+        let results = futures::executor::block_on(schedule(
+            (0..3).collect(),
+            0,
+            None,
+            |i| fake(i, 1, &running, &max),
+            |_| true,
+        ));
+        assert_eq!(results, vec![Some(0), Some(1), Some(2)]);
+    }
+
+    #[test]
+    fn test_schedule_order() {
+        let (running, max) = (Cell::new(0), Cell::new(0));
+        // Later items complete first.
+        let results = futures::executor::block_on(schedule(
+            (0..5).collect(),
+            5,
+            None,
+            |i| fake(i, 10 - i, &running, &max),
+            |_| true,
+        ));
+
+        assert_eq!(results, (0..5).map(Some).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_schedule_quorum() {
+        let (running, max) = (Cell::new(0), Cell::new(0));
+        // Only even items are acknowledged. Items complete in order.
+        let results = futures::executor::block_on(schedule(
+            (0..10).collect(),
+            2,
+            Some(2),
+            |i| fake(i, i, &running, &max),
+            |i| i % 2 == 0,
+        ));
+
+        // We stop as soon as the second acknowledgement is in. The items that didn't
+        // complete are still accounted for.
+        let completed = results.iter().flatten().collect::<Vec<_>>();
+        assert_eq!(results.len(), 10);
+        assert_eq!(completed.iter().filter(|i| **i % 2 == 0).count(), 2);
+        assert!(completed.len() < 10);
+        assert!(results
+            .iter()
+            .enumerate()
+            .all(|(ix, r)| r.map_or(true, |i| i == ix)));
+
+        // Without enough acknowledgements, all items are synced.
+        let results = futures::executor::block_on(schedule(
+            (0..4).collect(),
+            2,
+            Some(3),
+            |i| fake(i, i, &running, &max),
+            |i| i % 2 == 0,
+        ));
+        assert_eq!(results, (0..4).map(Some).collect::<Vec<_>>());
+    }
+}
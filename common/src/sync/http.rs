@@ -0,0 +1,416 @@
+//! Fetching over a seed's git HTTPS endpoint, for when the link protocol is
+//! unreachable, eg. because UDP is blocked.
+//!
+//! The refs a seed holds for other peers are imported under the project's
+//! namespace in the local monorepo, the same way they would be replicated over
+//! the link protocol: only the refs of tracked peers and of the project's
+//! delegates are imported. Only fetching is supported: seeds don't accept pushes
+//! over HTTPS.
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+
+use either::Either;
+use librad::git::refs::Signed;
+use librad::git::Urn;
+use librad::identities::git::Identities;
+use librad::identities::{Person, Project};
+use librad::PeerId;
+use url::Url;
+
+use crate::git;
+
+/// Name of the blob holding a peer's signed refs, in the tree of its
+/// `rad/signed_refs` commit.
+const SIGNED_REFS_BLOB: &str = "refs";
+
+/// Transport used to sync with seeds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transport {
+    /// Use the link protocol, and fall back to HTTPS for fetches that fail.
+    Auto,
+    /// Only use the link protocol.
+    Link,
+    /// Fetch over HTTPS only.
+    Https,
+}
+
+impl Default for Transport {
+    fn default() -> Self {
+        Self::Auto
+    }
+}
+
+impl fmt::Display for Transport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Auto => write!(f, "auto"),
+            Self::Link => write!(f, "link"),
+            Self::Https => write!(f, "https"),
+        }
+    }
+}
+
+impl FromStr for Transport {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            "link" => Ok(Self::Link),
+            "https" => Ok(Self::Https),
+            _ => Err(format!("unknown transport '{}'", s)),
+        }
+    }
+}
+
+/// Whether the given remote ref should be fetched. Only the refs of the given
+/// peers are, along with the project's identity refs.
+fn is_wanted(r: &str, peers: &[PeerId]) -> bool {
+    match git::parse_remote(r) {
+        Some((peer, _)) => peers.contains(&peer),
+        None => is_identity(r),
+    }
+}
+
+/// Whether the given remote ref is one of the project's identity refs.
+fn is_identity(r: &str) -> bool {
+    r == "refs/rad/id" || r.starts_with("refs/rad/ids/")
+}
+
+/// Get the refspecs to fetch from the given remote refs. Only the refs of the given
+/// peers are fetched, see [`is_wanted`]. Identity refs are only ever fast-forwarded.
+pub fn refspecs(refs: &[String], project: &Urn, peers: &[PeerId]) -> Vec<String> {
+    let namespace = format!("refs/namespaces/{}/", project.encode_id());
+
+    refs.iter()
+        .filter(|r| is_wanted(r, peers))
+        .map(|r| {
+            let force = if is_identity(r) { "" } else { "+" };
+            format!("{}{}:{}{}", force, r, namespace, r)
+        })
+        .collect()
+}
+
+/// Fetch the refs of a project from a seed's git URL into the monorepo. Returns
+/// the number of refs that were updated.
+///
+/// As over the link protocol, only the refs of the `tracked` peers and of the
+/// project's delegates are fetched. Refs of the `skip` peers never are, eg. the
+/// local peer, whose refs can only be updated locally, or blocked peers.
+///
+/// Since the seed isn't trusted, refs are first fetched into a scratch repository.
+/// The project identities must verify, and only the refs that a peer signed in its
+/// `rad/signed_refs` are imported, the same way as over the link protocol.
+pub fn fetch(
+    repo: &git::Repository,
+    url: &Url,
+    project: &Urn,
+    tracked: &[PeerId],
+    skip: &[PeerId],
+) -> Result<usize, git2::Error> {
+    let url = url
+        .join(&project.encode_id())
+        .map_err(|e| git2::Error::from_str(&e.to_string()))?;
+    let scratch = git::Scratch::new(repo, "rad-sync-https")?;
+    let mut remote = scratch.repo.remote_anonymous(url.as_str())?;
+    let specs = |refs: &[String]| {
+        refs.iter()
+            .map(|r| format!("+{}:{}", r, r))
+            .collect::<Vec<_>>()
+    };
+
+    remote.connect(git::Direction::Fetch)?;
+    let advertised = remote
+        .list()?
+        .iter()
+        .map(|head| head.name().to_owned())
+        .collect::<Vec<_>>();
+    remote.disconnect()?;
+
+    // The identities are fetched first, since they determine the delegates.
+    let identities = advertised
+        .iter()
+        .filter(|r| is_identity(r))
+        .cloned()
+        .collect::<Vec<_>>();
+    if !identities.is_empty() {
+        remote.fetch(&specs(&identities), None, None)?;
+    }
+    let mut peers = tracked.to_vec();
+    for r in &identities {
+        peers.extend(verify_identity(&scratch.repo, r, project)?);
+    }
+    peers.retain(|p| !skip.contains(p));
+
+    let refs = advertised
+        .into_iter()
+        .filter(|r| is_wanted(r, &peers))
+        .collect::<Vec<_>>();
+    let remotes = refs
+        .iter()
+        .filter(|r| !is_identity(r))
+        .cloned()
+        .collect::<Vec<_>>();
+
+    if refs.is_empty() {
+        return Ok(0);
+    }
+    if !remotes.is_empty() {
+        remote.fetch(&specs(&remotes), None, None)?;
+    }
+    let mut signed = HashMap::new();
+    let refs = refs
+        .into_iter()
+        .filter(|r| match git::parse_remote(r) {
+            Some((peer, _)) => signed
+                .entry(peer)
+                .or_insert_with(|| signed_refs(&scratch.repo, &peer))
+                .as_ref()
+                .map_or(false, |signed| {
+                    scratch
+                        .repo
+                        .refname_to_id(r)
+                        .map_or(false, |oid| signed.contains(&(r.clone(), oid)))
+                }),
+            None => true,
+        })
+        .collect::<Vec<_>>();
+
+    for (peer, refs) in &signed {
+        if refs.is_none() {
+            log::warn!("Skipping refs of {}: invalid or missing signed refs", peer);
+        }
+    }
+
+    let specs = refspecs(&refs, project, &peers);
+    if specs.is_empty() {
+        return Ok(0);
+    }
+    // The project's top-level identity ref is only set if it doesn't exist yet,
+    // eg. when cloning.
+    let identity = format!("refs/namespaces/{}/refs/rad/id", project.encode_id());
+    let specs = if repo.find_reference(&identity).is_ok() {
+        specs
+            .into_iter()
+            .filter(|s| !s.ends_with(&identity))
+            .collect()
+    } else {
+        specs
+    };
+
+    let targets = || {
+        specs
+            .iter()
+            .filter_map(|s| s.split_once(':'))
+            .map(|(_, dst)| repo.refname_to_id(dst).ok())
+            .collect::<Vec<_>>()
+    };
+    let before = targets();
+    repo.remote_anonymous(&scratch.path.to_string_lossy())?
+        .fetch(&specs, None, None)?;
+    let after = targets();
+
+    Ok(before
+        .iter()
+        .zip(after.iter())
+        .filter(|(a, b)| a != b)
+        .count())
+}
+
+/// Verify an identity ref fetched into the scratch repository. The project identity,
+/// at `refs/rad/id`, must be the given project's. Its delegates are resolved from
+/// `refs/rad/ids/*`, and their peer ids are returned.
+fn verify_identity(
+    repo: &git::Repository,
+    r: &str,
+    project: &Urn,
+) -> Result<Vec<PeerId>, git2::Error> {
+    let invalid = |err: &dyn std::fmt::Display| {
+        git2::Error::from_str(&format!("identity at {} failed to verify: {}", r, err))
+    };
+    let head = repo.refname_to_id(r)?;
+    let (urn, delegates) = match Identities::<Person>::from(repo).verify(head) {
+        Ok(person) => (person.urn(), Vec::new()),
+        Err(err) if r != "refs/rad/id" => return Err(invalid(&err)),
+        Err(_) => {
+            let verified = Identities::<Project>::from(repo)
+                .verify(head, |urn: Urn| {
+                    repo.refname_to_id(&format!("refs/rad/ids/{}", urn.encode_id()))
+                })
+                .map_err(|err| invalid(&err))?;
+            let delegates = verified
+                .delegations()
+                .iter()
+                .flat_map(|d| match d {
+                    Either::Left(pk) => vec![PeerId::from(*pk)],
+                    Either::Right(indirect) => indirect
+                        .delegations()
+                        .iter()
+                        .map(|pk| PeerId::from(*pk))
+                        .collect(),
+                })
+                .collect();
+
+            (verified.urn(), delegates)
+        }
+    };
+    if r == "refs/rad/id" && &urn != project {
+        return Err(invalid(&format!("expected {}, found {}", project, urn)));
+    }
+    Ok(delegates)
+}
+
+/// Get the refs signed by a peer, as fetched into the scratch repository, along with
+/// its `rad/signed_refs` ref itself. Returns `None` if they are missing or their
+/// signature doesn't verify.
+fn signed_refs(repo: &git::Repository, peer: &PeerId) -> Option<HashSet<(String, git::Oid)>> {
+    let name = format!("refs/remotes/{}/rad/signed_refs", peer);
+    let commit = repo.find_reference(&name).ok()?.peel_to_commit().ok()?;
+    let blob = commit
+        .tree()
+        .ok()?
+        .get_name(SIGNED_REFS_BLOB)?
+        .to_object(repo)
+        .ok()?
+        .peel_to_blob()
+        .ok()?;
+    let signed = Signed::from_json(blob.content(), peer).ok()?;
+    let mut refs = signed
+        .iter_categorised()
+        .map(|((name, oid), category)| {
+            (
+                format!("refs/remotes/{}/{}/{}", peer, category, name),
+                git::Oid::from(*oid),
+            )
+        })
+        .collect::<HashSet<_>>();
+    refs.insert((name, commit.id()));
+
+    Some(refs)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test;
+    use librad::git::refs::Refs;
+
+    #[test]
+    fn test_refspecs() {
+        let urn = Urn::from_str("rad:git:hnrkyghsrokxzxpy9pww69xr11dr9q7edbxfo").unwrap();
+        let local =
+            PeerId::from_str("hyb5to4rshftx4apgmu9s6wnsp4ddmp1mz6ijh4qqey7fb8wrpawxa").unwrap();
+        let remote =
+            PeerId::from_str("hyd7wpd8p5aqnm9htsfoatxkckmw6ingnsdudns9code5xq17h1rhw").unwrap();
+        let refs = vec![
+            format!("refs/remotes/{}/heads/master", local),
+            format!("refs/remotes/{}/heads/master", remote),
+            format!("refs/remotes/{}/cobs/xyz.radicle.issue/1", remote),
+            String::from("refs/rad/id"),
+            String::from("refs/heads/master"),
+        ];
+        // Only the refs of the given peers are fetched.
+        let specs = refspecs(&refs, &urn, &[remote]);
+
+        assert_eq!(
+            specs,
+            vec![
+                format!(
+                    "+refs/remotes/{}/heads/master:refs/namespaces/{}/refs/remotes/{}/heads/master",
+                    remote,
+                    urn.encode_id(),
+                    remote
+                ),
+                format!(
+                    "+refs/remotes/{}/cobs/xyz.radicle.issue/1:refs/namespaces/{}/refs/remotes/{}/cobs/xyz.radicle.issue/1",
+                    remote,
+                    urn.encode_id(),
+                    remote
+                ),
+                format!(
+                    "refs/rad/id:refs/namespaces/{}/refs/rad/id",
+                    urn.encode_id()
+                ),
+            ]
+        );
+        assert_eq!("https".parse::<Transport>(), Ok(Transport::Https));
+        assert!("udp".parse::<Transport>().is_err());
+    }
+
+    #[test]
+    fn test_fetch() {
+        let (storage, profile, _, project) = test::setup::profile();
+        let urn = project.urn();
+        let peer = *storage.peer_id();
+        let ns = format!("refs/namespaces/{}/refs", urn.encode_id());
+        let monorepo = git::Repository::open_bare(profile.paths().git_dir()).unwrap();
+
+        // Publish a branch, and sign it.
+        let sig = git2::Signature::now("anonymous", "anonymous@radicle.xyz").unwrap();
+        let tree = monorepo.treebuilder(None).unwrap().write().unwrap();
+        let tree = monorepo.find_tree(tree).unwrap();
+        let head = monorepo
+            .commit(
+                Some(&format!("{}/heads/master", ns)),
+                &sig,
+                &sig,
+                "initial",
+                &tree,
+                &[],
+            )
+            .unwrap();
+        Refs::update(&storage, &urn).unwrap();
+
+        // The seed holds our refs as a remote, as it would over the link protocol.
+        let tmp = std::env::temp_dir().join("rad-sync-http");
+        std::fs::remove_dir_all(&tmp).ok();
+
+        let seed = git::Repository::init_bare(tmp.join("seed").join(urn.encode_id())).unwrap();
+        let url = Url::from_directory_path(tmp.join("seed")).unwrap();
+        let specs = [
+            format!("{0}/heads/*:refs/remotes/{1}/heads/*", ns, peer),
+            format!("{0}/rad/*:refs/remotes/{1}/rad/*", ns, peer),
+            format!("{}/rad/id:refs/rad/id", ns),
+            format!("{}/rad/ids/*:refs/rad/ids/*", ns),
+        ];
+        monorepo
+            .remote_anonymous(&seed.path().to_string_lossy())
+            .unwrap()
+            .push(&specs, None)
+            .unwrap();
+
+        // Refs the peer didn't sign are left out.
+        let forged = format!("refs/remotes/{}/heads/forged", peer);
+        seed.reference(&forged, head, false, "forge").unwrap();
+
+        // Peers that are skipped aren't fetched, even if they are delegates.
+        let local = git::Repository::init_bare(tmp.join("skipped")).unwrap();
+        fetch(&local, &url, &urn, &[], &[peer]).unwrap();
+        assert!(local.find_reference(&format!("{}/rad/id", ns)).is_ok());
+        assert!(local
+            .find_reference(&format!("{}/remotes/{}/heads/master", ns, peer))
+            .is_err());
+
+        // The delegates are fetched without being tracked.
+        let local = git::Repository::init_bare(tmp.join("local")).unwrap();
+        assert!(fetch(&local, &url, &urn, &[], &[]).unwrap() > 0);
+        assert_eq!(
+            local
+                .refname_to_id(&format!("{}/remotes/{}/heads/master", ns, peer))
+                .unwrap(),
+            head
+        );
+        assert!(local.find_reference(&format!("{}/rad/id", ns)).is_ok());
+        assert!(local
+            .find_reference(&format!("{}/remotes/{}/heads/forged", ns, peer))
+            .is_err());
+
+        // Nothing is fetched if the project identity doesn't verify.
+        let local = git::Repository::init_bare(tmp.join("other")).unwrap();
+        seed.reference("refs/rad/id", head, true, "forge").unwrap();
+
+        assert!(fetch(&local, &url, &urn, &[peer], &[]).is_err());
+        assert!(local.references().unwrap().next().is_none());
+    }
+}
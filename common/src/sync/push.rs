@@ -26,29 +26,53 @@ pub enum Error {
     Response(#[from] request_pull::Error),
     #[error("no response")]
     NoResponse,
+    #[error("timed out")]
+    Timeout,
 }
 
+/// Push to a seed, by requesting it to pull from us. The timeout applies to the
+/// whole request, including the progress messages sent by the seed.
 pub(super) async fn push<S, E>(
     client: &Client<S, E>,
     urn: Urn,
     seed: Seed<Vec<SocketAddr>>,
     timeout: time::Duration,
+    progress: impl Fn(&str),
 ) -> Result<request_pull::Success, Error>
 where
     S: Signer + Clone,
     E: ConnectPeer + Clone + Send + Sync + 'static,
 {
-    let mut req = client.request_pull(seed.clone(), urn.clone()).await?;
+    tokio::time::timeout(timeout, pull(client, urn, seed, progress))
+        .await
+        .map_err(|_| Error::Timeout)?
+}
 
-    while let Ok(Some(res)) = tokio::time::timeout(timeout, req.next()).await {
+async fn pull<S, E>(
+    client: &Client<S, E>,
+    urn: Urn,
+    seed: Seed<Vec<SocketAddr>>,
+    progress: impl Fn(&str),
+) -> Result<request_pull::Success, Error>
+where
+    S: Signer + Clone,
+    E: ConnectPeer + Clone + Send + Sync + 'static,
+{
+    let mut req = client.request_pull(seed, urn).await?;
+
+    loop {
+        let res = match req.next().await {
+            Some(res) => res,
+            None => return Err(Error::NoResponse),
+        };
         match res {
             Ok(res) => match res {
                 request_pull::Response::Success(succ) => return Ok(succ),
                 request_pull::Response::Error(err) => {
                     return Err(err.into());
                 }
-                request_pull::Response::Progress(_) => {
-                    continue;
+                request_pull::Response::Progress(msg) => {
+                    progress(&msg.to_string());
                 }
             },
             Err(err) => {
@@ -56,6 +80,4 @@ where
             }
         }
     }
-
-    Err(Error::NoResponse)
 }
@@ -0,0 +1,353 @@
+//! Queue of pushes that failed because seeds were unreachable.
+//!
+//! Failed pushes are retried with exponential backoff, either automatically on the
+//! next `rad` invocation, or explicitly with `rad sync --flush`. The queue is kept
+//! per profile, next to the profile configuration.
+//!
+//! Since several `rad` processes may update the queue at the same time, updates
+//! are made under a lock, see [`Queue::update`], and the queue file is replaced
+//! atomically.
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use librad::git::Urn;
+use librad::profile::Profile;
+use librad::PeerId;
+
+use crate::project;
+use crate::sync::Seed;
+
+/// Name of the queue file, in the profile directory.
+pub const QUEUE_FILE: &str = "sync-queue.json";
+/// Delay before the first retry. Doubles with every failed attempt.
+pub const BASE_DELAY: Duration = Duration::from_secs(60);
+/// Maximum delay between retries.
+pub const MAX_DELAY: Duration = Duration::from_secs(6 * 60 * 60);
+/// How long to wait for the queue lock.
+pub const LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+/// Age after which a lock is considered left behind by an interrupted process.
+/// Updates don't involve the network, so they never take this long.
+const STALE_LOCK: Duration = Duration::from_secs(60);
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("i/o error: {0}")]
+    Io(#[from] io::Error),
+    #[error("queue decoding error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("queue is locked by another process, see {0}")]
+    Locked(PathBuf),
+}
+
+/// A push to a seed that failed and is waiting to be retried.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Entry {
+    /// Project or identity that was pushed.
+    #[serde(deserialize_with = "project::deserialize_urn")]
+    pub urn: Urn,
+    /// Seed address, eg. `<peer-id>@<host>:<port>`.
+    pub seed: String,
+    /// When the push first failed, in seconds since the epoch.
+    pub queued: u64,
+    /// Number of failed attempts.
+    pub attempts: u32,
+    /// When the push was last attempted, in seconds since the epoch.
+    pub last_attempt: u64,
+}
+
+impl Entry {
+    /// Time at which the push should be retried, in seconds since the epoch.
+    pub fn next_attempt(&self) -> u64 {
+        let exp = self.attempts.saturating_sub(1).min(16);
+        let delay = BASE_DELAY.saturating_mul(1 << exp).min(MAX_DELAY).as_secs();
+
+        self.last_attempt.saturating_add(delay)
+    }
+
+    /// Whether the push should be retried at the given time.
+    pub fn is_due(&self, now: u64) -> bool {
+        now >= self.next_attempt()
+    }
+
+    /// Parse the seed address.
+    pub fn seed(&self) -> Option<Seed<String>> {
+        Seed::from_str(&self.seed).ok()
+    }
+
+    fn matches(&self, urn: &Urn, peer: &PeerId) -> bool {
+        &self.urn == urn && self.seed().map_or(false, |s| &s.peer == peer)
+    }
+}
+
+/// The push queue of a profile.
+#[derive(Debug)]
+pub struct Queue {
+    path: PathBuf,
+    entries: Vec<Entry>,
+}
+
+impl Queue {
+    /// Path of the queue of the given profile.
+    pub fn path(profile: &Profile) -> PathBuf {
+        profile.paths().seeds_file().with_file_name(QUEUE_FILE)
+    }
+
+    /// Open the queue of the given profile, for reading. To modify the queue, use
+    /// [`Queue::update`].
+    pub fn open(profile: &Profile) -> Result<Self, Error> {
+        Self::load(Self::path(profile))
+    }
+
+    /// Modify the queue of the given profile, and save it. The queue is locked
+    /// in the meantime, so that concurrent updates aren't lost.
+    pub fn update<T>(profile: &Profile, f: impl FnOnce(&mut Self) -> T) -> Result<T, Error> {
+        Self::update_at(Self::path(profile), LOCK_TIMEOUT, f)
+    }
+
+    /// Modify the queue at the given path, see [`Queue::update`].
+    pub fn update_at<P: AsRef<Path>, T>(
+        path: P,
+        timeout: Duration,
+        f: impl FnOnce(&mut Self) -> T,
+    ) -> Result<T, Error> {
+        let _lock = Lock::acquire(path.as_ref(), timeout)?;
+        let mut queue = Self::load(path)?;
+        let result = f(&mut queue);
+
+        queue.save()?;
+
+        Ok(result)
+    }
+
+    /// Load a queue from the given path. A missing file is an empty queue.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref().to_path_buf();
+        let entries = match fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err.into()),
+        };
+
+        Ok(Self { path, entries })
+    }
+
+    /// Write the queue to disk, by replacing the queue file.
+    fn save(&self) -> Result<(), Error> {
+        if self.entries.is_empty() {
+            match fs::remove_file(&self.path) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err.into()),
+                _ => return Ok(()),
+            }
+        }
+        let tmp = sibling(&self.path, "tmp");
+
+        fs::write(&tmp, serde_json::to_vec_pretty(&self.entries)?)?;
+        fs::rename(&tmp, &self.path)?;
+
+        Ok(())
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Entries that should be retried at the given time.
+    pub fn due(&self, now: u64) -> impl Iterator<Item = &Entry> {
+        self.entries.iter().filter(move |e| e.is_due(now))
+    }
+
+    /// Record a failed push.
+    pub fn failed(&mut self, urn: &Urn, seed: &Seed<String>, now: u64) {
+        if let Some(entry) = self.entries.iter_mut().find(|e| e.matches(urn, &seed.peer)) {
+            entry.attempts += 1;
+            entry.last_attempt = now;
+        } else {
+            self.entries.push(Entry {
+                urn: urn.clone(),
+                seed: seed.to_string(),
+                queued: now,
+                attempts: 1,
+                last_attempt: now,
+            });
+        }
+    }
+
+    /// Record a successful push, removing any queued entry for it. Returns whether
+    /// an entry was removed.
+    pub fn succeeded(&mut self, urn: &Urn, peer: &PeerId) -> bool {
+        let len = self.entries.len();
+        self.entries.retain(|e| !e.matches(urn, peer));
+
+        self.entries.len() != len
+    }
+
+    /// Remove the entries whose seed address can't be parsed, since they can never
+    /// be retried. Returns the entries removed.
+    pub fn remove_invalid(&mut self) -> Vec<Entry> {
+        let (valid, invalid) = self.entries.drain(..).partition(|e| e.seed().is_some());
+        self.entries = valid;
+
+        invalid
+    }
+}
+
+/// Exclusive lock on a queue file, released when dropped.
+struct Lock {
+    path: PathBuf,
+}
+
+impl Lock {
+    /// Acquire the lock of the given queue file, waiting for up to `timeout`.
+    fn acquire(queue: &Path, timeout: Duration) -> Result<Self, Error> {
+        let path = sibling(queue, "lock");
+        let start = std::time::Instant::now();
+
+        loop {
+            match fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+            {
+                Ok(_) => return Ok(Self { path }),
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+                    let stale = fs::metadata(&path)
+                        .and_then(|m| m.modified())
+                        .ok()
+                        .and_then(|t| t.elapsed().ok())
+                        .map_or(false, |age| age > STALE_LOCK);
+
+                    if stale {
+                        fs::remove_file(&path).ok();
+                    } else if start.elapsed() >= timeout {
+                        return Err(Error::Locked(path));
+                    } else {
+                        thread::sleep(Duration::from_millis(10));
+                    }
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        fs::remove_file(&self.path).ok();
+    }
+}
+
+/// A file next to the given one, with the given extension added to its name.
+fn sibling(path: &Path, ext: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(ext);
+
+    path.with_file_name(name)
+}
+
+/// Current time, in seconds since the epoch.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_queue() {
+        let path = std::env::temp_dir().join("rad-sync-queue.json");
+        fs::remove_file(&path).ok();
+
+        let urn = Urn::from_str("rad:git:hnrkyghsrokxzxpy9pww69xr11dr9q7edbxfo").unwrap();
+        let seed = Seed::from_str(
+            "hyb5to4rshftx4apgmu9s6wnsp4ddmp1mz6ijh4qqey7fb8wrpawxa@pine.radicle.garden:8776",
+        )
+        .unwrap();
+
+        let mut queue = Queue::load(&path).unwrap();
+        assert!(queue.is_empty());
+
+        queue.failed(&urn, &seed, 1000);
+        queue.failed(&urn, &seed, 1000);
+        assert_eq!(queue.entries().len(), 1);
+
+        let entry = &queue.entries()[0];
+        assert_eq!(entry.attempts, 2);
+        assert_eq!(entry.seed().unwrap().peer, seed.peer);
+        // Second attempt failed: the delay has doubled.
+        assert_eq!(entry.next_attempt(), 1000 + 2 * BASE_DELAY.as_secs());
+        assert_eq!(queue.due(1000).count(), 0);
+        assert_eq!(queue.due(1000 + 2 * BASE_DELAY.as_secs()).count(), 1);
+
+        queue.save().unwrap();
+        let mut queue = Queue::load(&path).unwrap();
+        assert_eq!(queue.entries().len(), 1);
+
+        assert!(queue.succeeded(&urn, &seed.peer));
+        assert!(queue.is_empty());
+        queue.save().unwrap();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_update() {
+        let path = std::env::temp_dir().join("rad-sync-queue-update.json");
+        fs::remove_file(&path).ok();
+        fs::remove_file(sibling(&path, "lock")).ok();
+
+        let urn = Urn::from_str("rad:git:hnrkyghsrokxzxpy9pww69xr11dr9q7edbxfo").unwrap();
+        let seed = Seed::from_str(
+            "hyb5to4rshftx4apgmu9s6wnsp4ddmp1mz6ijh4qqey7fb8wrpawxa@pine.radicle.garden:8776",
+        )
+        .unwrap();
+
+        Queue::update_at(&path, LOCK_TIMEOUT, |q| q.failed(&urn, &seed, 1000)).unwrap();
+        Queue::update_at(&path, LOCK_TIMEOUT, |q| q.failed(&urn, &seed, 2000)).unwrap();
+        assert_eq!(Queue::load(&path).unwrap().entries()[0].attempts, 2);
+        assert!(!sibling(&path, "tmp").exists());
+
+        // The queue can't be updated while it's locked.
+        let lock = Lock::acquire(&path, LOCK_TIMEOUT).unwrap();
+        assert!(matches!(
+            Queue::update_at(&path, Duration::from_millis(50), |_| {}),
+            Err(Error::Locked(_))
+        ));
+        drop(lock);
+
+        // Entries that can't be retried are removed.
+        let removed = Queue::update_at(&path, LOCK_TIMEOUT, |q| {
+            q.entries[0].seed = String::from("not a seed");
+            q.remove_invalid()
+        })
+        .unwrap();
+        assert_eq!(removed.len(), 1);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_backoff_limit() {
+        let entry = Entry {
+            urn: Urn::from_str("rad:git:hnrkyghsrokxzxpy9pww69xr11dr9q7edbxfo").unwrap(),
+            seed: String::new(),
+            queued: 0,
+            attempts: 100,
+            last_attempt: 0,
+        };
+        assert_eq!(entry.next_attempt(), MAX_DELAY.as_secs());
+    }
+}
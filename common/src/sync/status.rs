@@ -0,0 +1,160 @@
+//! Compare local refs with a seed's view of them, without syncing.
+use std::collections::HashMap;
+use std::fmt;
+
+use librad::git::Urn;
+use librad::PeerId;
+use url::Url;
+
+use crate::{git, project};
+
+/// Status of a local ref relative to the same ref on a seed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RefStatus {
+    /// The seed has the same commit.
+    UpToDate,
+    /// The seed is missing local commits.
+    Ahead { commits: usize },
+    /// The seed has commits that aren't in the local ref.
+    Behind { commits: usize },
+    /// Both sides have commits the other doesn't have.
+    Diverged { ahead: usize, behind: usize },
+    /// The seed's commit isn't available locally, so it can't be compared.
+    Unknown,
+    /// The seed doesn't have the ref.
+    Missing,
+}
+
+impl RefStatus {
+    /// Compare a local commit with the commit a seed has for the same ref.
+    pub fn compare(
+        repo: &git::Repository,
+        local: git::Oid,
+        seed: Option<git::Oid>,
+    ) -> Result<Self, git2::Error> {
+        let seed = match seed {
+            Some(seed) if seed == local => return Ok(Self::UpToDate),
+            Some(seed) => seed,
+            None => return Ok(Self::Missing),
+        };
+        if repo.find_commit(seed).is_err() {
+            return Ok(Self::Unknown);
+        }
+        let status = match repo.graph_ahead_behind(local, seed)? {
+            (ahead, 0) => Self::Ahead { commits: ahead },
+            (0, behind) => Self::Behind { commits: behind },
+            (ahead, behind) => Self::Diverged { ahead, behind },
+        };
+
+        Ok(status)
+    }
+
+    pub fn is_up_to_date(&self) -> bool {
+        *self == Self::UpToDate
+    }
+}
+
+impl fmt::Display for RefStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UpToDate => write!(f, "up-to-date"),
+            Self::Ahead { commits } => write!(f, "ahead by {} commit(s)", commits),
+            Self::Behind { commits } => write!(f, "behind by {} commit(s)", commits),
+            Self::Diverged { ahead, behind } => {
+                write!(f, "diverged ({} ahead, {} behind)", ahead, behind)
+            }
+            Self::Missing => write!(f, "not on seed"),
+            Self::Unknown => write!(f, "unknown, seed commit not fetched"),
+        }
+    }
+}
+
+/// Get the local branch and COB refs of a project, eg. `heads/master` or
+/// `cobs/<type>/<id>`.
+pub fn local_refs(
+    repo: &git::Repository,
+    project: &Urn,
+) -> Result<Vec<(String, git::Oid)>, git2::Error> {
+    let prefix = format!("refs/namespaces/{}/refs/", project.encode_id());
+    let mut refs = Vec::new();
+
+    for r in repo.references_glob(&format!("{}*", prefix))? {
+        let r = r?;
+        let (name, oid) = match (r.name(), r.target()) {
+            (Some(name), Some(oid)) => (name, oid),
+            _ => continue,
+        };
+        if let Some(name) = name.strip_prefix(&prefix) {
+            if name.starts_with("heads/") || name.starts_with("cobs/") {
+                refs.push((name.to_owned(), oid));
+            }
+        }
+    }
+    refs.sort();
+
+    Ok(refs)
+}
+
+/// Compare the local refs of a project with the refs published under our peer
+/// on the seed at the given git URL.
+pub fn status(
+    repo: &git::Repository,
+    url: &Url,
+    project: &Urn,
+    peer: &PeerId,
+) -> anyhow::Result<Vec<(String, RefStatus)>> {
+    let seed: HashMap<_, _> = project::list_seed_refs(repo, url, project)?
+        .remove(peer)
+        .unwrap_or_default()
+        .into_iter()
+        .collect();
+
+    local_refs(repo, project)?
+        .into_iter()
+        .map(|(name, oid)| {
+            let status = RefStatus::compare(repo, oid, seed.get(&name).copied())?;
+            Ok((name, status))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn commit(repo: &git::Repository, parents: &[&git::Commit], msg: &str) -> git::Oid {
+        let sig = git2::Signature::now("anonymous", "anonymous@radicle.xyz").unwrap();
+        let tree = repo.treebuilder(None).unwrap().write().unwrap();
+        let tree = repo.find_tree(tree).unwrap();
+
+        repo.commit(None, &sig, &sig, msg, &tree, parents).unwrap()
+    }
+
+    #[test]
+    fn test_compare() {
+        let tmp = std::env::temp_dir().join("rad-sync-status");
+        std::fs::remove_dir_all(&tmp).ok();
+        let repo = git::Repository::init_bare(&tmp).unwrap();
+
+        let base = commit(&repo, &[], "base");
+        let base_commit = repo.find_commit(base).unwrap();
+        let left = commit(&repo, &[&base_commit], "left");
+        let right = commit(&repo, &[&base_commit], "right");
+        let unknown = git::Oid::from_str("c0ffeec0ffeec0ffeec0ffeec0ffeec0ffeec0ff").unwrap();
+
+        let compare = |local, seed| RefStatus::compare(&repo, local, seed).unwrap();
+
+        assert_eq!(compare(base, Some(base)), RefStatus::UpToDate);
+        assert_eq!(compare(base, None), RefStatus::Missing);
+        assert_eq!(compare(left, Some(base)), RefStatus::Ahead { commits: 1 });
+        assert_eq!(compare(base, Some(left)), RefStatus::Behind { commits: 1 });
+        assert_eq!(compare(base, Some(unknown)), RefStatus::Unknown);
+        assert_eq!(
+            compare(left, Some(right)),
+            RefStatus::Diverged {
+                ahead: 1,
+                behind: 1
+            }
+        );
+    }
+}
@@ -0,0 +1,289 @@
+//! Watch the local refs of projects, to sync them when they change.
+//!
+//! Refs are polled: when the branch or COB refs of a project change, a push is
+//! scheduled once no further changes happened for the debounce period. Fetches
+//! are scheduled for all projects at a fixed interval.
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use librad::git::Urn;
+
+use crate::git;
+use crate::sync::status;
+
+/// How often refs are polled by default.
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// How long refs must be left unchanged before they are pushed, by default.
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_secs(5);
+/// How often projects are fetched by default.
+pub const DEFAULT_FETCH_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Watch options.
+#[derive(Debug, Clone, Copy)]
+pub struct Options {
+    /// How often refs are polled.
+    pub poll_interval: Duration,
+    /// How long refs must be left unchanged before they are pushed.
+    pub debounce: Duration,
+    /// How often projects are fetched.
+    pub fetch_interval: Duration,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            debounce: DEFAULT_DEBOUNCE,
+            fetch_interval: DEFAULT_FETCH_INTERVAL,
+        }
+    }
+}
+
+/// A sync to perform.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// The local refs of the project changed, and should be pushed.
+    Push(Urn),
+    /// The project should be fetched.
+    Fetch(Urn),
+}
+
+/// Local refs of a project, by name.
+type Refs = BTreeMap<String, git::Oid>;
+
+/// Watches the local refs of a set of projects.
+pub struct Watcher<'a> {
+    repo: &'a git::Repository,
+    options: Options,
+    refs: Vec<(Urn, Refs)>,
+    /// Projects with changes that weren't pushed yet, and when they last changed.
+    pending: HashMap<Urn, Instant>,
+    last_fetch: Instant,
+}
+
+impl<'a> Watcher<'a> {
+    /// Start watching the given projects. Their current refs are considered synced.
+    pub fn new(
+        repo: &'a git::Repository,
+        urns: impl IntoIterator<Item = Urn>,
+        options: Options,
+        now: Instant,
+    ) -> Result<Self, git2::Error> {
+        let mut refs = Vec::new();
+        for urn in urns {
+            let local = status::local_refs(repo, &urn)?.into_iter().collect();
+            refs.push((urn, local));
+        }
+
+        Ok(Self {
+            repo,
+            options,
+            refs,
+            pending: HashMap::new(),
+            last_fetch: now,
+        })
+    }
+
+    /// Check for changes, and return the syncs that are due at the given time.
+    pub fn poll(&mut self, now: Instant) -> Result<Vec<Event>, git2::Error> {
+        let mut events = Vec::new();
+
+        for (urn, refs) in self.refs.iter_mut() {
+            let current: Refs = status::local_refs(self.repo, urn)?.into_iter().collect();
+
+            if &current != refs {
+                *refs = current;
+                self.pending.insert(urn.clone(), now);
+            }
+        }
+
+        for (urn, _) in &self.refs {
+            if let Some(changed) = self.pending.get(urn) {
+                if now.saturating_duration_since(*changed) >= self.options.debounce {
+                    self.pending.remove(urn);
+                    events.push(Event::Push(urn.clone()));
+                }
+            }
+        }
+
+        if now.saturating_duration_since(self.last_fetch) >= self.options.fetch_interval {
+            self.last_fetch = now;
+            events.extend(self.refs.iter().map(|(urn, _)| Event::Fetch(urn.clone())));
+        }
+
+        Ok(events)
+    }
+
+    /// Poll for changes until `stop` is set, passing due syncs to `handler`.
+    ///
+    /// Errors reading refs, eg. while they are being written to, are logged and
+    /// polling is retried after the poll interval.
+    pub fn run(&mut self, stop: &AtomicBool, mut handler: impl FnMut(Event)) {
+        while !stop.load(Ordering::SeqCst) {
+            let events = match self.poll(Instant::now()) {
+                Ok(events) => events,
+                Err(err) => {
+                    log::warn!("Failed to read local refs: {}", err);
+                    Vec::new()
+                }
+            };
+            for event in events {
+                if stop.load(Ordering::SeqCst) {
+                    break;
+                }
+                handler(event);
+            }
+            thread::sleep(self.options.poll_interval);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::str::FromStr;
+
+    fn commit(repo: &git::Repository, name: &str, msg: &str) {
+        let sig = git2::Signature::now("anonymous", "anonymous@radicle.xyz").unwrap();
+        let tree = repo.treebuilder(None).unwrap().write().unwrap();
+        let tree = repo.find_tree(tree).unwrap();
+        let parent = repo
+            .find_reference(name)
+            .ok()
+            .and_then(|r| r.peel_to_commit().ok());
+        let parents = parent.iter().collect::<Vec<_>>();
+
+        repo.commit(Some(name), &sig, &sig, msg, &tree, &parents)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_watch() {
+        let tmp = std::env::temp_dir().join("rad-sync-watch");
+        std::fs::remove_dir_all(&tmp).ok();
+
+        let repo = git::Repository::init_bare(&tmp).unwrap();
+        let urn = Urn::from_str("rad:git:hnrkyghsrokxzxpy9pww69xr11dr9q7edbxfo").unwrap();
+        let head = format!("refs/namespaces/{}/refs/heads/master", urn.encode_id());
+        let options = Options {
+            poll_interval: Duration::from_millis(1),
+            debounce: Duration::from_secs(5),
+            fetch_interval: Duration::from_secs(60),
+        };
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        commit(&repo, &head, "initial");
+        let mut watcher = Watcher::new(&repo, vec![urn.clone()], options, start).unwrap();
+        assert!(watcher.poll(at(1)).unwrap().is_empty());
+
+        // Changes are only pushed once they settle.
+        commit(&repo, &head, "first");
+        assert!(watcher.poll(at(2)).unwrap().is_empty());
+        commit(&repo, &head, "second");
+        assert!(watcher.poll(at(4)).unwrap().is_empty());
+        assert!(watcher.poll(at(8)).unwrap().is_empty());
+        assert_eq!(watcher.poll(at(9)).unwrap(), vec![Event::Push(urn.clone())]);
+        assert!(watcher.poll(at(20)).unwrap().is_empty());
+
+        // Fetches happen periodically.
+        assert_eq!(
+            watcher.poll(at(60)).unwrap(),
+            vec![Event::Fetch(urn.clone())]
+        );
+        assert!(watcher.poll(at(61)).unwrap().is_empty());
+
+        // A stopped watcher doesn't call the handler.
+        let stop = AtomicBool::new(true);
+        let mut events = Vec::new();
+        commit(&repo, &head, "third");
+        watcher.run(&stop, |e| events.push(e));
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn test_watch_run() {
+        let tmp = std::env::temp_dir().join("rad-sync-watch-run");
+        std::fs::remove_dir_all(&tmp).ok();
+
+        // A bare repository stands in for the seed.
+        let repo = git::Repository::init_bare(tmp.join("local")).unwrap();
+        let seed = git::Repository::init_bare(tmp.join("seed")).unwrap();
+        let url = format!("file://{}", seed.path().display());
+        let urn = Urn::from_str("rad:git:hnrkyghsrokxzxpy9pww69xr11dr9q7edbxfo").unwrap();
+        let head = format!("refs/namespaces/{}/refs/heads/master", urn.encode_id());
+        let options = Options {
+            poll_interval: Duration::from_millis(1),
+            debounce: Duration::from_secs(0),
+            fetch_interval: Duration::from_secs(60),
+        };
+
+        commit(&repo, &head, "initial");
+        let mut watcher = Watcher::new(&repo, vec![urn.clone()], options, Instant::now()).unwrap();
+        commit(&repo, &head, "first");
+
+        // Changes are pushed to the seed by the handler, which stops the watcher.
+        let stop = AtomicBool::new(false);
+        let mut events = Vec::new();
+        watcher.run(&stop, |event| {
+            if let Event::Push(urn) = &event {
+                let refspec = format!(
+                    "refs/namespaces/{0}/refs/heads/*:refs/namespaces/{0}/refs/heads/*",
+                    urn.encode_id()
+                );
+                repo.remote_anonymous(&url)
+                    .unwrap()
+                    .push(&[refspec.as_str()], None)
+                    .unwrap();
+                stop.store(true, Ordering::SeqCst);
+            }
+            events.push(event);
+        });
+
+        assert_eq!(events, vec![Event::Push(urn)]);
+        assert_eq!(
+            seed.refname_to_id(&head).unwrap(),
+            repo.refname_to_id(&head).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_watch_run_error() {
+        let tmp = std::env::temp_dir().join("rad-sync-watch-error");
+        std::fs::remove_dir_all(&tmp).ok();
+
+        let repo = git::Repository::init_bare(&tmp).unwrap();
+        let urn = Urn::from_str("rad:git:hnrkyghsrokxzxpy9pww69xr11dr9q7edbxfo").unwrap();
+        let head = format!("refs/namespaces/{}/refs/heads/master", urn.encode_id());
+        let packed = tmp.join("packed-refs");
+        let options = Options {
+            poll_interval: Duration::from_millis(1),
+            debounce: Duration::from_secs(0),
+            fetch_interval: Duration::from_secs(0),
+        };
+
+        commit(&repo, &head, "initial");
+        let mut watcher = Watcher::new(&repo, vec![urn.clone()], options, Instant::now()).unwrap();
+
+        // Refs can't be read while the packed refs are corrupt.
+        std::fs::write(&packed, "garbage").unwrap();
+        assert!(watcher.poll(Instant::now()).is_err());
+
+        // The watcher keeps polling until refs can be read again.
+        let fixer = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            std::fs::remove_file(packed).unwrap();
+        });
+        let stop = AtomicBool::new(false);
+        let mut events = Vec::new();
+        watcher.run(&stop, |event| {
+            stop.store(true, Ordering::SeqCst);
+            events.push(event);
+        });
+        fixer.join().unwrap();
+
+        assert_eq!(events, vec![Event::Fetch(urn)]);
+    }
+}
@@ -94,3 +94,109 @@ where
         keystore.secret_key,
     )))
 }
+
+/// Load a COB document fixture from `fixtures/cobs`. Fixtures are object histories,
+/// ie. concatenated automerge changes, as they were written by older versions.
+pub fn fixture(name: &str) -> automerge::Automerge {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("fixtures")
+        .join("cobs")
+        .join(name);
+    let bytes = std::fs::read(&path).unwrap();
+
+    automerge::Automerge::load(&bytes).unwrap()
+}
+
+/// In-process HTTP server serving canned responses, eg. to stand in for a seed's API.
+pub mod mock {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+    use std::time::Duration;
+
+    use url::Url;
+
+    /// A canned response.
+    #[derive(Debug, Clone)]
+    pub struct Route {
+        /// Request path, eg. `/v1/peer`.
+        pub path: String,
+        /// Response status.
+        pub status: u16,
+        /// Response body.
+        pub body: String,
+        /// Delay before responding.
+        pub delay: Duration,
+    }
+
+    impl Route {
+        /// A successful JSON response.
+        pub fn ok(path: &str, body: impl Into<String>) -> Self {
+            Self {
+                path: path.to_owned(),
+                status: 200,
+                body: body.into(),
+                delay: Duration::ZERO,
+            }
+        }
+    }
+
+    /// A running server. Requests for unknown paths get a `404` response.
+    pub struct Server {
+        url: Url,
+    }
+
+    impl Server {
+        /// Start a server on a random local port.
+        pub fn start(routes: Vec<Route>) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let addr = listener.local_addr().unwrap();
+
+            thread::spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    let routes = routes.clone();
+                    thread::spawn(move || respond(stream, &routes));
+                }
+            });
+
+            Self {
+                url: Url::parse(&format!("http://{}", addr)).unwrap(),
+            }
+        }
+
+        pub fn url(&self) -> Url {
+            self.url.clone()
+        }
+    }
+
+    fn respond(mut stream: TcpStream, routes: &[Route]) {
+        let mut reader = BufReader::new(&stream);
+        let mut request = String::new();
+        if reader.read_line(&mut request).is_err() {
+            return;
+        }
+        // Skip headers.
+        let mut line = String::new();
+        while reader.read_line(&mut line).map_or(false, |n| n > 2) {
+            line.clear();
+        }
+
+        let path = request.split_whitespace().nth(1).unwrap_or_default();
+        let (status, body) = match routes.iter().find(|r| r.path == path) {
+            Some(route) => {
+                thread::sleep(route.delay);
+                (route.status, route.body.as_str())
+            }
+            None => (404, ""),
+        };
+
+        write!(
+            stream,
+            "HTTP/1.1 {} -\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        )
+        .ok();
+    }
+}
@@ -0,0 +1,371 @@
+//! Project tracking policies.
+//!
+//! A project's tracking policy is set in the `[tracking]` section of its
+//! `Radicle.toml`, eg.
+//!
+//! ```toml
+//! [tracking]
+//! delegates = true
+//! contributors = true
+//! block = ["hyb5to4rshftx4apgmu9s6wnsp4ddmp1mz6ijh4qqey7fb8wrpawxa"]
+//! ```
+//!
+//! Delegates and contributors, ie. peers who opened patches, are tracked
+//! automatically when syncing, while blocked peers are never tracked.
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use librad::git::storage::{ReadOnly, Storage};
+use librad::git::tracking;
+use librad::git::Urn;
+use librad::profile::Profile;
+use librad::PeerId;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::{cobs, project};
+
+/// Tracking policy of a project.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Policy {
+    /// Automatically track the project delegates.
+    #[serde(default)]
+    pub delegates: bool,
+    /// Automatically track peers who open patches.
+    #[serde(default)]
+    pub contributors: bool,
+    /// Peers that are never tracked.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub block: Vec<PeerId>,
+}
+
+/// Why a peer is tracked automatically.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reason {
+    /// The peer is a project delegate.
+    Delegate,
+    /// The peer opened a patch.
+    Contributor,
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Delegate => write!(f, "delegate"),
+            Self::Contributor => write!(f, "contributor"),
+        }
+    }
+}
+
+/// Outcome of applying a tracking policy.
+#[derive(Debug, Default)]
+pub struct Applied {
+    /// Peers that were tracked.
+    pub tracked: Vec<(PeerId, Reason)>,
+    /// Blocked peers that were untracked.
+    pub untracked: Vec<PeerId>,
+}
+
+impl Policy {
+    /// Get the policy of the working copy, ie. from `Radicle.toml`. If there is
+    /// no such file, the default policy is returned, which doesn't track anyone
+    /// automatically.
+    pub fn local() -> Result<Self, io::Error> {
+        match Config::local() {
+            Ok(config) => Ok(config.tracking),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Get the policy of a project, which may not be the one checked out in the
+    /// current directory. See [`Config::of_project`].
+    pub fn of_project<S: AsRef<ReadOnly>>(storage: &S, urn: &Urn) -> Result<Self, io::Error> {
+        match Config::of_project(storage, urn).1 {
+            Ok(config) => Ok(config.tracking),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Whether this is the default policy.
+    pub fn is_default(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// Whether the given peer is blocked.
+    pub fn is_blocked(&self, peer: &PeerId) -> bool {
+        self.block.contains(peer)
+    }
+
+    /// Block a peer. Returns `false` if the peer was already blocked.
+    pub fn block(&mut self, peer: PeerId) -> bool {
+        if self.is_blocked(&peer) {
+            return false;
+        }
+        self.block.push(peer);
+
+        true
+    }
+
+    /// Get the peers to track, out of the given delegates and contributors.
+    /// Blocked peers are left out, and peers that are both delegates and
+    /// contributors are returned once, as delegates.
+    pub fn candidates(
+        &self,
+        delegates: impl IntoIterator<Item = PeerId>,
+        contributors: impl IntoIterator<Item = PeerId>,
+    ) -> Vec<(PeerId, Reason)> {
+        let mut candidates: Vec<(PeerId, Reason)> = Vec::new();
+        let delegates = delegates
+            .into_iter()
+            .filter(|_| self.delegates)
+            .map(|p| (p, Reason::Delegate));
+        let contributors = contributors
+            .into_iter()
+            .filter(|_| self.contributors)
+            .map(|p| (p, Reason::Contributor));
+
+        for (peer, reason) in delegates.chain(contributors) {
+            if self.is_blocked(&peer) || candidates.iter().any(|(p, _)| p == &peer) {
+                continue;
+            }
+            candidates.push((peer, reason));
+        }
+        candidates
+    }
+}
+
+/// Get the peers tracked in the context of a project.
+pub fn tracked<S: AsRef<ReadOnly>>(storage: &S, urn: &Urn) -> anyhow::Result<Vec<PeerId>> {
+    let mut peers = Vec::new();
+
+    for tracked in tracking::tracked(storage.as_ref(), Some(urn))? {
+        if let Some(peer) = tracked?.peer_id() {
+            peers.push(peer);
+        }
+    }
+    Ok(peers)
+}
+
+/// Get the peer ids of a project's delegates.
+pub fn delegates(project: &project::Metadata) -> Vec<PeerId> {
+    project
+        .delegates
+        .iter()
+        .flat_map(|d| match d {
+            project::Delegate::Direct { id } => vec![*id],
+            project::Delegate::Indirect { ids, .. } => ids.iter().copied().collect(),
+        })
+        .collect()
+}
+
+/// Get the peer ids of the authors of a project's patches.
+pub fn contributors(
+    project: &project::Metadata,
+    storage: &Storage,
+    profile: &Profile,
+) -> anyhow::Result<Vec<PeerId>> {
+    let cobs = cobs::store(profile, storage)?;
+    let patches = cobs.patches().all(&project.urn)?;
+
+    Ok(patches.into_iter().map(|(_, p)| p.author.peer).collect())
+}
+
+/// Apply a tracking policy to a project: blocked peers are untracked, and the
+/// delegates and contributors the policy asks for are tracked.
+pub fn apply(
+    policy: &Policy,
+    project: &project::Metadata,
+    storage: &Storage,
+    profile: &Profile,
+) -> anyhow::Result<Applied> {
+    let mut applied = Applied::default();
+    let urn = &project.urn;
+
+    for peer in &policy.block {
+        let result = tracking::untrack(
+            storage,
+            urn,
+            *peer,
+            tracking::UntrackArgs {
+                policy: tracking::policy::Untrack::MustExist,
+                prune: true,
+            },
+        )?;
+        if result.is_ok() {
+            applied.untracked.push(*peer);
+        }
+    }
+
+    let contributors = if policy.contributors {
+        contributors(project, storage, profile)?
+    } else {
+        Vec::new()
+    };
+
+    for (peer, reason) in policy.candidates(delegates(project), contributors) {
+        if &peer == storage.peer_id() {
+            continue;
+        }
+        let result = tracking::track(
+            storage,
+            urn,
+            Some(peer),
+            tracking::config::Config::default(),
+            tracking::policy::Track::MustNotExist,
+        )?;
+        if result.is_ok() {
+            applied.tracked.push((peer, reason));
+        }
+    }
+    Ok(applied)
+}
+
+/// Write a blocklist to the `[tracking]` table of the given `Radicle.toml`, which is
+/// created if needed. Only the `block` key is rewritten, so that the rest of the
+/// file, including comments, is kept as is.
+pub fn write_blocklist(path: &Path, block: &[PeerId]) -> Result<(), io::Error> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err),
+    };
+    let contents = with_blocklist(&contents, block)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+    fs::write(path, contents)
+}
+
+/// Set the `block` key of the `tracking` table in the given TOML document. The table
+/// may also be an inline table, or be defined with dotted keys.
+fn with_blocklist(contents: &str, block: &[PeerId]) -> Result<String, toml_edit::TomlError> {
+    let mut doc = contents.parse::<toml_edit::Document>()?;
+    let array = block
+        .iter()
+        .map(|p| p.to_string())
+        .collect::<toml_edit::Array>();
+
+    match doc
+        .get_mut("tracking")
+        .and_then(|item| item.as_table_like_mut())
+    {
+        Some(table) => match table.get_mut("block").and_then(|i| i.as_value_mut()) {
+            Some(value) => {
+                // Keep any comment following the value.
+                let decor = value.decor().clone();
+
+                *value = toml_edit::Value::Array(array);
+                *value.decor_mut() = decor;
+            }
+            None => {
+                table.insert("block", toml_edit::value(array));
+            }
+        },
+        None => {
+            let mut table = toml_edit::Table::new();
+            if !contents.trim().is_empty() {
+                table.decor_mut().set_prefix("\n");
+            }
+            table.insert("block", toml_edit::value(array));
+            doc.insert("tracking", toml_edit::Item::Table(table));
+        }
+    }
+    Ok(doc.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn test_candidates() {
+        let alice =
+            PeerId::from_str("hyb5to4rshftx4apgmu9s6wnsp4ddmp1mz6ijh4qqey7fb8wrpawxa").unwrap();
+        let bob =
+            PeerId::from_str("hyd7wpd8p5aqnm9htsfoatxkckmw6ingnsdudns9code5xq17h1rhw").unwrap();
+        let eve =
+            PeerId::from_str("hyd1to75dyfpizchxp43rdwhisp8nbr76g5pxa5f4y7jh4pa6jjzns").unwrap();
+
+        let mut policy = Policy::default();
+        assert!(policy.is_default());
+        assert!(policy.candidates([alice], [bob, eve]).is_empty());
+
+        policy.delegates = true;
+        assert_eq!(
+            policy.candidates([alice], [bob, eve]),
+            vec![(alice, Reason::Delegate)]
+        );
+
+        policy.contributors = true;
+        assert!(policy.block(eve));
+        assert!(!policy.block(eve));
+        assert_eq!(
+            policy.candidates([alice], [alice, bob, eve]),
+            vec![(alice, Reason::Delegate), (bob, Reason::Contributor)]
+        );
+
+        let toml = toml::to_string(&policy).unwrap();
+        assert_eq!(toml::from_str::<Policy>(&toml).unwrap(), policy);
+    }
+
+    /// Get the blocklist of a `Radicle.toml` document.
+    fn blocklist(contents: &str) -> Vec<PeerId> {
+        toml::from_str::<toml::Value>(contents).unwrap()["tracking"]
+            .clone()
+            .try_into::<Policy>()
+            .unwrap()
+            .block
+    }
+
+    #[test]
+    fn test_with_blocklist() {
+        let alice =
+            PeerId::from_str("hyb5to4rshftx4apgmu9s6wnsp4ddmp1mz6ijh4qqey7fb8wrpawxa").unwrap();
+        let bob =
+            PeerId::from_str("hyd7wpd8p5aqnm9htsfoatxkckmw6ingnsdudns9code5xq17h1rhw").unwrap();
+
+        // The table is added if missing.
+        let updated = with_blocklist("", &[alice]).unwrap();
+        assert_eq!(blocklist(&updated), vec![alice]);
+
+        let updated = with_blocklist("# Seeds.\n[[seed]]\nname = \"pine\"\n", &[alice]).unwrap();
+        assert!(updated.starts_with("# Seeds.\n[[seed]]\nname = \"pine\"\n"));
+        assert_eq!(blocklist(&updated), vec![alice]);
+
+        // Only the blocklist is replaced, even if it spans multiple lines. Strings
+        // that look like comments or arrays are left alone.
+        let contents = format!(
+            "# Our policy.\n[tracking]\ndelegates = true # Always.\nblock = [\n  \"{}\", # Spam.\n] # Blocked.\n\n[[seed]]\nname = \"pine # [1]\"\n",
+            alice
+        );
+        let updated = with_blocklist(&contents, &[alice, bob]).unwrap();
+        assert!(updated.starts_with("# Our policy.\n[tracking]\ndelegates = true # Always.\n"));
+        assert!(updated.contains("# Blocked."));
+        assert!(updated.ends_with("\n[[seed]]\nname = \"pine # [1]\"\n"));
+        assert_eq!(updated.matches("[tracking]").count(), 1);
+        assert_eq!(blocklist(&updated), vec![alice, bob]);
+
+        // The key is added to an existing table.
+        let updated = with_blocklist("[tracking]\ndelegates = true\n", &[bob]).unwrap();
+        assert!(updated.starts_with("[tracking]\ndelegates = true\n"));
+        assert_eq!(blocklist(&updated), vec![bob]);
+
+        // Dotted keys and inline tables are edited in place.
+        let dotted = format!("tracking.block = [\"{}\"]\n", alice);
+        let updated = with_blocklist(&dotted, &[alice, bob]).unwrap();
+        assert!(!updated.contains("[tracking]"));
+        assert_eq!(blocklist(&updated), vec![alice, bob]);
+
+        let updated = with_blocklist("tracking = { delegates = true }\n", &[bob]).unwrap();
+        assert!(!updated.contains("[tracking]"));
+        assert_eq!(blocklist(&updated), vec![bob]);
+
+        // Invalid documents are left alone.
+        assert!(with_blocklist("[tracking\n", &[bob]).is_err());
+    }
+}
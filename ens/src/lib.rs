@@ -5,6 +5,7 @@ use anyhow::anyhow;
 use ethers::prelude::{Address, Chain, Http, Provider, Signer, SignerMiddleware};
 use librad::git::identities::local::LocalIdentity;
 use librad::git::Storage;
+use librad::profile::Profile;
 
 use radicle_common::args::{Args, Error, Help};
 use radicle_common::ethereum::{
@@ -12,7 +13,7 @@ use radicle_common::ethereum::{
     resolver::{self, PublicResolver},
     ProviderOptions, SignerOptions,
 };
-use radicle_common::{keys, person, seed};
+use radicle_common::{config, keys, person, seed};
 use radicle_terminal as term;
 
 pub const HELP: Help = Help {
@@ -155,7 +156,7 @@ pub fn run(options: Options, ctx: impl term::Context) -> anyhow::Result<()> {
             let signer_opts = options.signer;
             let (wallet, provider) =
                 rt.block_on(term::ethereum::get_wallet(signer_opts, provider))?;
-            rt.block_on(setup(&name, id, provider, wallet, &storage))?;
+            rt.block_on(setup(&name, id, provider, wallet, &storage, &profile))?;
         }
         Operation::SetLocal(name) => set_ens_payload(&name, &storage)?,
     }
@@ -189,6 +190,7 @@ async fn setup(
     provider: Provider<Http>,
     signer: ethereum::Wallet,
     storage: &Storage,
+    profile: &Profile,
 ) -> anyhow::Result<()> {
     let urn = id.urn();
     let chain_id = signer.chain_id();
@@ -217,7 +219,8 @@ async fn setup(
     let seed_url = url::Url::parse(&format!("https://{}", seed_host))?;
 
     let spinner = term::spinner("Querying seed...");
-    let seed_id = match seed::get_seed_id(seed_url) {
+    let config = config::Config::resolve(profile).config;
+    let seed_id = match seed::api_client(&seed_url, config.seeds()).peer_id() {
         Ok(id) => {
             spinner.clear();
             term::text_input("Seed ID", Some(id))?
@@ -7,13 +7,16 @@ use radicle_terminal as term;
 pub use rad_account;
 pub use rad_auth;
 pub use rad_checkout;
+pub use rad_ci;
 pub use rad_clone;
+pub use rad_cob;
 pub use rad_comment;
 pub use rad_edit;
 #[cfg(feature = "ethereum")]
 pub use rad_ens;
 #[cfg(feature = "ethereum")]
 pub use rad_gov;
+pub use rad_inbox;
 pub use rad_init;
 pub use rad_inspect;
 pub use rad_issue;
@@ -21,12 +24,16 @@ pub use rad_ls;
 pub use rad_merge;
 pub use rad_patch;
 pub use rad_path;
+pub use rad_peer;
 pub use rad_pull;
 pub use rad_push;
 pub use rad_remote;
 pub use rad_review;
 pub use rad_rm;
+pub use rad_search;
+pub use rad_seed;
 pub use rad_self;
+pub use rad_show;
 pub use rad_sync;
 pub use rad_track;
 pub use rad_untrack;
@@ -0,0 +1,215 @@
+use std::ffi::OsString;
+use std::str::FromStr;
+
+use anyhow::anyhow;
+
+use radicle_common as common;
+use radicle_common::args::{Args, Error, Help};
+use radicle_common::cobs::inbox::{Inbox, Notification};
+use radicle_common::cobs::search;
+use radicle_common::cobs::Identifier;
+use radicle_common::petname::Petnames;
+use radicle_common::{cobs, keys, project, Urn};
+use radicle_terminal as term;
+
+/// Number of characters of a comment to show.
+pub const PREVIEW_WIDTH: usize = 72;
+
+pub const HELP: Help = Help {
+    name: "inbox",
+    description: env!("CARGO_PKG_DESCRIPTION"),
+    version: env!("CARGO_PKG_VERSION"),
+    usage: r#"
+Usage
+
+    rad inbox [<option>...]
+    rad inbox read [<id>] [<option>...]
+
+    Shows changes to the issues and patches of the current project that
+    you haven't seen yet, such as new comments, revisions, reviews and
+    state changes. Your own changes are never shown.
+
+    The first time the inbox of a project is viewed, everything in it
+    is marked as read.
+
+    Use `read` to mark notifications as read, either for a single issue
+    or patch, or for the whole project.
+
+Options
+
+    --all         Show or mark the notifications of all projects
+    --mentions    Only show comments that mention you
+    --help        Print help
+"#,
+};
+
+#[derive(Debug)]
+pub enum Operation {
+    List { mentions: bool },
+    Read { id: Option<Identifier> },
+}
+
+#[derive(Debug)]
+pub struct Options {
+    pub op: Operation,
+    pub all: bool,
+}
+
+impl Args for Options {
+    fn from_args(args: Vec<OsString>) -> anyhow::Result<(Self, Vec<OsString>)> {
+        use lexopt::prelude::*;
+
+        let mut parser = lexopt::Parser::from_args(args);
+        let mut read = false;
+        let mut id: Option<Identifier> = None;
+        let mut all = false;
+        let mut mentions = false;
+
+        while let Some(arg) = parser.next()? {
+            match arg {
+                Long("all") => {
+                    all = true;
+                }
+                Long("mentions") => {
+                    mentions = true;
+                }
+                Long("help") => {
+                    return Err(Error::Help.into());
+                }
+                Value(val) if !read && val == "read" => {
+                    read = true;
+                }
+                Value(val) if read && id.is_none() => {
+                    let val = val
+                        .to_str()
+                        .ok_or_else(|| anyhow!("object id specified is not UTF-8"))?;
+
+                    id = Some(Identifier::from_str(val)?);
+                }
+                _ => return Err(anyhow!(arg.unexpected())),
+            }
+        }
+
+        if all && id.is_some() {
+            anyhow::bail!("an object id cannot be combined with `--all`");
+        }
+        let op = if read {
+            Operation::Read { id }
+        } else {
+            Operation::List { mentions }
+        };
+
+        Ok((Options { op, all }, vec![]))
+    }
+}
+
+pub fn run(options: Options, ctx: impl term::Context) -> anyhow::Result<()> {
+    let profile = ctx.profile()?;
+    let signer = term::signer(&profile)?;
+    let storage = keys::storage(&profile, signer)?;
+    let cobs = cobs::store(&profile, &storage)?;
+    let inbox = Inbox::open(profile.paths());
+    let petnames = Petnames::open(&profile)?;
+
+    let projects: Vec<(Urn, String)> = if options.all {
+        project::list(&storage)?
+            .into_iter()
+            .map(|(urn, meta, _)| (urn, meta.name))
+            .collect()
+    } else {
+        let (urn, _) = project::cwd()
+            .map_err(|_| anyhow!("this command must be run in the context of a project"))?;
+        let name = project::get(&storage, &urn)?
+            .map(|meta| meta.name)
+            .unwrap_or_else(|| urn.encode_id());
+
+        vec![(urn, name)]
+    };
+
+    match options.op {
+        Operation::List { mentions } => {
+            let mut total = 0;
+
+            for (urn, name) in &projects {
+                let unread = inbox
+                    .unread(&cobs, urn)?
+                    .into_iter()
+                    .filter(|n| !mentions || n.mention)
+                    .collect::<Vec<_>>();
+
+                if unread.is_empty() {
+                    continue;
+                }
+                if options.all {
+                    term::headline(&format!(
+                        "{} {}",
+                        term::format::highlight(name),
+                        term::format::dim(format!("({})", unread.len()))
+                    ));
+                }
+                total += unread.len();
+
+                for mut n in unread {
+                    if let Some(author) = &mut n.author {
+                        author.resolve(&storage).ok();
+                    }
+                    print(&n, &petnames);
+                }
+            }
+            if total == 0 {
+                term::print(term::format::italic("Your inbox is empty."));
+            }
+        }
+        Operation::Read { id } => {
+            let mut marked = 0;
+
+            for (urn, _) in &projects {
+                let id = match &id {
+                    Some(id) => Some(
+                        cobs.resolve_id::<cobs::issue::Issue>(urn, id)?
+                            .or(cobs.resolve_id::<cobs::patch::Patch>(urn, id)?)
+                            .ok_or_else(|| anyhow!("issue or patch '{}' not found", id))?,
+                    ),
+                    None => None,
+                };
+                marked += inbox.mark_read(&cobs, urn, id.as_ref())?;
+            }
+            term::success!("Marked {} notification(s) as read", marked);
+        }
+    }
+
+    Ok(())
+}
+
+fn print(n: &Notification, petnames: &Petnames) {
+    let badge = match n.kind {
+        search::Kind::Issue => term::format::badge_secondary(n.kind),
+        search::Kind::Patch => term::format::badge_primary(n.kind),
+    };
+    let mut header = vec![
+        badge,
+        term::format::highlight(common::fmt::cob(&n.id)),
+        term::format::bold(&n.title),
+    ];
+    if let Some(author) = &n.author {
+        header.push(term::format::tertiary(author.name(petnames)));
+    }
+    header.push(term::format::dim(&n.change));
+    header.push(term::format::dim(n.timestamp));
+
+    if n.mention {
+        header.push(term::format::yellow("@mention"));
+    }
+    term::info!("{}", header.join(" "));
+
+    if let Some(body) = n.change.body() {
+        let line = body.lines().next().unwrap_or_default();
+        let mut preview = line.chars().take(PREVIEW_WIDTH).collect::<String>();
+
+        if preview.len() < body.trim_end().len() {
+            preview.push('…');
+        }
+        term::indented(&term::format::italic(preview));
+    }
+    term::blank();
+}
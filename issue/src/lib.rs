@@ -20,7 +20,7 @@ Usage
     rad issue state <id> [--closed | --open | --solved]
     rad issue delete <id>
     rad issue react <id> [--emoji <char>]
-    rad issue list
+    rad issue list [--open | --closed | --solved]
 
 Options
 
@@ -66,7 +66,9 @@ pub enum Operation {
         id: cobs::issue::IssueId,
         reaction: cobs::Reaction,
     },
-    List,
+    List {
+        state: Option<cobs::issue::State>,
+    },
 }
 
 /// Tool options.
@@ -95,15 +97,21 @@ impl Args for Options {
                 Long("title") if op == Some(OperationName::Create) => {
                     title = Some(parser.value()?.to_string_lossy().into());
                 }
-                Long("closed") if op == Some(OperationName::State) => {
+                Long("closed")
+                    if op == Some(OperationName::State) || op == Some(OperationName::List) =>
+                {
                     state = Some(cobs::issue::State::Closed {
                         reason: CloseReason::Other,
                     });
                 }
-                Long("open") if op == Some(OperationName::State) => {
+                Long("open")
+                    if op == Some(OperationName::State) || op == Some(OperationName::List) =>
+                {
                     state = Some(cobs::issue::State::Open);
                 }
-                Long("solved") if op == Some(OperationName::State) => {
+                Long("solved")
+                    if op == Some(OperationName::State) || op == Some(OperationName::List) =>
+                {
                     state = Some(cobs::issue::State::Closed {
                         reason: CloseReason::Solved,
                     });
@@ -157,7 +165,7 @@ impl Args for Options {
             OperationName::Delete => Operation::Delete {
                 id: id.ok_or_else(|| anyhow!("an issue id to remove must be provided"))?,
             },
-            OperationName::List => Operation::List,
+            OperationName::List => Operation::List { state },
         };
 
         Ok((Options { op }, vec![]))
@@ -227,8 +235,12 @@ pub fn run(options: Options, ctx: impl term::Context) -> anyhow::Result<()> {
                 issues.create(&project, &meta.title, description.trim(), &meta.labels)?;
             }
         }
-        Operation::List => {
-            for (id, issue) in issues.all(&project)? {
+        Operation::List { state } => {
+            let issues = match state {
+                Some(state) => issues.filter(&project, state)?,
+                None => issues.all(&project)?,
+            };
+            for (id, issue) in issues {
                 println!("{} {}", id, issue.title());
             }
         }
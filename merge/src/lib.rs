@@ -6,8 +6,9 @@ use anyhow::{anyhow, Context};
 
 use radicle_common as common;
 use radicle_common::args::{Args, Error, Help};
-use radicle_common::cobs::patch::{Patch, PatchId};
+use radicle_common::cobs::patch::{CheckStatus, Patch, PatchId};
 use radicle_common::patch::MergeStyle;
+use radicle_common::petname::Petnames;
 use radicle_common::{cobs, git, keys, project};
 use radicle_terminal as term;
 
@@ -29,6 +30,7 @@ Options
 
     -i, --interactive         Ask for confirmations
     -r, --revision <number>   Revision number to merge, defaults to the latest
+        --require-checks      Refuse to merge unless all checks of the revision pass
         --help                Print help
 "#,
 };
@@ -48,6 +50,7 @@ pub struct Options {
     pub id: cobs::Identifier,
     pub interactive: bool,
     pub revision: Option<RevisionIx>,
+    pub require_checks: bool,
 }
 
 impl Args for Options {
@@ -58,6 +61,7 @@ impl Args for Options {
         let mut id: Option<cobs::Identifier> = None;
         let mut revision: Option<RevisionIx> = None;
         let mut interactive = false;
+        let mut require_checks = false;
 
         while let Some(arg) = parser.next()? {
             match arg {
@@ -67,6 +71,9 @@ impl Args for Options {
                 Long("interactive") | Short('i') => {
                     interactive = true;
                 }
+                Long("require-checks") => {
+                    require_checks = true;
+                }
                 Long("revision") | Short('r') => {
                     let value = parser.value()?;
                     let id =
@@ -94,6 +101,7 @@ impl Args for Options {
                 id: id.ok_or_else(|| anyhow!("a patch id to merge must be provided"))?,
                 interactive,
                 revision,
+                require_checks,
             },
             vec![],
         ))
@@ -113,6 +121,7 @@ pub fn run(options: Options, ctx: impl term::Context) -> anyhow::Result<()> {
         .ok_or_else(|| anyhow!("couldn't load project {} from local state", urn))?;
     let cobs = cobs::store(&profile, &storage)?;
     let patches = cobs.patches();
+    let petnames = Petnames::open(&profile)?;
 
     if repo.head_detached()? {
         anyhow::bail!("HEAD is in a detached state; can't merge");
@@ -139,6 +148,31 @@ pub fn run(options: Options, ctx: impl term::Context) -> anyhow::Result<()> {
         .get(revision_id)
         .ok_or_else(|| anyhow!("revision R{} does not exist", revision_id))?;
 
+    //
+    // Check results
+    //
+    for check in revision.checks.values() {
+        term::info!(
+            "{} {} {}",
+            rad_patch::pretty_check_status(check.status),
+            term::format::highlight(&check.name),
+            term::format::italic(&check.summary)
+        );
+    }
+    match revision.check_status() {
+        Some(CheckStatus::Pass) => {}
+        Some(CheckStatus::Fail) if options.require_checks => {
+            anyhow::bail!("revision R{} has failing checks; can't merge", revision_id);
+        }
+        Some(CheckStatus::Fail) => {
+            term::warning(&format!("Revision R{} has failing checks", revision_id));
+        }
+        None if options.require_checks => {
+            anyhow::bail!("revision R{} has no checks; can't merge", revision_id);
+        }
+        None => {}
+    }
+
     //
     // Analyze merge
     //
@@ -206,7 +240,7 @@ pub fn run(options: Options, ctx: impl term::Context) -> anyhow::Result<()> {
         term::format::tertiary(common::fmt::cob(&patch_id)),
         term::format::dim(format!("R{}", revision_id)),
         term::format::secondary(common::fmt::oid(&revision.oid)),
-        term::format::tertiary(patch.author.name()),
+        term::format::tertiary(patch.author.name(&petnames)),
         term::format::highlight(branch),
         term::format::secondary(common::fmt::oid(&head_oid)),
         merge_style_pretty
@@ -266,10 +300,11 @@ fn merge_commit(
 ) -> anyhow::Result<()> {
     let description = patch.description().trim();
     let mut merge_opts = git::MergeOptions::new();
+    // Petnames are local, so the published message uses the author's own name.
     let mut merge_msg = format!(
         "Merge patch '{}' from {}",
         common::fmt::cob(&patch_id),
-        patch.author.name()
+        patch.author.name(&Petnames::default())
     );
     write!(&mut merge_msg, "\n\n")?;
 
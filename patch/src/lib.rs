@@ -8,7 +8,7 @@ use std::str::FromStr;
 
 use anyhow::anyhow;
 
-use common::cobs::patch::Verdict;
+use common::cobs::patch::{CheckStatus, RevisionIx, Verdict};
 use librad::git::identities::local::LocalIdentity;
 use librad::git::storage::ReadOnlyStorage;
 use librad::git::Storage;
@@ -18,6 +18,7 @@ use librad::profile::Profile;
 use radicle_common as common;
 use radicle_common::args::{Args, Error, Help};
 use radicle_common::cobs::patch::{MergeTarget, Patch, PatchId, PatchStore};
+use radicle_common::petname::Petnames;
 use radicle_common::tokio;
 use radicle_common::{cobs, git, keys, patch, project, sync};
 use radicle_terminal as term;
@@ -31,6 +32,7 @@ pub const HELP: Help = Help {
 Usage
 
     rad patch [<option>...]
+    rad patch check <id> --name <name> --status <pass|fail> [<option>...]
 
 Create options
 
@@ -40,6 +42,13 @@ Create options
     -m, --message [<string>]   Provide a comment message to the patch or revision (default: prompt)
         --no-message           Leave the patch or revision comment message blank
 
+Check options
+
+    --name <name>              Name of the check, eg. `build`
+    --status <pass|fail>       Result of the check
+    --summary <string>         Summary of the check output, eg. the failing tests
+    --revision <number>        Revision number to attach the result to, defaults to the latest
+
 Options
 
     -l, --list                 List all patches (default: false)
@@ -79,6 +88,16 @@ impl Default for Update {
     }
 }
 
+/// Options of `rad patch check`.
+#[derive(Debug)]
+pub struct CheckOptions {
+    pub id: cobs::Identifier,
+    pub name: String,
+    pub status: CheckStatus,
+    pub summary: String,
+    pub revision: Option<RevisionIx>,
+}
+
 #[derive(Default, Debug)]
 pub struct Options {
     pub list: bool,
@@ -87,6 +106,7 @@ pub struct Options {
     pub push: bool,
     pub update: Update,
     pub message: Comment,
+    pub check: Option<CheckOptions>,
 }
 
 impl Args for Options {
@@ -100,6 +120,12 @@ impl Args for Options {
         let mut message = Comment::default();
         let mut push = true;
         let mut update = Update::default();
+        let mut check = false;
+        let mut check_id: Option<cobs::Identifier> = None;
+        let mut check_name: Option<String> = None;
+        let mut check_status: Option<CheckStatus> = None;
+        let mut check_summary = String::new();
+        let mut check_revision: Option<RevisionIx> = None;
 
         while let Some(arg) = parser.next()? {
             match arg {
@@ -141,13 +167,63 @@ impl Args for Options {
                 Long("no-push") => {
                     push = false;
                 }
+                Long("name") if check => {
+                    check_name = Some(parser.value()?.to_string_lossy().into());
+                }
+                Long("status") if check => {
+                    let value = parser.value()?;
+                    let status =
+                        CheckStatus::from_str(&value.to_string_lossy()).map_err(|e| anyhow!(e))?;
+
+                    check_status = Some(status);
+                }
+                Long("summary") if check => {
+                    check_summary = parser.value()?.to_string_lossy().into();
+                }
+                Long("revision") | Short('r') if check => {
+                    let value = parser.value()?;
+                    let ix =
+                        RevisionIx::from_str(value.to_str().unwrap_or_default()).map_err(|_| {
+                            anyhow!("invalid revision number `{}`", value.to_string_lossy())
+                        })?;
+                    check_revision = Some(ix);
+                }
                 Long("help") => {
                     return Err(Error::Help.into());
                 }
+                Value(val) if !check && val == "check" => {
+                    check = true;
+                }
+                Value(val) if check && check_id.is_none() => {
+                    let val = val
+                        .to_str()
+                        .ok_or_else(|| anyhow!("patch id specified is not UTF-8"))?;
+                    let id = cobs::Identifier::from_str(val)
+                        .map_err(|_| anyhow!("invalid patch id '{}'", val))?;
+
+                    check_id = Some(id);
+                }
                 _ => return Err(anyhow::anyhow!(arg.unexpected())),
             }
         }
 
+        let check = if check {
+            let name = check_name
+                .filter(|n| !n.trim().is_empty())
+                .ok_or_else(|| anyhow!("a check name must be specified with `--name`"))?;
+
+            Some(CheckOptions {
+                id: check_id.ok_or_else(|| anyhow!("a patch id must be specified"))?,
+                name,
+                status: check_status
+                    .ok_or_else(|| anyhow!("a check status must be specified with `--status`"))?,
+                summary: check_summary,
+                revision: check_revision,
+            })
+        } else {
+            None
+        };
+
         Ok((
             Options {
                 list,
@@ -156,6 +232,7 @@ impl Args for Options {
                 push,
                 update,
                 verbose,
+                check,
             },
             vec![],
         ))
@@ -172,7 +249,9 @@ pub fn run(options: Options, ctx: impl term::Context) -> anyhow::Result<()> {
     let project = project::get(&storage, &urn)?
         .ok_or_else(|| anyhow!("couldn't load project {} from local state", urn))?;
 
-    if options.list {
+    if let Some(opts) = options.check {
+        check(&storage, &profile, &project, opts)?;
+    } else if options.list {
         list(&storage, Some(repo), &profile, &project, options)?;
     } else {
         create(&storage, &profile, &project, &repo, options)?;
@@ -205,6 +284,7 @@ fn list(
     let patches = cobs.patches();
     let proposed = patches.proposed(&project.urn)?;
     let monorepo = git::Repository::open_bare(profile.paths().git_dir())?;
+    let petnames = Petnames::open(profile)?;
 
     // Patches the user authored.
     let mut own = Vec::new();
@@ -228,7 +308,16 @@ fn list(
         for (id, patch) in &mut own {
             term::blank();
 
-            print(&cobs.whoami, id, patch, project, &monorepo, &repo, storage)?;
+            print(
+                &cobs.whoami,
+                id,
+                patch,
+                project,
+                &monorepo,
+                &repo,
+                storage,
+                &petnames,
+            )?;
         }
     }
     term::blank();
@@ -241,7 +330,16 @@ fn list(
         for (id, patch) in &mut other {
             term::blank();
 
-            print(&cobs.whoami, id, patch, project, &monorepo, &repo, storage)?;
+            print(
+                &cobs.whoami,
+                id,
+                patch,
+                project,
+                &monorepo,
+                &repo,
+                storage,
+                &petnames,
+            )?;
         }
     }
     term::blank();
@@ -249,6 +347,50 @@ fn list(
     Ok(())
 }
 
+fn check(
+    storage: &Storage,
+    profile: &Profile,
+    project: &project::Metadata,
+    options: CheckOptions,
+) -> anyhow::Result<()> {
+    let cobs = cobs::store(profile, storage)?;
+    let patches = cobs.patches();
+    let (patch_id, patch) = patches
+        .resolve(&project.urn, &options.id)?
+        .ok_or_else(|| anyhow!("couldn't find patch {} locally", &options.id))?;
+    let revision = options.revision.unwrap_or_else(|| patch.version());
+
+    if patch.revisions.get(revision).is_none() {
+        anyhow::bail!("revision R{} does not exist", revision);
+    }
+    let check = patches.check(
+        &project.urn,
+        &patch_id,
+        revision,
+        &options.name,
+        options.status,
+        options.summary,
+    )?;
+
+    term::success!(
+        "Check {} of {} {} marked as {}",
+        term::format::highlight(&check.name),
+        term::format::tertiary(common::fmt::cob(&patch_id)),
+        term::format::dim(format!("R{}", revision)),
+        pretty_check_status(check.status),
+    );
+
+    Ok(())
+}
+
+/// Human friendly check status.
+pub fn pretty_check_status(status: CheckStatus) -> String {
+    match status {
+        CheckStatus::Pass => term::format::positive("✓ pass"),
+        CheckStatus::Fail => term::format::negative("✗ fail"),
+    }
+}
+
 fn update(
     patch: Patch,
     patch_id: PatchId,
@@ -446,10 +588,11 @@ fn create(
     // TODO: List matching working copy refs for all targets.
 
     let user_name = storage.config_readonly()?.user_name()?;
+    let petnames = Petnames::open(profile)?;
     term::blank();
     term::info!(
         "{}/{} ({}) <- {}/{} ({})",
-        target_peer.name(),
+        target_peer.name(&petnames),
         term::format::highlight(&project.default_branch.to_string()),
         term::format::secondary(&common::fmt::oid(target_oid)),
         user_name,
@@ -594,6 +737,7 @@ pub fn print(
     monorepo: &git::Repository,
     repo: &Option<git::Repository>,
     storage: &Storage,
+    petnames: &Petnames,
 ) -> anyhow::Result<()> {
     for r in patch.revisions.iter_mut() {
         for (_, r) in &mut r.reviews {
@@ -610,7 +754,7 @@ pub fn print(
     let mut author_info = vec![format!(
         "{}* opened by {}",
         prefix,
-        term::format::tertiary(patch.author.name()),
+        term::format::tertiary(patch.author.name(petnames)),
     )];
 
     if you {
@@ -647,7 +791,7 @@ pub fn print(
                 "{}{} by {} {}",
                 " ".repeat(term::text_width(prefix)),
                 term::format::secondary(term::format::dim("✓ merged")),
-                term::format::tertiary(peer.name()),
+                term::format::tertiary(peer.name(petnames)),
                 badges.join(" "),
             ),
         ));
@@ -674,11 +818,38 @@ pub fn print(
                 "{}{} by {} {}",
                 " ".repeat(term::text_width(prefix)),
                 verdict,
-                term::format::tertiary(review.author.name()),
+                term::format::tertiary(review.author.name(petnames)),
                 badges.join(" "),
             ),
         ));
     }
+    for check in revision.checks.values() {
+        let peer = project::PeerInfo::get(&check.runner, project, storage);
+        let status = match check.status {
+            CheckStatus::Pass => term::format::positive(term::format::dim("✓ passed")),
+            CheckStatus::Fail => term::format::negative(term::format::dim("✗ failed")),
+        };
+        let mut summary = Vec::new();
+
+        if peer.id == *storage.peer_id() {
+            summary.push(term::format::secondary("(you)"));
+        }
+        if !check.summary.is_empty() {
+            summary.push(term::format::italic(&check.summary));
+        }
+
+        timeline.push((
+            check.timestamp,
+            format!(
+                "{}{} {} on {} {}",
+                " ".repeat(term::text_width(prefix)),
+                term::format::highlight(&check.name),
+                status,
+                term::format::tertiary(peer.name(petnames)),
+                summary.join(" "),
+            ),
+        ));
+    }
     timeline.sort_by_key(|(t, _)| *t);
 
     for (time, event) in timeline.iter().rev() {
@@ -0,0 +1,153 @@
+use std::ffi::OsString;
+use std::str::FromStr;
+
+use anyhow::anyhow;
+
+use librad::PeerId;
+
+use radicle_common::args::{Args, Error, Help};
+use radicle_common::petname::Petnames;
+use radicle_terminal as term;
+
+pub const HELP: Help = Help {
+    name: "peer",
+    description: env!("CARGO_PKG_DESCRIPTION"),
+    version: env!("CARGO_PKG_VERSION"),
+    usage: r#"
+Usage
+
+    rad peer name <peer-id> <name> [<option>...]
+    rad peer name <peer-id> --remove [<option>...]
+    rad peer ls [<option>...]
+
+    Manages peer petnames. Petnames are local names for peers, used
+    wherever peers are displayed, eg. in the tracking graph or as patch
+    and issue authors. They take precedence over the names peers give
+    themselves in their identity, and are never shared.
+
+    Petnames must be unique, and can't contain whitespace. Adding a
+    remote with `rad remote add` also sets the peer's petname, if it
+    doesn't have one.
+
+Options
+
+    --remove    Remove the peer's petname
+    --help      Print help
+"#,
+};
+
+#[derive(Debug)]
+pub enum Operation {
+    Name { peer: PeerId, name: Option<String> },
+    List,
+}
+
+#[derive(Debug)]
+pub struct Options {
+    pub op: Operation,
+}
+
+impl Args for Options {
+    fn from_args(args: Vec<OsString>) -> anyhow::Result<(Self, Vec<OsString>)> {
+        use lexopt::prelude::*;
+
+        let mut parser = lexopt::Parser::from_args(args);
+        let mut op: Option<String> = None;
+        let mut peer: Option<PeerId> = None;
+        let mut name: Option<String> = None;
+        let mut remove = false;
+
+        while let Some(arg) = parser.next()? {
+            match arg {
+                Long("remove") => {
+                    remove = true;
+                }
+                Long("help") => {
+                    return Err(Error::Help.into());
+                }
+                Value(val) if op.is_none() => match val.to_string_lossy().as_ref() {
+                    "name" | "ls" => op = Some(val.to_string_lossy().into_owned()),
+                    unknown => anyhow::bail!("unknown operation '{}'", unknown),
+                },
+                Value(val) if peer.is_none() && op.as_deref() == Some("name") => {
+                    let val = val.to_string_lossy();
+                    let val = PeerId::from_str(&val)
+                        .map_err(|_| anyhow!("invalid <peer-id> '{}'", val))?;
+
+                    peer = Some(val);
+                }
+                Value(val) if name.is_none() && op.as_deref() == Some("name") => {
+                    name = Some(val.to_string_lossy().into_owned());
+                }
+                _ => return Err(anyhow!(arg.unexpected())),
+            }
+        }
+
+        let op = match op.as_deref() {
+            Some("name") => {
+                let peer = peer.ok_or_else(|| anyhow!("a <peer-id> must be specified"))?;
+
+                match (name, remove) {
+                    (Some(_), true) => {
+                        anyhow::bail!("`--remove` can't be used when a name is specified")
+                    }
+                    (None, false) => anyhow::bail!("a name must be specified, or `--remove`"),
+                    (name, _) => Operation::Name { peer, name },
+                }
+            }
+            Some("ls") | None => Operation::List,
+            _ => unreachable!(),
+        };
+
+        Ok((Options { op }, vec![]))
+    }
+}
+
+pub fn run(options: Options, ctx: impl term::Context) -> anyhow::Result<()> {
+    let profile = ctx.profile()?;
+    let mut petnames = Petnames::open(&profile)?;
+
+    match options.op {
+        Operation::Name {
+            peer,
+            name: Some(name),
+        } => {
+            petnames.set(peer, &name).map_err(|err| Error::WithHint {
+                err: err.into(),
+                hint: "hint: petnames must be unique, and can't contain whitespace",
+            })?;
+            petnames.save()?;
+
+            term::success!(
+                "Peer {} is now known as {}",
+                term::format::tertiary(peer),
+                term::format::highlight(name)
+            );
+        }
+        Operation::Name { peer, name: None } => match petnames.remove(&peer) {
+            Some(name) => {
+                petnames.save()?;
+                term::success!(
+                    "Petname {} of peer {} removed",
+                    term::format::highlight(name),
+                    term::format::tertiary(peer)
+                );
+            }
+            None => anyhow::bail!("peer {} has no petname", peer),
+        },
+        Operation::List => {
+            let mut table = term::Table::default();
+            for (peer, name) in petnames.iter() {
+                table.push([term::format::bold(name), term::format::tertiary(peer)]);
+            }
+
+            if table.is_empty() {
+                term::print(term::format::italic("No petnames set."));
+            } else {
+                table.render();
+            }
+        }
+    }
+
+    Ok(())
+}
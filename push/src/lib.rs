@@ -143,6 +143,7 @@ pub fn run(options: Options, ctx: impl term::Context) -> anyhow::Result<()> {
                 mode: Mode::Push,
                 origin: None,
                 sync_self: false,
+                ..rad_sync::Options::default()
             },
             ctx,
         )?;
@@ -1,12 +1,18 @@
+use std::collections::HashSet;
+use std::convert::TryFrom;
 use std::ffi::OsString;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use anyhow::anyhow;
 use anyhow::Context as _;
 
+use librad::git::storage::Storage;
 use librad::git::tracking;
+use librad::git::Urn;
 use librad::PeerId;
 
 use radicle_common::args::{Args, Error, Help};
+use radicle_common::petname::Petnames;
 use radicle_common::{git, keys, project, sync, tokio};
 use radicle_terminal as term;
 
@@ -20,15 +26,27 @@ Usage
     rad remote add <name> <peer-id> [-f | --fetch]
     rad remote rm <name | peer-id>
     rad remote ls
+    rad remote prune [--stale <days>] [--dry-run]
 
 Examples
 
     rad remote add cloudhead hyn9diwfnytahjq8u3iw63h9jte1ydcatxax3saymwdxqu1zo645pe
 
+    When adding a remote, its name is also saved as the peer's petname, unless
+    the peer already has one. See `rad peer name`.
+
+    `rad remote prune` removes the remotes of peers that are no longer
+    tracked, or that have no branches, as well as remote-tracking branches
+    left behind by removed remotes. With `--stale`, peers whose branches
+    haven't been updated in the given number of days are untracked first.
+    Project delegates are never untracked.
+
 Options
 
-    -f, --fetch     Fetch the remote immediately after it is setup
-        --help      Print help
+    -f, --fetch         Fetch the remote immediately after it is setup
+        --stale <days>  Untrack peers without updates for the given number of days
+        --dry-run       Show what would be pruned, without pruning anything
+        --help          Print help
 "#,
 };
 
@@ -43,6 +61,10 @@ pub enum Operation {
         remote: String,
     },
     List,
+    Prune {
+        stale: Option<u64>,
+        dry_run: bool,
+    },
 }
 
 /// Tool options.
@@ -60,6 +82,8 @@ impl Args for Options {
         let mut remote: Option<String> = None;
         let mut op: Option<String> = None;
         let mut fetch = false;
+        let mut stale: Option<u64> = None;
+        let mut dry_run = false;
 
         while let Some(arg) = parser.next()? {
             match arg {
@@ -69,6 +93,24 @@ impl Args for Options {
                 Long("fetch") | Short('f') if op.is_some() => {
                     fetch = true;
                 }
+                Long("stale") if op.as_deref() == Some("prune") => {
+                    let value = parser.value()?;
+                    let days = value
+                        .to_string_lossy()
+                        .parse()
+                        .context("invalid value specified for '--stale'")?;
+
+                    if stale_secs(days).is_none() {
+                        anyhow::bail!(
+                            "invalid value specified for '--stale': {} days is out of range",
+                            days
+                        );
+                    }
+                    stale = Some(days);
+                }
+                Long("dry-run") if op.as_deref() == Some("prune") => {
+                    dry_run = true;
+                }
                 Value(val) if op.is_none() => {
                     op = Some(val.to_string_lossy().to_string());
                 }
@@ -95,6 +137,7 @@ impl Args for Options {
                     remote: remote.ok_or_else(|| anyhow!("a remote name must be specified"))?,
                 },
                 "ls" => Operation::List,
+                "prune" => Operation::Prune { stale, dry_run },
 
                 unknown => anyhow::bail!("unknown operation '{}'", unknown),
             },
@@ -130,6 +173,14 @@ pub fn run(options: Options, ctx: impl term::Context) -> anyhow::Result<()> {
                 term::format::highlight(peer)
             );
 
+            let mut petnames = Petnames::open(&profile)?;
+            if petnames.get(&peer).is_none() {
+                match petnames.set(peer, &name) {
+                    Ok(_) => petnames.save()?,
+                    Err(err) => term::warning(&format!("Petname not saved: {}", err)),
+                }
+            }
+
             if fetch {
                 let rt = tokio::runtime::Runtime::new()?;
                 let seeds = sync::seeds(&profile)?;
@@ -143,7 +194,124 @@ pub fn run(options: Options, ctx: impl term::Context) -> anyhow::Result<()> {
             );
         }
         Operation::Remove { remote } => term::remote::remove(&remote, &storage, &repo, &urn)?,
-        Operation::List => term::remote::list(&storage, &repo, &urn)?,
+        Operation::List => {
+            let petnames = Petnames::open(&profile)?;
+            term::remote::list(&storage, &repo, &urn, &petnames)?
+        }
+        Operation::Prune { stale, dry_run } => {
+            let petnames = Petnames::open(&profile)?;
+            prune(&urn, &repo, &storage, &petnames, stale, dry_run)?
+        }
+    }
+
+    Ok(())
+}
+
+/// Get the given number of days in seconds, if it doesn't overflow.
+fn stale_secs(days: u64) -> Option<i64> {
+    days.checked_mul(24 * 60 * 60)
+        .and_then(|secs| i64::try_from(secs).ok())
+}
+
+/// Remove orphaned remotes and remote-tracking branches, and optionally untrack
+/// stale peers.
+fn prune(
+    urn: &Urn,
+    repo: &git::Repository,
+    storage: &Storage,
+    petnames: &Petnames,
+    stale: Option<u64>,
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    let proj = project::get(storage, urn)?
+        .ok_or_else(|| anyhow!("project {} not found in local storage", urn))?;
+    let tracked = project::tracked(&proj, storage)?;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default();
+    let prefix = if dry_run {
+        term::format::dim("(dry run)")
+    } else {
+        String::new()
+    };
+    let mut pruned = 0;
+
+    let mut untracked = HashSet::new();
+    if let Some(days) = stale {
+        let secs = stale_secs(days).ok_or_else(|| anyhow!("{} days is out of range", days))?;
+        let cutoff = now.saturating_sub(secs);
+
+        for (peer, info) in &tracked {
+            if info.delegate {
+                continue;
+            }
+            match project::last_update(storage, urn, peer)? {
+                Some(time) if time < cutoff => {}
+                _ => continue,
+            }
+            if !dry_run {
+                tracking::untrack(
+                    storage,
+                    urn,
+                    *peer,
+                    tracking::UntrackArgs {
+                        policy: tracking::policy::Untrack::MustExist,
+                        prune: true,
+                    },
+                )??;
+            }
+            term::success!(
+                "Untracked stale peer {} {} {}",
+                term::format::tertiary(petnames.peer(peer)),
+                term::format::dim(format!("(no updates in {} day(s))", days)),
+                prefix
+            );
+            untracked.insert(*peer);
+            pruned += 1;
+        }
+    }
+
+    let mut remotes = git::remotes(repo)?;
+    remotes.dedup_by(|(a, _), (b, _)| a == b);
+
+    for (name, peer) in remotes {
+        if &peer == storage.peer_id() {
+            continue;
+        }
+        let reason = if !tracked.contains_key(&peer) || untracked.contains(&peer) {
+            "peer not tracked"
+        } else if project::last_update(storage, urn, &peer)?.is_none() {
+            "peer has no branches"
+        } else {
+            continue;
+        };
+        if !dry_run {
+            repo.remote_delete(&name)?;
+        }
+        term::success!(
+            "Removed remote {} {} {}",
+            term::format::highlight(&name),
+            term::format::dim(format!("({})", reason)),
+            prefix
+        );
+        pruned += 1;
+    }
+
+    for refname in git::orphaned_remote_refs(repo)? {
+        if !dry_run {
+            repo.find_reference(&refname)?.delete()?;
+        }
+        term::success!(
+            "Removed remote-tracking branch {} {}",
+            term::format::highlight(&refname),
+            prefix
+        );
+        pruned += 1;
+    }
+
+    if pruned == 0 {
+        term::info!("{}", term::format::dim("Nothing to prune"));
     }
 
     Ok(())
@@ -7,6 +7,7 @@ use common::cobs::patch::Verdict;
 use radicle_common as common;
 use radicle_common::args::{Args, Error, Help};
 use radicle_common::cobs::patch::Patch;
+use radicle_common::petname::Petnames;
 use radicle_common::tokio;
 use radicle_common::{cobs, keys, project, sync};
 use radicle_terminal as term;
@@ -151,6 +152,7 @@ pub fn run(options: Options, ctx: impl term::Context) -> anyhow::Result<()> {
         .ok_or_else(|| anyhow!("revision R{} does not exist", revision_ix))?;
     let message = options.message.get(REVIEW_HELP_MSG);
 
+    let petnames = Petnames::open(&profile)?;
     patch.author.resolve(&storage).ok();
 
     let verdict_pretty = match options.verdict {
@@ -163,7 +165,7 @@ pub fn run(options: Options, ctx: impl term::Context) -> anyhow::Result<()> {
         verdict_pretty,
         patch_id_pretty,
         term::format::dim(format!("R{}", revision_ix)),
-        term::format::tertiary(patch.author.name())
+        term::format::tertiary(patch.author.name(&petnames))
     )) {
         anyhow::bail!("Patch review aborted");
     }
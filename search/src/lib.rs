@@ -0,0 +1,149 @@
+use std::ffi::OsString;
+
+use anyhow::anyhow;
+
+use radicle_common as common;
+use radicle_common::args::{Args, Error, Help};
+use radicle_common::cobs::issue::Issue;
+use radicle_common::cobs::patch::Patch;
+use radicle_common::cobs::search;
+use radicle_common::petname::Petnames;
+use radicle_common::{cobs, keys, project, Urn};
+use radicle_terminal as term;
+
+pub const HELP: Help = Help {
+    name: "search",
+    description: env!("CARGO_PKG_DESCRIPTION"),
+    version: env!("CARGO_PKG_VERSION"),
+    usage: r#"
+Usage
+
+    rad search <query> [<option>...]
+
+    Searches issue and patch titles, descriptions, comments, replies
+    and reviews of the current project. The search is case-insensitive.
+
+Options
+
+    --all       Search all projects on this device
+    --help      Print help
+"#,
+};
+
+#[derive(Debug)]
+pub struct Options {
+    pub query: String,
+    pub all: bool,
+}
+
+impl Args for Options {
+    fn from_args(args: Vec<OsString>) -> anyhow::Result<(Self, Vec<OsString>)> {
+        use lexopt::prelude::*;
+
+        let mut parser = lexopt::Parser::from_args(args);
+        let mut query: Option<String> = None;
+        let mut all = false;
+
+        while let Some(arg) = parser.next()? {
+            match arg {
+                Long("all") => {
+                    all = true;
+                }
+                Long("help") => {
+                    return Err(Error::Help.into());
+                }
+                Value(val) if query.is_none() => {
+                    let val = val
+                        .to_str()
+                        .ok_or_else(|| anyhow!("search query is not UTF-8"))?;
+
+                    query = Some(val.to_owned());
+                }
+                _ => return Err(anyhow!(arg.unexpected())),
+            }
+        }
+
+        let query = query
+            .filter(|q| !q.trim().is_empty())
+            .ok_or_else(|| anyhow!("a search query must be provided"))?;
+
+        Ok((Options { query, all }, vec![]))
+    }
+}
+
+pub fn run(options: Options, ctx: impl term::Context) -> anyhow::Result<()> {
+    let profile = ctx.profile()?;
+    let signer = term::signer(&profile)?;
+    let storage = keys::storage(&profile, signer)?;
+    let cobs = cobs::store(&profile, &storage)?;
+    let petnames = Petnames::open(&profile)?;
+
+    let projects: Vec<(Urn, Option<String>)> = if options.all {
+        project::list(&storage)?
+            .into_iter()
+            .map(|(urn, meta, _)| (urn, Some(meta.name)))
+            .collect()
+    } else {
+        let (urn, _) = project::cwd()
+            .map_err(|_| anyhow!("this command must be run in the context of a project"))?;
+        vec![(urn, None)]
+    };
+
+    let mut hits = Vec::new();
+    for (urn, name) in &projects {
+        match search_project(&cobs, urn, &options.query) {
+            Ok(found) => hits.extend(found.into_iter().map(|h| (name, h))),
+            // When searching every project, one unreadable project shouldn't stop the search.
+            Err(err) if options.all => {
+                term::warning(&format!("Skipping project {}: {}", urn, err));
+            }
+            Err(err) => return Err(err),
+        }
+    }
+
+    if hits.is_empty() {
+        term::print(term::format::italic("No results."));
+        return Ok(());
+    }
+    // Most recent first.
+    hits.sort_by_key(|(_, h)| std::cmp::Reverse(h.timestamp));
+
+    for (project, mut hit) in hits {
+        hit.author.resolve(&storage).ok();
+
+        let mut header = vec![
+            term::format::badge_secondary(hit.kind),
+            term::format::highlight(common::fmt::cob(&hit.id)),
+        ];
+        if let Some(project) = project {
+            header.push(term::format::bold(project));
+        }
+        header.push(term::format::tertiary(hit.author.name(&petnames)));
+        header.push(term::format::dim(format!("({})", hit.field)));
+        header.push(term::format::dim(hit.timestamp));
+
+        term::info!("{}", header.join(" "));
+        term::indented(&format!(
+            "{}{}{}",
+            term::format::dim(&hit.snippet.before),
+            term::format::yellow(term::format::bold(&hit.snippet.matched)),
+            term::format::dim(&hit.snippet.after),
+        ));
+        term::blank();
+    }
+
+    Ok(())
+}
+
+/// Search the issues and patches of a project.
+fn search_project(cobs: &cobs::Store, urn: &Urn, query: &str) -> anyhow::Result<Vec<search::Hit>> {
+    let mut hits = Vec::new();
+
+    for (id, issue) in cobs.all::<Issue>(urn)? {
+        hits.extend(search::issue(&id, &issue, query));
+    }
+    for (id, patch) in cobs.all::<Patch>(urn)? {
+        hits.extend(search::patch(&id, &patch, query));
+    }
+    Ok(hits)
+}
@@ -0,0 +1,326 @@
+use std::convert::TryFrom;
+use std::ffi::OsString;
+use std::str::FromStr;
+use std::time::Instant;
+
+use anyhow::anyhow;
+
+use radicle_common::args::{Args, Error, Help};
+use radicle_common::config::{Config, Scope, SeedConfig, Status};
+use radicle_common::profile;
+use radicle_common::seed::{Address, SeedApiClient};
+use radicle_common::sync::Seed;
+use radicle_terminal as term;
+
+pub const HELP: Help = Help {
+    name: "seed",
+    description: env!("CARGO_PKG_DESCRIPTION"),
+    version: env!("CARGO_PKG_VERSION"),
+    usage: r#"
+Usage
+
+    rad seed add <addr> [--name <name>] [--local] [<option>...]
+    rad seed rm <name | peer-id | host> [--local] [<option>...]
+    rad seed ls [--resolved] [<option>...]
+    rad seed ping [<name | peer-id | host>] [<option>...]
+
+    Manages the seeds used by `rad sync` and other commands that
+    interact with the network.
+
+    Seeds are added to the profile configuration by default. With
+    `--local`, they are added to the `Radicle.toml` file of the current
+    directory instead, which takes precedence over the profile.
+
+    Seed addresses have the format <peer-id>@<host>:<port>, or
+    rad://<peer-id>@<host>:<port>.
+
+    Seeds are resolved from the first configuration that has any, in
+    order: the working copy (`Radicle.toml`), the profile (`config.toml`),
+    the `lnk` seeds file, and the built-in defaults. `rad seed ls` shows
+    the seeds in use, along with the configuration they come from;
+    `--resolved` also shows how each configuration was considered.
+
+    Seeds of the `lnk` seeds file are imported into the profile
+    configuration once, alongside the seeds it already has, the first
+    time a seed is added to or removed from the profile. From then on,
+    the `lnk` seeds file is no longer used.
+
+    `rad seed ping` queries the HTTP API of each configured seed, and
+    checks that the seed's peer id matches the configured one.
+
+Options
+
+    --name <name>    Name of the seed being added
+    --local          Use the local configuration, ie. `Radicle.toml`
+    --resolved       Show how the seed configuration was resolved
+    --help           Print help
+"#,
+};
+
+#[derive(Debug)]
+pub enum Operation {
+    Add {
+        seed: Seed<String>,
+        name: Option<String>,
+    },
+    Remove {
+        query: String,
+    },
+    List {
+        resolved: bool,
+    },
+    Ping {
+        query: Option<String>,
+    },
+}
+
+#[derive(Debug)]
+pub struct Options {
+    pub op: Operation,
+    pub scope: Scope,
+}
+
+impl Args for Options {
+    fn from_args(args: Vec<OsString>) -> anyhow::Result<(Self, Vec<OsString>)> {
+        use lexopt::prelude::*;
+
+        let mut parser = lexopt::Parser::from_args(args);
+        let mut op: Option<String> = None;
+        let mut value: Option<String> = None;
+        let mut name: Option<String> = None;
+        let mut scope = Scope::Profile;
+        let mut resolved = false;
+
+        while let Some(arg) = parser.next()? {
+            match arg {
+                Long("name") => {
+                    name = Some(parser.value()?.to_string_lossy().into_owned());
+                }
+                Long("resolved") => {
+                    resolved = true;
+                }
+                Long("local") => {
+                    scope = Scope::Local;
+                }
+                Long("help") => {
+                    return Err(Error::Help.into());
+                }
+                Value(val) if op.is_none() => match val.to_string_lossy().as_ref() {
+                    "add" | "rm" | "ls" | "ping" => op = Some(val.to_string_lossy().into_owned()),
+                    unknown => anyhow::bail!("unknown operation '{}'", unknown),
+                },
+                Value(val) if value.is_none() && op.as_deref() != Some("ls") => {
+                    let val = val
+                        .to_str()
+                        .ok_or_else(|| anyhow!("value specified is not UTF-8"))?;
+
+                    value = Some(val.to_owned());
+                }
+                _ => return Err(anyhow!(arg.unexpected())),
+            }
+        }
+
+        if name.is_some() && op.as_deref() != Some("add") {
+            anyhow::bail!("`--name` can only be used when adding a seed");
+        }
+        if resolved && op.as_deref() != Some("ls") {
+            anyhow::bail!("`--resolved` can only be used when listing seeds");
+        }
+        let op = match op.as_deref() {
+            Some("add") => {
+                let addr = value.ok_or_else(|| anyhow!("a seed address must be specified"))?;
+                let seed = parse_seed(&addr).map_err(|_| Error::WithHint {
+                    err: anyhow!("invalid seed address specified: '{}'", addr),
+                    hint: "hint: valid seed addresses have the format <peer-id>@<addr>, eg. hyb5to4rshftx4apgmu9s6wnsp4ddmp1mz6ijh4qqey7fb8wrpawxa@pine.radicle.garden:8776",
+                })?;
+
+                Operation::Add { seed, name }
+            }
+            Some("rm") => Operation::Remove {
+                query: value
+                    .ok_or_else(|| anyhow!("a seed name, peer id or host must be specified"))?,
+            },
+            Some("ls") => Operation::List { resolved },
+            Some("ping") => Operation::Ping { query: value },
+            _ => anyhow::bail!("an operation must be specified, see `rad seed --help`"),
+        };
+
+        Ok((Options { op, scope }, vec![]))
+    }
+}
+
+pub fn run(options: Options, ctx: impl term::Context) -> anyhow::Result<()> {
+    let profile = ctx.profile()?;
+    let writes = matches!(options.op, Operation::Add { .. } | Operation::Remove { .. });
+
+    // The `lnk` seeds are imported before the profile configuration is first modified.
+    if writes && options.scope == Scope::Profile {
+        if let Some(n) = Config::migrate(&profile)? {
+            term::info!(
+                "Imported {} seed(s) from {} into {}",
+                n,
+                term::format::dim(profile.paths().seeds_file().display()),
+                term::format::dim(Config::path(&profile).display())
+            );
+        }
+    }
+
+    match options.op {
+        Operation::Add { seed, name } => add(seed, name, options.scope, &profile)?,
+        Operation::Remove { query } => remove(&query, options.scope, &profile)?,
+        Operation::List { resolved } => list(resolved, &profile)?,
+        Operation::Ping { query } => ping(query.as_deref(), &profile)?,
+    }
+
+    Ok(())
+}
+
+/// Parse a seed address, either as `<peer-id>@<addr>` or as a `rad://` URL.
+fn parse_seed(s: &str) -> anyhow::Result<Seed<String>> {
+    if s.starts_with("rad://") {
+        let addr = Address::from_str(s)?;
+        return Seed::try_from(addr);
+    }
+    Seed::from_str(s).map_err(|_| anyhow!("invalid seed address '{}'", s))
+}
+
+fn add(
+    seed: Seed<String>,
+    name: Option<String>,
+    scope: Scope,
+    profile: &profile::Profile,
+) -> anyhow::Result<()> {
+    let path = scope.path(profile);
+    let mut config = Config::scope(scope, profile)?;
+    let seed = SeedConfig::new(&seed, name)?;
+
+    let name = seed
+        .name
+        .clone()
+        .unwrap_or_else(|| seed.p2p.as_str().to_owned());
+
+    config.add_seed(seed);
+    config.write(&path)?;
+
+    term::success!(
+        "Added seed {} to {}",
+        term::format::highlight(name),
+        term::format::dim(path.display())
+    );
+
+    Ok(())
+}
+
+fn remove(query: &str, scope: Scope, profile: &profile::Profile) -> anyhow::Result<()> {
+    let path = scope.path(profile);
+    let mut config = Config::scope(scope, profile)?;
+
+    match config.remove_seed(query) {
+        0 => anyhow::bail!("no seed matching '{}' in {}", query, path.display()),
+        n => {
+            config.write(&path)?;
+            term::success!(
+                "Removed {} seed(s) from {}",
+                n,
+                term::format::dim(path.display())
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn list(resolved: bool, profile: &profile::Profile) -> anyhow::Result<()> {
+    let resolution = Config::resolve(profile);
+
+    if resolved {
+        let mut table = term::Table::default();
+
+        for (source, status) in &resolution.sources {
+            let status = match status {
+                Status::Used => term::format::positive(status),
+                Status::Invalid(_) => term::format::negative(status),
+                _ => term::format::dim(status),
+            };
+            table.push([term::format::highlight(source), status]);
+        }
+        table.render();
+        term::blank();
+    }
+
+    let mut table = term::Table::default();
+    for seed in resolution.config.seeds() {
+        table.push([
+            term::format::bold(seed.name.clone().unwrap_or_default()),
+            term::format::tertiary(seed.peer().map(|p| p.to_string()).unwrap_or_default()),
+            term::format::dim(seed.p2p.host_str().unwrap_or_default()),
+            term::format::dim(&resolution.source),
+        ]);
+    }
+
+    if table.is_empty() {
+        term::print(term::format::italic("No seeds configured."));
+    } else {
+        table.render();
+    }
+
+    Ok(())
+}
+
+fn ping(query: Option<&str>, profile: &profile::Profile) -> anyhow::Result<()> {
+    let config = Config::resolve(profile).config;
+    let seeds = config
+        .seeds()
+        .filter(|s| query.map_or(true, |q| s.matches(q)))
+        .collect::<Vec<_>>();
+
+    if seeds.is_empty() {
+        match query {
+            Some(q) => anyhow::bail!("no configured seed matches '{}'", q),
+            None => anyhow::bail!("no seeds configured, see `rad seed add --help`"),
+        }
+    }
+
+    let mut failed = 0;
+    for seed in seeds {
+        let name = seed
+            .name
+            .clone()
+            .unwrap_or_else(|| seed.api.host_str().unwrap_or_default().to_owned());
+        let spinner = term::spinner(format!("Pinging {}...", term::format::highlight(&name)));
+        let start = Instant::now();
+
+        match SeedApiClient::from_config(seed).peer_id() {
+            Ok(id) if Some(id) == seed.peer() || seed.peer().is_none() => {
+                spinner.clear();
+                term::success!(
+                    "{} {} {}",
+                    term::format::highlight(&name),
+                    term::format::tertiary(id),
+                    term::format::dim(format!("({}ms)", start.elapsed().as_millis()))
+                );
+            }
+            Ok(id) => {
+                spinner.clear();
+                term::warning(&format!(
+                    "{} returned peer id {}, but {} is configured",
+                    name,
+                    id,
+                    seed.peer().map(|p| p.to_string()).unwrap_or_default()
+                ));
+                failed += 1;
+            }
+            Err(err) => {
+                spinner.clear();
+                term::error(format!("{} is unreachable: {}", name, err));
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        anyhow::bail!("{} seed(s) failed to respond as expected", failed);
+    }
+
+    Ok(())
+}
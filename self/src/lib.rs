@@ -1,9 +1,14 @@
+use std::collections::HashMap;
 use std::ffi::OsString;
+use std::str::FromStr;
 
 use anyhow::anyhow;
 
+use librad::git::tracking;
+use librad::PeerId;
+
 use radicle_common::args::{Args, Error, Help};
-use radicle_common::{keys, person, profile};
+use radicle_common::{cobs, keys, person, profile, project, sync, tokio, Urn};
 use radicle_terminal as term;
 
 pub const HELP: Help = Help {
@@ -14,6 +19,26 @@ pub const HELP: Help = Help {
 Usage
 
     rad self [<option>...]
+    rad self device ls
+    rad self device add <peer-id>
+    rad self device rm <peer-id>
+    rad self device accept <urn> <peer-id>
+
+    Your personal identity can be shared by several devices, eg. laptops,
+    so that they are known as the same person. Each device has its own
+    peer id, see `rad self --peer`.
+
+    To add a device, run `rad self device add <peer-id>` with the peer id
+    of the new device, and sync your identity with `rad sync --self`. The
+    update is signed by the current device, but must also be signed by
+    the new device: on the new device, run `rad self device accept <urn>
+    <peer-id>` with your identity's URN and the peer id of the current
+    device, and sync again. `rad self device ls` shows the devices of
+    your identity, and which of them have yet to sign the latest update.
+
+    Removing a device with `rad self device rm <peer-id>` works the same
+    way: the update must be signed by a majority of the devices before
+    and after the removal, which can include the removed device.
 
 Options
 
@@ -21,6 +46,7 @@ Options
     --urn        Show URN
     --peer       Show Peer ID
     --profile    Show Profile ID
+    --activity   Show your activity across projects, most recent first
     --help       Show help
 "#,
 };
@@ -31,12 +57,22 @@ enum Show {
     Urn,
     Peer,
     Profile,
+    Activity,
     All,
 }
 
+#[derive(Debug)]
+enum Device {
+    List,
+    Add { peer: PeerId },
+    Remove { peer: PeerId },
+    Accept { urn: Urn, peer: PeerId },
+}
+
 #[derive(Debug)]
 pub struct Options {
     show: Show,
+    device: Option<Device>,
 }
 
 impl Args for Options {
@@ -45,9 +81,17 @@ impl Args for Options {
 
         let mut parser = lexopt::Parser::from_args(args);
         let mut show: Option<Show> = None;
+        let mut device = false;
+        let mut values: Vec<String> = Vec::new();
 
         while let Some(arg) = parser.next()? {
             match arg {
+                Value(val) if !device && show.is_none() && val == "device" => {
+                    device = true;
+                }
+                Value(val) if device => {
+                    values.push(val.to_string_lossy().into_owned());
+                }
                 Long("name") if show.is_none() => {
                     show = Some(Show::Name);
                 }
@@ -60,6 +104,9 @@ impl Args for Options {
                 Long("profile") if show.is_none() => {
                     show = Some(Show::Profile);
                 }
+                Long("activity") if show.is_none() => {
+                    show = Some(Show::Activity);
+                }
                 Long("help") => {
                     return Err(Error::Help.into());
                 }
@@ -67,9 +114,41 @@ impl Args for Options {
             }
         }
 
+        let device = if device {
+            let peer = |v: Option<&String>| -> anyhow::Result<PeerId> {
+                let v = v.ok_or_else(|| anyhow!("a <peer-id> must be specified"))?;
+                PeerId::from_str(v).map_err(|_| anyhow!("invalid <peer-id> '{}'", v))
+            };
+            let op = match values.first().map(|s| s.as_str()) {
+                Some("ls") | None => Device::List,
+                Some("add") => Device::Add {
+                    peer: peer(values.get(1))?,
+                },
+                Some("rm") => Device::Remove {
+                    peer: peer(values.get(1))?,
+                },
+                Some("accept") => {
+                    let urn = values
+                        .get(1)
+                        .ok_or_else(|| anyhow!("a <urn> must be specified"))?;
+                    let urn = Urn::from_str(urn).map_err(|_| anyhow!("invalid <urn> '{}'", urn))?;
+
+                    Device::Accept {
+                        urn,
+                        peer: peer(values.get(2))?,
+                    }
+                }
+                Some(unknown) => anyhow::bail!("unknown device operation '{}'", unknown),
+            };
+            Some(op)
+        } else {
+            None
+        };
+
         Ok((
             Options {
                 show: show.unwrap_or(Show::All),
+                device,
             },
             vec![],
         ))
@@ -78,6 +157,10 @@ impl Args for Options {
 
 pub fn run(options: Options, ctx: impl term::Context) -> anyhow::Result<()> {
     let profile = ctx.profile()?;
+
+    if let Some(device) = options.device {
+        return self::device(device, &profile);
+    }
     let storage = profile::read_only(&profile)?;
 
     match options.show {
@@ -102,6 +185,7 @@ pub fn run(options: Options, ctx: impl term::Context) -> anyhow::Result<()> {
                     .ok_or_else(|| anyhow!("no user found"))?,
             );
         }
+        Show::Activity => activity(&profile)?,
         Show::All => all(&profile)?,
     }
 
@@ -158,3 +242,149 @@ fn all(profile: &profile::Profile) -> anyhow::Result<()> {
 
     Ok(())
 }
+
+fn device(op: Device, profile: &profile::Profile) -> anyhow::Result<()> {
+    let signer = term::signer(profile)?;
+    let storage = keys::storage(profile, signer.clone())?;
+
+    match op {
+        Device::List => {}
+        Device::Add { peer } => {
+            person::add_device(&storage, peer)?;
+            term::success!(
+                "Device {} added to your identity",
+                term::format::tertiary(peer)
+            );
+        }
+        Device::Remove { peer } => {
+            person::remove_device(&storage, &peer)?;
+            term::success!(
+                "Device {} removed from your identity",
+                term::format::tertiary(peer)
+            );
+        }
+        Device::Accept { urn, peer } => {
+            tracking::track(
+                &storage,
+                &urn,
+                Some(peer),
+                tracking::config::Config::default(),
+                tracking::policy::Track::Any,
+            )??;
+
+            let rt = tokio::runtime::Runtime::new()?;
+            term::sync::sync(
+                urn.clone(),
+                sync::seeds(profile)?,
+                sync::Mode::Fetch,
+                profile,
+                signer,
+                &rt,
+            )?;
+            let person = person::accept_device(&storage, &urn, peer)?;
+
+            if person::devices(&person).contains(storage.peer_id()) {
+                term::success!(
+                    "This device is now part of identity {} {}",
+                    term::format::highlight(person.subject().name.to_string()),
+                    term::format::dim(&urn)
+                );
+            } else {
+                term::success!(
+                    "Signed the removal of this device from identity {} {}",
+                    term::format::highlight(person.subject().name.to_string()),
+                    term::format::dim(&urn)
+                );
+            }
+        }
+    }
+
+    let id = person::local(&storage)?;
+    let latest = person::get(&storage, &id.urn())?;
+    let latest = latest.as_ref().map_or(&*id, |p| p);
+    let pending = person::pending(&storage, latest)?;
+    let devices = person::devices(latest);
+
+    let mut table = term::Table::default();
+    for peer in devices.iter().copied() {
+        let mut name = term::format::tertiary(peer);
+        if &peer == storage.peer_id() {
+            name = format!("{} {}", name, term::format::badge_secondary("this device"));
+        }
+        let status = if pending.contains(&peer) {
+            term::format::yellow("signature pending")
+        } else {
+            term::format::positive("signed")
+        };
+        table.push([name, status]);
+    }
+    // Devices removed by the latest update may still have to sign it.
+    for peer in pending.iter().filter(|p| !devices.contains(p)) {
+        table.push([
+            term::format::tertiary(peer),
+            term::format::yellow("signature pending, removed"),
+        ]);
+    }
+    term::blank();
+    table.render();
+
+    if !pending.is_empty() {
+        term::blank();
+        term::info!(
+            "{} The latest update of your identity has yet to be signed by {} device(s).",
+            term::format::yellow("!!"),
+            pending.len()
+        );
+        term::info!(
+            "   On each pending device, run {} and sync with {}.",
+            term::format::secondary(format!(
+                "`rad self device accept {} {}`",
+                id.urn(),
+                storage.peer_id()
+            )),
+            term::format::secondary("`rad sync --self`")
+        );
+    }
+
+    Ok(())
+}
+
+fn activity(profile: &profile::Profile) -> anyhow::Result<()> {
+    let signer = term::signer(profile)?;
+    let storage = keys::storage(profile, signer)?;
+    let cobs = cobs::store(profile, &storage)?;
+
+    let mut activity = match cobs.users().local()? {
+        Some(user) => user.activity,
+        None => vec![],
+    };
+    if activity.is_empty() {
+        term::print(term::format::italic("No activity."));
+        return Ok(());
+    }
+    // Most recent first.
+    activity.sort_by_key(|a| std::cmp::Reverse(a.timestamp));
+
+    let mut names = HashMap::new();
+    let mut table = term::Table::default();
+
+    for a in activity {
+        let urn = a.event.project().clone();
+        let name = names.entry(urn.clone()).or_insert_with(|| {
+            project::get(&storage, &urn)
+                .ok()
+                .flatten()
+                .map(|meta| meta.name)
+                .unwrap_or_else(|| urn.encode_id())
+        });
+
+        table.push([
+            term::format::dim(a.timestamp),
+            term::format::bold(name.as_str()),
+            a.event.to_string(),
+        ]);
+    }
+    table.render();
+
+    Ok(())
+}
@@ -0,0 +1,275 @@
+use std::ffi::OsString;
+use std::str::FromStr;
+
+use anyhow::anyhow;
+
+use librad::git::Storage;
+use librad::PeerId;
+
+use radicle_common as common;
+use radicle_common::args::{Args, Error, Help};
+use radicle_common::cobs::issue::{self, Issue, IssueId};
+use radicle_common::cobs::patch;
+use radicle_common::cobs::{Identifier, Store};
+use radicle_common::petname::Petnames;
+use radicle_common::{cobs, git, keys, project, Urn};
+use radicle_terminal as term;
+
+pub const HELP: Help = Help {
+    name: "show",
+    description: env!("CARGO_PKG_DESCRIPTION"),
+    version: env!("CARGO_PKG_VERSION"),
+    usage: r#"
+Usage
+
+    rad show <id> [<option>...]
+
+    Shows the object identified by <id>, which can be:
+
+    * A project or person URN, eg. `rad:git:hnrk…`
+    * A Peer ID
+    * An issue or patch id, or a prefix of one. Issues and patches
+      are looked up in the current project.
+
+Options
+
+    --help   Print help
+"#,
+};
+
+#[derive(Debug)]
+pub struct Options {
+    pub id: String,
+}
+
+impl Args for Options {
+    fn from_args(args: Vec<OsString>) -> anyhow::Result<(Self, Vec<OsString>)> {
+        use lexopt::prelude::*;
+
+        let mut parser = lexopt::Parser::from_args(args);
+        let mut id: Option<String> = None;
+
+        while let Some(arg) = parser.next()? {
+            match arg {
+                Long("help") => {
+                    return Err(Error::Help.into());
+                }
+                Value(val) if id.is_none() => {
+                    let val = val
+                        .to_str()
+                        .ok_or_else(|| anyhow!("id specified is not UTF-8"))?;
+
+                    id = Some(val.to_owned());
+                }
+                _ => return Err(anyhow!(arg.unexpected())),
+            }
+        }
+
+        Ok((
+            Options {
+                id: id.ok_or_else(|| anyhow!("an id must be provided"))?,
+            },
+            vec![],
+        ))
+    }
+}
+
+pub fn run(options: Options, ctx: impl term::Context) -> anyhow::Result<()> {
+    let profile = ctx.profile()?;
+    let signer = term::signer(&profile)?;
+    let storage = keys::storage(&profile, signer)?;
+    let petnames = Petnames::open(&profile)?;
+
+    if let Ok(urn) = Urn::from_str(&options.id) {
+        return show_identity(&urn, &storage);
+    }
+    if let Ok(peer) = PeerId::from_str(&options.id) {
+        return show_peer(&peer, &storage, &petnames);
+    }
+
+    let (urn, repo) = project::cwd()
+        .map_err(|_| anyhow!("issues and patches can only be shown in the context of a project"))?;
+    let cobs = cobs::store(&profile, &storage)?;
+    let identifier = Identifier::from_str(&options.id)?;
+
+    match cobs.resolve_any(&urn, &identifier)? {
+        Some((typename, id)) if typename == *issue::TYPENAME => {
+            show_issue(&cobs, &urn, &id, &storage, &petnames)?;
+        }
+        Some((typename, id)) if typename == *patch::TYPENAME => {
+            let mut patch = cobs
+                .patches()
+                .get(&urn, &id)?
+                .ok_or_else(|| anyhow!("couldn't load patch {}", id))?;
+            let project = project::get(&storage, &urn)?
+                .ok_or_else(|| anyhow!("couldn't load project {} from local state", urn))?;
+            let monorepo = git::Repository::open_bare(profile.paths().git_dir())?;
+
+            rad_patch::print(
+                &cobs.whoami,
+                &id,
+                &mut patch,
+                &project,
+                &monorepo,
+                &Some(repo),
+                &storage,
+                &petnames,
+            )?;
+            term::blank();
+            term::markdown(patch.description());
+        }
+        Some((typename, id)) => {
+            anyhow::bail!(
+                "objects of type `{}` cannot be shown, found {}",
+                typename,
+                id
+            );
+        }
+        None => {
+            anyhow::bail!("nothing found for `{}`", options.id);
+        }
+    }
+
+    Ok(())
+}
+
+fn show_identity(urn: &Urn, storage: &Storage) -> anyhow::Result<()> {
+    if let Some(proj) = project::get(storage, urn)? {
+        let mut table = term::Table::default();
+
+        table.push([String::from("Name"), term::format::bold(&proj.name)]);
+        table.push([String::from("URN"), term::format::highlight(&proj.urn)]);
+        table.push([
+            String::from("Description"),
+            term::format::italic(&proj.description),
+        ]);
+        table.push([
+            String::from("Default branch"),
+            term::format::tertiary(&proj.default_branch),
+        ]);
+        for delegate in &proj.delegates {
+            table.push([String::from("Delegate"), term::format::tertiary(delegate)]);
+        }
+        table.render_tree();
+
+        return Ok(());
+    }
+    if let Some(person) = project::PeerIdentity::get(urn, storage)? {
+        let mut table = term::Table::default();
+
+        table.push([String::from("Name"), term::format::bold(&person.name)]);
+        table.push([String::from("URN"), term::format::highlight(&person.urn)]);
+        if let Some(ens) = &person.ens {
+            table.push([String::from("ENS"), term::format::tertiary(&ens.name)]);
+        }
+        table.render_tree();
+
+        return Ok(());
+    }
+    anyhow::bail!("identity {} was not found", urn);
+}
+
+fn show_peer(peer: &PeerId, storage: &Storage, petnames: &Petnames) -> anyhow::Result<()> {
+    let mut table = term::Table::default();
+
+    table.push([String::from("Peer ID"), term::format::highlight(peer)]);
+
+    if let Some(name) = petnames.get(peer) {
+        table.push([String::from("Petname"), term::format::bold(name)]);
+    }
+
+    // Peers are only known by name in the context of a project.
+    if let Ok((urn, _)) = project::cwd() {
+        if let Some(proj) = project::get(storage, &urn)? {
+            let info = project::PeerInfo::get(peer, &proj, storage);
+
+            if let Some(person) = &info.person {
+                table.push([String::from("Name"), term::format::bold(&person.name)]);
+                table.push([String::from("URN"), term::format::tertiary(&person.urn)]);
+            }
+            table.push([
+                String::from("Delegate"),
+                term::format::tertiary(if info.delegate { "yes" } else { "no" }),
+            ]);
+        }
+    }
+    if peer == storage.peer_id() {
+        table.push([String::from("Device"), term::format::secondary("(you)")]);
+    }
+    table.push([
+        String::from("Key (hash)"),
+        term::format::tertiary(keys::to_ssh_fingerprint(peer)?),
+    ]);
+    table.render_tree();
+
+    Ok(())
+}
+
+fn show_issue(
+    cobs: &Store,
+    project: &Urn,
+    id: &IssueId,
+    storage: &Storage,
+    petnames: &Petnames,
+) -> anyhow::Result<()> {
+    let mut issue: Issue = cobs
+        .issues()
+        .get(project, id)?
+        .ok_or_else(|| anyhow!("couldn't load issue {}", id))?;
+    issue.resolve(storage).ok();
+
+    let state = match issue.state() {
+        issue::State::Open => term::format::badge_positive("open"),
+        issue::State::Closed { .. } => term::format::badge_negative("closed"),
+    };
+    term::info!(
+        "{} {} {}",
+        term::format::bold(issue.title()),
+        term::format::highlight(common::fmt::cob(id)),
+        state,
+    );
+
+    let mut info = vec![format!(
+        "└─ * opened by {}",
+        term::format::tertiary(issue.author().name(petnames))
+    )];
+    if issue.author().urn() == &cobs.whoami.urn() {
+        info.push(term::format::secondary("(you)"));
+    }
+    info.push(term::format::dim(issue.timestamp()));
+    term::info!("{}", info.join(" "));
+
+    if !issue.labels().is_empty() {
+        let mut labels = issue
+            .labels()
+            .iter()
+            .map(|l| l.name().to_owned())
+            .collect::<Vec<_>>();
+        labels.sort();
+
+        term::info!("   {}", term::format::yellow(labels.join(", ")));
+    }
+    term::blank();
+    term::markdown(issue.description());
+
+    for comment in issue.comments() {
+        term::blank();
+        term::info!(
+            "{} {}",
+            term::format::tertiary(comment.author.name(petnames)),
+            term::format::dim(comment.timestamp)
+        );
+        term::markdown(&comment.body);
+
+        for reply in &comment.replies {
+            term::info!(
+                "   └─ {} {}",
+                term::format::tertiary(reply.author.name(petnames)),
+                term::format::dim(reply.timestamp)
+            );
+            term::indented(&reply.body);
+        }
+    }
+
+    Ok(())
+}
@@ -3,6 +3,10 @@ use std::convert::TryInto;
 use std::ffi::OsString;
 use std::iter;
 use std::str::FromStr;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time;
+use std::time::Instant;
 
 use librad::git::Storage;
 use librad::git::Urn;
@@ -10,9 +14,12 @@ use librad::profile::Profile;
 
 use radicle_common::args;
 use radicle_common::args::{Args, Error, Help};
+use radicle_common::cobs::inbox::Inbox;
 use radicle_common::nonempty::NonEmpty;
+use radicle_common::sync::status::RefStatus;
+use radicle_common::sync::watch::{self, Event, Watcher};
 use radicle_common::sync::Mode;
-use radicle_common::{identity, keys, person, project, sync, tokio};
+use radicle_common::{cobs, config, git, identity, keys, logger, person, project, sync, tokio};
 use radicle_terminal as term;
 
 use anyhow::anyhow;
@@ -28,16 +35,64 @@ Usage
 
     rad sync [<urn> | <url>] [--seed <address>]... [<options>...]
     rad sync --self [--seed <address>]...
+    rad sync --status [<urn>] [--seed <address>]...
+    rad sync --all [--seed <address>]... [<options>...]
+    rad sync --watch [<urn>] [--all] [--seed <address>]... [<options>...]
+    rad sync --flush
 
     If a <urn> is specified, seeds may be given via the `--seed` option.
     If a <url> is specified, the seed is implied.
     If neither is specified, the URN and seed of the current project is used.
     If the project has no configured seed, the active profile's default seed list is used.
 
+    With `--all`, every project in local storage is synced, as well as
+    your local identity. Failures don't stop the other projects from
+    being synced, and a summary is printed at the end.
+
+    With `--watch`, sync runs until interrupted: local branch and COB
+    refs are watched, and pushed to seeds shortly after they change.
+    Projects are also fetched every few minutes. By default, the current
+    project is watched; with `--all`, all local projects are.
+
+    With `--status`, nothing is synced: instead, every local branch and
+    COB ref is compared with the seed's copy, and shown as up-to-date,
+    ahead, behind or diverged. The seed's refs are listed from its git
+    URL. When the seed has a commit that wasn't fetched yet, the ref is
+    shown as unknown, since it could be either behind or diverged.
+
+    Projects are fetched over the link protocol, falling back to the
+    seed's git HTTPS endpoint for seeds that can't be reached. With
+    `--transport`, either transport can be forced. Pushes always use the
+    link protocol.
+
+    Each project's tracking policy is applied first, from the working
+    copy's `Radicle.toml`, or the one committed to the project's default
+    branch: delegates and contributors are tracked if the policy asks for
+    it, and blocked peers are untracked and not fetched. See
+    `rad track --policy`.
+
+    Pushes that fail, eg. because a seed is unreachable, are queued and
+    retried with exponential backoff on later invocations of `rad clone`,
+    `rad pull`, `rad push` and `rad track` from a terminal. With `--flush`,
+    all queued pushes are retried right away.
+
 Options
 
     --seed <address>    Sync to the given seed (may be specified multiple times)
     --self              Sync your local identity only
+    --all               Sync all local projects and your local identity
+    --watch             Keep running, and sync whenever local refs change
+    --fetch-only        Only fetch from seeds
+    --push-only         Only push to seeds
+    --status            Show how local refs compare to the seeds, without syncing
+    --flush             Retry all queued pushes
+    --parallel <n>      Number of seeds to sync with at the same time (default: 4)
+    --quorum <n>        With `--push-only`, stop once <n> seeds have acknowledged
+                        the push by updating refs; the other seeds are skipped
+    --timeout <secs>    Time allowed for each fetch or push, for seeds without a
+                        configured `timeout` (default: 9)
+    --transport <t>     Transport to fetch with: `auto`, `link` or `https` (default: auto)
+    --verbose, -v       Report the outcome of successful syncs too
     --help              Print help
 
 Seed addresses
@@ -57,6 +112,11 @@ pub struct Options {
     pub mode: Mode,
     pub verbose: bool,
     pub sync_self: bool,
+    pub status: bool,
+    pub flush: bool,
+    pub all: bool,
+    pub watch: bool,
+    pub sync: sync::Options,
 }
 
 impl Args for Options {
@@ -67,8 +127,14 @@ impl Args for Options {
         let mut verbose = false;
         let mut origin = None;
         let mut sync_self = false;
+        let mut status = false;
+        let mut flush = false;
+        let mut all = false;
+        let mut watch = false;
+        let mut mode = Mode::default();
         let mut unparsed = Vec::new();
         let mut seeds = Vec::new();
+        let mut opts = sync::Options::default();
 
         while let Some(arg) = parser.next()? {
             match arg {
@@ -81,6 +147,40 @@ impl Args for Options {
                 Long("self") => {
                     sync_self = true;
                 }
+                Long("status") => {
+                    status = true;
+                }
+                Long("flush") => {
+                    flush = true;
+                }
+                Long("all") => {
+                    all = true;
+                }
+                Long("watch") => {
+                    watch = true;
+                }
+                Long("fetch-only") => {
+                    mode = Mode::Fetch;
+                }
+                Long("push-only") => {
+                    mode = Mode::Push;
+                }
+                Long("parallel") => {
+                    opts.parallelism = number(&parser.value()?, "parallel")?;
+                }
+                Long("quorum") => {
+                    opts.quorum = Some(number(&parser.value()?, "quorum")?);
+                }
+                Long("timeout") => {
+                    let secs = number(&parser.value()?, "timeout")?;
+                    opts.timeout = time::Duration::from_secs(secs as u64);
+                }
+                Long("transport") => {
+                    let value = parser.value()?;
+                    let value = value.to_string_lossy();
+
+                    opts.transport = value.parse().map_err(|e: String| anyhow!(e))?;
+                }
                 Long("seed") => {
                     let value = parser.value()?;
                     let value = value.to_string_lossy();
@@ -121,24 +221,82 @@ impl Args for Options {
             );
         }
 
+        if status && sync_self {
+            anyhow::bail!("`--status` cannot be used with `--self`");
+        }
+        if flush && (status || sync_self || all || origin.is_some() || !seeds.is_empty()) {
+            anyhow::bail!("`--flush` cannot be combined with other sync targets");
+        }
+        if all && (status || sync_self || origin.is_some()) {
+            anyhow::bail!("`--all` cannot be combined with other sync targets");
+        }
+        if watch && (status || sync_self || flush) {
+            anyhow::bail!("`--watch` cannot be used with `--status`, `--self` or `--flush`");
+        }
+
         Ok((
             Options {
                 origin,
                 seeds,
-                mode: Mode::default(),
+                mode,
                 sync_self,
+                status,
+                flush,
+                all,
+                watch,
                 verbose,
+                sync: opts,
             },
             unparsed,
         ))
     }
 }
 
+/// Parse a strictly positive number option.
+fn number(value: &OsString, option: &str) -> anyhow::Result<usize> {
+    match value.to_str().map(usize::from_str) {
+        Some(Ok(n)) if n > 0 => Ok(n),
+        _ => Err(anyhow!(
+            "invalid value for `--{}`: '{}'",
+            option,
+            value.to_string_lossy()
+        )),
+    }
+}
+
 pub fn run(options: Options, ctx: impl term::Context) -> anyhow::Result<()> {
     let profile = ctx.profile()?;
     let signer = term::signer(&profile)?;
     let storage = keys::storage(&profile, signer)?;
     let rt = tokio::runtime::Runtime::new()?;
+
+    if options.flush {
+        return flush(&profile, rt);
+    }
+    if options.all {
+        // Seeds given on the command line are used for all projects, otherwise each
+        // project is synced with its own seeds.
+        let seeds = options.seeds.clone().try_into().ok();
+        let mut options = options;
+        options.sync.timeouts = sync::seed_timeouts(&profile);
+
+        if options.watch {
+            let targets = project::list(&storage)?
+                .into_iter()
+                .map(|(urn, _, _)| {
+                    let seeds = match &seeds {
+                        Some(seeds) => seeds.clone(),
+                        None => sync::project_seeds(&profile, &storage, &urn)?,
+                    };
+                    Ok((urn, seeds))
+                })
+                .collect::<anyhow::Result<_>>()?;
+
+            return watch(&profile, targets, options, rt);
+        }
+        return sync_all(&profile, seeds, storage, options, rt);
+    }
+
     let urn = if let Some(origin) = &options.origin {
         origin.urn.clone()
     } else {
@@ -149,17 +307,316 @@ pub fn run(options: Options, ctx: impl term::Context) -> anyhow::Result<()> {
         NonEmpty::new(seed)
     } else if let Ok(seeds) = options.seeds.clone().try_into() {
         seeds
+    } else if options.status {
+        // Use the seed configuration directly, since it has the seeds' git URLs.
+        let seeds = config::Config::resolve(&profile).config.seed;
+
+        return status(&urn, &profile, &storage, seeds);
     } else {
         sync::seeds(&profile)?
     };
 
-    if options.sync_self {
+    if options.status {
+        let seeds = seeds
+            .iter()
+            .map(|s| config::SeedConfig::new(s, s.label.clone()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        return status(&urn, &profile, &storage, seeds);
+    }
+
+    let mut options = options;
+    options.sync.timeouts = sync::seed_timeouts(&profile);
+
+    if options.watch {
+        watch(&profile, vec![(urn, seeds)], options, rt)
+    } else if options.sync_self {
         sync_self(&profile, seeds, storage, options, rt)
     } else {
         sync(urn, &profile, seeds, storage, options, rt)
     }
 }
 
+/// Sync all local projects and the local identity, continuing past failures.
+/// Unless seeds are given, every project is synced with its configured seeds,
+/// see [`sync::project_seeds`].
+pub fn sync_all(
+    profile: &Profile,
+    seeds: Option<NonEmpty<sync::Seed<String>>>,
+    storage: Storage,
+    options: Options,
+    rt: tokio::runtime::Runtime,
+) -> anyhow::Result<()> {
+    let identity = person::local(&storage)?;
+    let targets = iter::once((String::from("(self)"), identity.urn()))
+        .chain(
+            project::list(&storage)?
+                .into_iter()
+                .map(|(urn, meta, _)| (meta.name, urn)),
+        )
+        .collect::<Vec<_>>();
+
+    match &seeds {
+        Some(seeds) => term::headline(&format!(
+            "Syncing 🌱 {} identities with {} seed(s)",
+            term::format::dim(targets.len()),
+            term::format::dim(seeds.len())
+        )),
+        None => term::headline(&format!(
+            "Syncing 🌱 {} identities with their configured seeds",
+            term::format::dim(targets.len()),
+        )),
+    }
+
+    let signer = term::signer(profile)?;
+    let mut table = term::Table::default();
+    let mut failed = 0;
+
+    for (name, urn) in &targets {
+        term::info!("{} {}", term::format::bold(name), term::format::dim(urn));
+        let seeds = match &seeds {
+            Some(seeds) => Ok(seeds.clone()),
+            None => sync::project_seeds(profile, &storage, urn),
+        };
+        let result = seeds.and_then(|seeds| {
+            term::sync::sync_with(
+                urn.clone(),
+                seeds,
+                options.mode,
+                options.sync.clone(),
+                profile,
+                signer.clone(),
+                &rt,
+            )
+        });
+
+        let status = match result {
+            Ok(results) => {
+                term::sync::report(&results, options.verbose);
+
+                let synced = results.iter().filter(|r| r.status().is_success()).count();
+                let status = format!("{}/{} seed(s)", synced, results.len());
+
+                if synced == results.len() {
+                    term::format::positive(status)
+                } else if synced > 0 {
+                    term::format::yellow(status)
+                } else {
+                    failed += 1;
+                    term::format::negative(status)
+                }
+            }
+            Err(err) => {
+                term::warning(&format!("Failed to sync {}: {}", urn, err));
+                failed += 1;
+                term::format::negative("failed")
+            }
+        };
+        table.push([
+            term::format::bold(name),
+            term::format::dim(urn.encode_id()),
+            status,
+        ]);
+    }
+
+    term::blank();
+    table.render();
+    term::blank();
+
+    if failed > 0 {
+        anyhow::bail!("{} of {} sync(s) failed", failed, targets.len());
+    }
+    term::success!("Synced {} identities", targets.len());
+
+    Ok(())
+}
+
+/// Sync the given projects with their seeds whenever their local refs change,
+/// until interrupted.
+pub fn watch(
+    profile: &Profile,
+    targets: Vec<(Urn, NonEmpty<sync::Seed<String>>)>,
+    options: Options,
+    rt: tokio::runtime::Runtime,
+) -> anyhow::Result<()> {
+    logger::init(log::Level::Info)?;
+
+    let stop = Arc::new(AtomicBool::new(false));
+    for signal in [signal_hook::consts::SIGINT, signal_hook::consts::SIGTERM] {
+        signal_hook::flag::register(signal, stop.clone())?;
+    }
+
+    let monorepo = git::Repository::open_bare(profile.paths().git_dir())?;
+    let mut watcher = Watcher::new(
+        &monorepo,
+        targets.iter().map(|(urn, _)| urn.clone()),
+        watch::Options::default(),
+        Instant::now(),
+    )?;
+    let signer = term::signer(profile)?;
+
+    log::info!(
+        "Watching {} project(s), press Ctrl-C to stop",
+        targets.len()
+    );
+
+    watcher.run(&stop, |event| {
+        handle(event, &targets, options.mode, |urn, seeds, mode| {
+            term::sync::sync_with(
+                urn.clone(),
+                seeds.clone(),
+                mode,
+                options.sync.clone(),
+                profile,
+                signer.clone(),
+                &rt,
+            )
+        });
+    });
+    log::info!("Stopped watching");
+
+    Ok(())
+}
+
+/// Sync the project of a watch event with its seeds, using `sync`, and log the
+/// outcome. Events that don't match the sync mode, or that are about projects
+/// that aren't watched, are ignored. Returns whether a sync was attempted.
+fn handle(
+    event: Event,
+    targets: &[(Urn, NonEmpty<sync::Seed<String>>)],
+    mode: Mode,
+    sync: impl FnOnce(
+        &Urn,
+        &NonEmpty<sync::Seed<String>>,
+        Mode,
+    ) -> anyhow::Result<NonEmpty<sync::SyncResult>>,
+) -> bool {
+    let (urn, mode) = match &event {
+        Event::Push(urn) if !matches!(mode, Mode::Fetch) => (urn, Mode::Push),
+        Event::Fetch(urn) if !matches!(mode, Mode::Push) => (urn, Mode::Fetch),
+        _ => return false,
+    };
+    let seeds = match targets.iter().find(|(u, _)| u == urn) {
+        Some((_, seeds)) => seeds,
+        None => return false,
+    };
+    log::info!(
+        "{} {}",
+        if mode.is_push() {
+            "Pushing"
+        } else {
+            "Fetching"
+        },
+        urn
+    );
+
+    match sync(urn, seeds, mode) {
+        Ok(results) => {
+            for result in results.iter() {
+                let status = result.status();
+
+                if status.is_success() {
+                    log::info!("{} {}: {}", urn, result.seed.peer, status);
+                } else {
+                    log::warn!("{} {}: {}", urn, result.seed.peer, status);
+                }
+            }
+        }
+        Err(err) => log::error!("Failed to sync {}: {}", urn, err),
+    }
+    true
+}
+
+/// Retry all queued pushes.
+pub fn flush(profile: &Profile, rt: tokio::runtime::Runtime) -> anyhow::Result<()> {
+    let queue = sync::queue::Queue::open(profile)?;
+    if queue.is_empty() {
+        term::info!("No queued pushes.");
+        return Ok(());
+    }
+    term::headline(&format!(
+        "Retrying {} queued push(es)",
+        term::format::dim(queue.entries().len())
+    ));
+
+    let signer = term::signer(profile)?;
+    let (succeeded, failed) = term::sync::flush(profile, signer, &rt, true)?;
+
+    term::blank();
+    if failed > 0 {
+        anyhow::bail!("{} push(es) failed again and remain queued", failed);
+    }
+    term::success!("{} queued push(es) completed", succeeded);
+
+    Ok(())
+}
+
+/// Show the status of local refs relative to each seed, without syncing.
+pub fn status(
+    urn: &Urn,
+    profile: &Profile,
+    storage: &Storage,
+    seeds: Vec<config::SeedConfig>,
+) -> anyhow::Result<()> {
+    let monorepo = git::Repository::open_bare(profile.paths().git_dir())?;
+    let peer = storage.peer_id();
+
+    if sync::status::local_refs(&monorepo, urn)?.is_empty() {
+        anyhow::bail!("project {} has no local refs to compare", urn);
+    }
+    term::headline(&format!(
+        "Comparing 🌱 {} with {} seed(s)",
+        term::format::highlight(urn),
+        term::format::dim(seeds.len())
+    ));
+
+    for seed in &seeds {
+        let name = seed
+            .name
+            .clone()
+            .unwrap_or_else(|| seed.git.host_str().unwrap_or_default().to_owned());
+        let spinner = term::spinner(format!(
+            "Listing refs on {}...",
+            term::format::highlight(&name)
+        ));
+
+        let refs = match sync::status::status(&monorepo, &seed.git, urn, peer) {
+            Ok(refs) => {
+                spinner.clear();
+                refs
+            }
+            Err(err) => {
+                spinner.failed();
+                term::warning(&format!("couldn't list refs on {}: {}", name, err));
+                term::blank();
+                continue;
+            }
+        };
+
+        term::info!("{}", term::format::bold(&name));
+
+        let mut table = term::Table::default();
+        for (r, status) in refs {
+            let status = match status {
+                RefStatus::UpToDate => term::format::positive(status),
+                RefStatus::Ahead { .. } | RefStatus::Missing => term::format::yellow(status),
+                RefStatus::Behind { .. } => term::format::tertiary(status),
+                RefStatus::Unknown => term::format::dim(status),
+                RefStatus::Diverged { .. } => term::format::negative(status),
+            };
+            let r = match r.strip_prefix("heads/") {
+                Some(branch) => term::format::highlight(branch),
+                None => term::format::dim(r),
+            };
+            table.push([r, status]);
+        }
+        table.render_tree();
+        term::blank();
+    }
+
+    Ok(())
+}
+
 pub fn sync_self(
     profile: &Profile,
     seeds: NonEmpty<sync::Seed<String>>,
@@ -176,11 +633,10 @@ pub fn sync_self(
     ));
 
     let signer = term::signer(profile)?;
-    let _result = term::sync::sync(urn, seeds, options.mode, profile, signer, &rt)?;
+    let results =
+        term::sync::sync_with(urn, seeds, options.mode, options.sync, profile, signer, &rt)?;
 
-    if options.verbose {
-        // TODO: When sync result is usable, output should go here.
-    }
+    term::sync::report(&results, options.verbose);
 
     Ok(())
 }
@@ -199,24 +655,34 @@ pub fn sync(
         term::format::dim(seeds.len())
     ));
 
-    let storage = storage.read_only();
     let signer = term::signer(profile)?;
-    let _result = term::sync::sync(
+    let results = term::sync::sync_with(
         urn.clone(),
         seeds.clone(),
         options.mode,
+        options.sync,
         profile,
         signer,
         &rt,
     )?;
+    term::sync::report(&results, options.verbose);
     term::blank();
 
-    if options.verbose {
-        // TODO: When sync result is usable, output should go here.
-        // TODO: Depending on the result, we can show `~` as in partial success, `ok` as in total
-        //       success and `!!` as in no success.
-        // TODO: NoConnection can be due to invalid PeerId!
-        // TODO: Success with no refs updated can mean the server is not tracking us.
+    // Let the user know about issue and patch changes that were fetched.
+    let cobs = cobs::store(profile, &storage)?;
+    match Inbox::open(profile.paths()).unread(&cobs, &urn) {
+        Ok(unread) if !unread.is_empty() => {
+            term::info!(
+                "📬 {} unread notification(s), see {}",
+                term::format::highlight(unread.len()),
+                term::format::secondary("rad inbox")
+            );
+            term::blank();
+        }
+        Ok(_) => {}
+        Err(err) => {
+            term::warning(&format!("Failed to check for notifications: {}", err));
+        }
     }
 
     if let Some(proj) = project::get(&storage, &urn)? {
@@ -279,3 +745,78 @@ pub fn sync(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use librad::PeerId;
+
+    #[test]
+    fn test_handle() {
+        let urn = Urn::from_str("rad:git:hnrkyghsrokxzxpy9pww69xr11dr9q7edbxfo").unwrap();
+        let other = Urn::from_str("rad:git:hnrkbtw9t1of4ykjy6er4qqwxtc54k9943eto").unwrap();
+        let peer =
+            PeerId::from_str("hyb5to4rshftx4apgmu9s6wnsp4ddmp1mz6ijh4qqey7fb8wrpawxa").unwrap();
+        let seed = sync::Seed {
+            peer,
+            addrs: "seed.local:8776".to_owned(),
+            label: None,
+        };
+        let targets = vec![(urn.clone(), NonEmpty::new(seed))];
+        let mut calls = Vec::new();
+
+        // Stands in for the seed, which always syncs successfully.
+        let mut seed = |urn: &Urn,
+                        seeds: &NonEmpty<sync::Seed<String>>,
+                        mode: Mode|
+         -> anyhow::Result<NonEmpty<sync::SyncResult>> {
+            calls.push((urn.clone(), mode.is_push()));
+
+            Ok(NonEmpty::new(sync::SyncResult {
+                seed: sync::Seed {
+                    peer: seeds.first().peer,
+                    addrs: vec![],
+                    label: None,
+                },
+                fetch: None,
+                push: None,
+                https: None,
+                cancelled: false,
+            }))
+        };
+
+        assert!(handle(
+            Event::Push(urn.clone()),
+            &targets,
+            Mode::All,
+            &mut seed
+        ));
+        assert!(handle(
+            Event::Fetch(urn.clone()),
+            &targets,
+            Mode::All,
+            &mut seed
+        ));
+        assert!(!handle(
+            Event::Push(urn.clone()),
+            &targets,
+            Mode::Fetch,
+            &mut seed
+        ));
+        assert!(!handle(
+            Event::Fetch(urn.clone()),
+            &targets,
+            Mode::Push,
+            &mut seed
+        ));
+        assert!(!handle(Event::Push(other), &targets, Mode::All, &mut seed));
+        assert!(handle(
+            Event::Fetch(urn.clone()),
+            &targets,
+            Mode::Fetch,
+            |_, _, _| Err(anyhow!("seed unreachable"))
+        ));
+
+        assert_eq!(calls, vec![(urn.clone(), true), (urn, false)]);
+    }
+}
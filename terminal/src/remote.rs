@@ -3,11 +3,17 @@ use std::collections::HashSet;
 use librad::git::storage::Storage;
 use librad::git::Urn;
 
+use radicle_common::petname::Petnames;
 use radicle_common::{git, project};
 
 use crate as term;
 
-pub fn list(storage: &Storage, repo: &git::Repository, urn: &Urn) -> anyhow::Result<()> {
+pub fn list(
+    storage: &Storage,
+    repo: &git::Repository,
+    urn: &Urn,
+    petnames: &Petnames,
+) -> anyhow::Result<()> {
     let mut table = term::Table::default();
     let proj = project::get(&storage, urn)?
         .ok_or_else(|| anyhow::anyhow!("project {} not found on local device", urn))?;
@@ -25,7 +31,13 @@ pub fn list(storage: &Storage, repo: &git::Repository, urn: &Urn) -> anyhow::Res
             String::new()
         };
 
-        if let Some(person) = project::person(&storage, urn.clone(), &peer)? {
+        if let Some(name) = petnames.get(&peer) {
+            table.push([
+                term::format::bold(name),
+                term::format::tertiary(peer),
+                delegate,
+            ]);
+        } else if let Some(person) = project::person(&storage, urn.clone(), &peer)? {
             table.push([
                 term::format::bold(person.subject().name.to_string()),
                 term::format::tertiary(peer),
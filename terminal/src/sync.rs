@@ -1,14 +1,21 @@
+use std::cell::RefCell;
 use std::convert::TryInto;
-use std::time;
 
-use librad::git::Urn;
+use librad::git::{Storage, Urn};
+use librad::{PeerId, Signer};
 
 use radicle_common as common;
+use radicle_common::config::SeedConfig;
+use radicle_common::git;
 use radicle_common::nonempty::NonEmpty;
+use radicle_common::petname::Petnames;
 use radicle_common::profile::Profile;
 use radicle_common::signer::ToSigner;
 use radicle_common::sync;
+use radicle_common::sync::http::Transport;
+use radicle_common::sync::queue::{self, Queue};
 use radicle_common::sync::SyncResult;
+use radicle_common::{project, tracking};
 
 use crate as term;
 
@@ -19,17 +26,104 @@ pub fn sync(
     profile: &Profile,
     signer: impl ToSigner,
     rt: &common::tokio::runtime::Runtime,
+) -> anyhow::Result<NonEmpty<SyncResult>> {
+    sync_with(
+        urn,
+        seeds,
+        mode,
+        sync::Options::from_profile(profile),
+        profile,
+        signer,
+        rt,
+    )
+}
+
+/// Like [`sync`], with the given sync options.
+///
+/// When pushing, pushes that fail are added to the profile's push queue, to be
+/// retried later, and pushes that succeed are removed from it.
+///
+/// When fetching, seeds that can't be fetched from over the link protocol are
+/// fetched from over HTTPS instead, unless the transport is [`Transport::Link`].
+///
+/// The project's tracking policy applies: peers it blocks are never fetched from,
+/// and when fetching, the peers it asks for are tracked first. See [`policy`].
+pub fn sync_with(
+    urn: Urn,
+    seeds: NonEmpty<sync::Seed<String>>,
+    mode: sync::Mode,
+    options: sync::Options,
+    profile: &Profile,
+    signer: impl ToSigner,
+    rt: &common::tokio::runtime::Runtime,
 ) -> anyhow::Result<NonEmpty<SyncResult>> {
     let signer = signer.to_signer(profile)?;
-    let timeout = time::Duration::from_secs(9);
-    let spinner = term::spinner("Syncing...");
-    let result = rt.block_on(async {
-        let (seeds, _errors) = sync::Seeds::resolve(seeds.iter()).await;
-        let client = sync::client(signer, profile).await?;
-        let result = sync::sync(&client, urn, seeds, mode, timeout).await;
-
-        Ok::<Vec<SyncResult>, anyhow::Error>(result)
-    })?;
+    let local = PeerId::from(signer.public_key());
+    let storage = Storage::open(profile.paths(), signer.clone())?;
+    let mut options = options;
+
+    match policy(&urn, mode, &storage, profile) {
+        Ok(blocked) => options.blocked.extend(blocked),
+        Err(err) => term::warning(&format!("Failed to apply tracking policy: {}", err)),
+    }
+    let transport = options.transport;
+    let skip = std::iter::once(local)
+        .chain(options.blocked.iter().copied())
+        .collect::<Vec<_>>();
+    // Over HTTPS, only fetching is supported: pushes always go over the link protocol.
+    let link = match (transport, mode) {
+        (Transport::Https, sync::Mode::Fetch) => None,
+        (Transport::Https, _) => Some(sync::Mode::Push),
+        (_, mode) => Some(mode),
+    };
+    let spinner = RefCell::new(term::spinner("Syncing..."));
+    let (mut result, resolved) = match link {
+        Some(link) => rt.block_on(async {
+            let (seeds, _errors) = sync::Seeds::resolve(seeds.iter()).await;
+            let resolved = seeds.0.iter().map(|s| s.peer).collect::<Vec<_>>();
+            let client = sync::client(signer.clone(), profile).await?;
+            let result = sync::sync(&client, urn.clone(), seeds, link, options, |peer, msg| {
+                spinner.borrow_mut().message(format!(
+                    "Syncing... {} {}",
+                    term::format::tertiary(common::fmt::peer(peer)),
+                    term::format::dim(msg.trim())
+                ));
+            })
+            .await;
+
+            Ok::<_, anyhow::Error>((result, resolved))
+        })?,
+        None => (Vec::new(), Vec::new()),
+    };
+
+    let mut spinner = spinner.into_inner();
+    spinner.message("Syncing...");
+
+    if mode.is_fetch() && transport != Transport::Link {
+        let tracked = tracking::tracked(&storage, &urn)?;
+
+        fetch_https(
+            &urn,
+            &seeds,
+            &tracked,
+            &skip,
+            &mut result,
+            profile,
+            &mut spinner,
+        )?;
+        spinner.message("Syncing...");
+    }
+    if mode.is_fetch() {
+        if let Err(err) = reindex(&urn, profile, &storage) {
+            term::warning(&format!("Failed to update COB index: {}", err));
+        }
+    }
+
+    if mode.is_push() {
+        if let Err(err) = enqueue(&urn, &seeds, &resolved, &result, profile) {
+            term::warning(&format!("Failed to update push queue: {}", err));
+        }
+    }
 
     let results = if let Ok(results) = result.try_into() {
         results
@@ -46,3 +140,255 @@ pub fn sync(
 
     Ok(results)
 }
+
+/// Fetch over HTTPS from the seeds that couldn't be fetched from over the link protocol.
+/// Only the tracked peers and the delegates are fetched, minus the skipped peers, see
+/// [`sync::http::fetch`].
+fn fetch_https(
+    urn: &Urn,
+    seeds: &NonEmpty<sync::Seed<String>>,
+    tracked: &[PeerId],
+    skip: &[PeerId],
+    results: &mut Vec<SyncResult>,
+    profile: &Profile,
+    spinner: &mut term::Spinner,
+) -> anyhow::Result<()> {
+    let urls = sync::seed_git_urls(profile);
+    let repo = git::Repository::open_bare(profile.paths().git_dir())?;
+
+    for seed in seeds.iter() {
+        let ix = results.iter().position(|r| r.seed.peer == seed.peer);
+        let fetched = ix.map_or(false, |ix| matches!(results[ix].fetch, Some(Ok(_))));
+        if fetched {
+            continue;
+        }
+        let url = match urls.get(&seed.peer) {
+            Some(url) => url.clone(),
+            None => match SeedConfig::new(seed, None) {
+                Ok(cfg) => cfg.git,
+                Err(_) => continue,
+            },
+        };
+        spinner.message(format!(
+            "Syncing... {} {}",
+            term::format::tertiary(common::fmt::peer(&seed.peer)),
+            term::format::dim(format!("fetching over HTTPS from {}", url))
+        ));
+
+        let outcome = sync::http::fetch(&repo, &url, urn, tracked, skip);
+        match ix {
+            Some(ix) => results[ix].https = Some(outcome),
+            None => results.push(SyncResult {
+                seed: sync::Seed {
+                    peer: seed.peer,
+                    addrs: vec![],
+                    label: seed.label.clone(),
+                },
+                fetch: None,
+                push: None,
+                https: Some(outcome),
+                cancelled: false,
+            }),
+        }
+    }
+    Ok(())
+}
+
+/// Apply the tracking policy of a project ahead of a sync, see [`tracking::apply`].
+/// Peers are only tracked and untracked when fetching. Returns the blocked peers.
+fn policy(
+    urn: &Urn,
+    mode: sync::Mode,
+    storage: &Storage,
+    profile: &Profile,
+) -> anyhow::Result<Vec<PeerId>> {
+    let policy = tracking::Policy::of_project(storage, urn)?;
+
+    if !mode.is_fetch() || policy.is_default() {
+        return Ok(policy.block);
+    }
+    if let Some(proj) = project::get(storage, urn)? {
+        let applied = tracking::apply(&policy, &proj, storage, profile)?;
+        let petnames = Petnames::open(profile)?;
+
+        for peer in &applied.untracked {
+            term::info!(
+                "{} Untracked blocked peer {}",
+                term::format::yellow("!!"),
+                term::format::tertiary(petnames.peer(peer))
+            );
+        }
+        for (peer, reason) in &applied.tracked {
+            term::success!(
+                "Tracking {} {}",
+                term::format::tertiary(petnames.peer(peer)),
+                term::format::dim(format!("({})", reason))
+            );
+        }
+    }
+    Ok(policy.block)
+}
+
+/// Bring the COB index of a project up to date with the changes that were fetched,
+/// so that they don't have to be replayed the next time objects are listed.
+fn reindex(urn: &Urn, profile: &Profile, storage: &Storage) -> anyhow::Result<()> {
+    let cobs = common::cobs::store(profile, storage)?;
+
+    cobs.refresh(urn);
+
+    Ok(())
+}
+
+/// Update the push queue with the outcome of a push.
+fn enqueue(
+    urn: &Urn,
+    seeds: &NonEmpty<sync::Seed<String>>,
+    resolved: &[PeerId],
+    results: &[SyncResult],
+    profile: &Profile,
+) -> Result<(), queue::Error> {
+    let now = queue::now();
+    let failed = Queue::update(profile, |queue| {
+        let mut failed = 0;
+
+        for seed in seeds.iter() {
+            match results.iter().find(|r| r.seed.peer == seed.peer) {
+                Some(SyncResult {
+                    push: Some(Ok(_)), ..
+                }) => {
+                    queue.succeeded(urn, &seed.peer);
+                }
+                Some(SyncResult {
+                    push: Some(Err(_)), ..
+                }) => {
+                    queue.failed(urn, seed, now);
+                    failed += 1;
+                }
+                // The seed couldn't be resolved.
+                None if !resolved.contains(&seed.peer) => {
+                    queue.failed(urn, seed, now);
+                    failed += 1;
+                }
+                // The push was cancelled, eg. because a quorum was reached.
+                _ => {}
+            }
+        }
+        failed
+    })?;
+
+    if failed > 0 {
+        term::info!(
+            "{} {} failed push(es) queued for retry, see {}",
+            term::format::yellow("!!"),
+            failed,
+            term::format::secondary("`rad sync --flush`")
+        );
+    }
+
+    Ok(())
+}
+
+/// Retry the pushes in the profile's push queue. Unless `all` is set, only pushes
+/// that are due according to their backoff are retried. Returns the number of
+/// pushes that succeeded and failed.
+pub fn flush(
+    profile: &Profile,
+    signer: impl ToSigner,
+    rt: &common::tokio::runtime::Runtime,
+    all: bool,
+) -> anyhow::Result<(usize, usize)> {
+    let signer = signer.to_signer(profile)?;
+    for entry in Queue::update(profile, Queue::remove_invalid)? {
+        term::warning(&format!(
+            "Dropping queued push of {} to invalid seed address '{}'",
+            entry.urn, entry.seed
+        ));
+    }
+    let queue = Queue::open(profile)?;
+    let now = queue::now();
+    let entries = queue
+        .entries()
+        .iter()
+        .filter(|e| all || e.is_due(now))
+        .collect::<Vec<_>>();
+
+    let mut urns: Vec<&Urn> = Vec::new();
+    for entry in &entries {
+        if !urns.contains(&&entry.urn) {
+            urns.push(&entry.urn);
+        }
+    }
+
+    let (mut succeeded, mut failed) = (0, 0);
+    for urn in urns {
+        let seeds = entries
+            .iter()
+            .filter(|e| &e.urn == urn)
+            .filter_map(|e| e.seed())
+            .collect::<Vec<_>>();
+        let seeds = match NonEmpty::from_vec(seeds) {
+            Some(seeds) => seeds,
+            None => continue,
+        };
+
+        term::info!("Retrying push of {}...", term::format::highlight(urn));
+
+        match sync_with(
+            urn.clone(),
+            seeds,
+            sync::Mode::Push,
+            sync::Options::from_profile(profile),
+            profile,
+            signer.clone(),
+            rt,
+        ) {
+            Ok(results) => {
+                for result in results.iter() {
+                    match result.push {
+                        Some(Ok(_)) => succeeded += 1,
+                        Some(Err(_)) => failed += 1,
+                        // The push wasn't attempted.
+                        None => {}
+                    }
+                }
+                report(&results, false);
+            }
+            Err(err) => {
+                term::warning(&format!("Failed to retry push of {}: {}", urn, err));
+            }
+        }
+    }
+
+    Ok((succeeded, failed))
+}
+
+/// Print the outcome of a sync, one line per seed. Unless `verbose` is set, seeds
+/// that synced without issues are left out.
+pub fn report(results: &NonEmpty<SyncResult>, verbose: bool) {
+    for result in results.iter() {
+        let status = result.status();
+
+        if !verbose && matches!(status, sync::Status::Synced { .. }) {
+            continue;
+        }
+        let seed = match result.seed.addrs.first() {
+            Some(addr) => format!("{}@{}", result.seed.peer, addr),
+            None => result.seed.peer.to_string(),
+        };
+        let badge = if status.is_success() {
+            term::format::positive("ok")
+        } else if status == sync::Status::Cancelled {
+            term::format::dim("--")
+        } else {
+            term::format::negative("!!")
+        };
+        let status = match status {
+            sync::Status::Synced { .. } => term::format::dim(status),
+            sync::Status::NotTracking => term::format::yellow(status),
+            sync::Status::Cancelled => term::format::dim(status),
+            _ => term::format::negative(status),
+        };
+
+        term::info!("{} {} {}", badge, term::format::tertiary(seed), status);
+    }
+}
@@ -38,6 +38,10 @@ impl<const W: usize> Table<W> {
         self.rows.push(row);
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
     pub fn render(self) {
         let width = term::width(); // Terminal width.
 
@@ -0,0 +1,359 @@
+//! Machine-readable output of the tracking graph.
+use std::fmt::Write as _;
+use std::str::FromStr;
+
+use librad::PeerId;
+
+use radicle_common::petname::Petnames;
+use radicle_common::project;
+
+use crate::Peer;
+
+/// Output format of the tracking graph.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Human-readable tree.
+    Tree,
+    /// JSON document.
+    Json,
+    /// Graphviz DOT graph.
+    Dot,
+}
+
+impl Default for Format {
+    fn default() -> Self {
+        Self::Tree
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tree" => Ok(Self::Tree),
+            "json" => Ok(Self::Json),
+            "dot" => Ok(Self::Dot),
+            _ => Err(format!("unknown format '{}'", s)),
+        }
+    }
+}
+
+/// Sort peers and their branches, so that the graph is always output in the same order.
+/// Delegates come first, then peers are ordered by id, with devices of the same person
+/// next to each other.
+pub fn sort(peers: &mut [Peer]) {
+    peers.sort_by_cached_key(|p| {
+        let delegate = p.meta.as_ref().map_or(false, |m| m.delegate);
+        let id = p.id.default_encoding();
+        let person = p
+            .meta
+            .as_ref()
+            .and_then(|m| m.person.as_ref())
+            .map_or_else(|| id.clone(), |p| p.urn.to_string());
+
+        (!delegate, person, id)
+    });
+    for peer in peers.iter_mut() {
+        peer.branches.sort_by(|a, b| a.name.cmp(&b.name));
+    }
+}
+
+/// Render the tracking graph as JSON.
+pub fn json(
+    project: &project::Metadata,
+    peers: &[Peer],
+    you: &PeerId,
+    petnames: &Petnames,
+) -> serde_json::Value {
+    let peers = peers
+        .iter()
+        .map(|peer| {
+            let branches = peer
+                .branches
+                .iter()
+                .map(|b| {
+                    serde_json::json!({
+                        "name": b.name,
+                        "head": b.head.to_string(),
+                        "message": b.message,
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            serde_json::json!({
+                "id": peer.id,
+                "name": peer.name(petnames),
+                "person": peer.meta.as_ref().and_then(|m| m.person.as_ref()).map(|p| p.urn.to_string()),
+                "delegate": peer.meta.as_ref().map_or(false, |m| m.delegate),
+                "you": &peer.id == you,
+                "branches": branches,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    serde_json::json!({
+        "urn": project.urn.to_string(),
+        "name": project.name,
+        "peers": peers,
+    })
+}
+
+/// Render the tracking graph in the Graphviz DOT language.
+///
+/// The project is linked to every peer, and every peer to its branches.
+pub fn dot(
+    project: &project::Metadata,
+    peers: &[Peer],
+    you: &PeerId,
+    petnames: &Petnames,
+) -> String {
+    let mut out = String::new();
+    let root = project.urn.to_string();
+
+    writeln!(out, "digraph tracking {{").ok();
+    writeln!(out, "  rankdir=LR;").ok();
+    writeln!(out, "  node [fontname=\"monospace\"];").ok();
+    writeln!(
+        out,
+        "  {} [shape=box, label={}];",
+        quote(&root),
+        quote(&format!("{}\n{}", project.name, root))
+    )
+    .ok();
+
+    for peer in peers {
+        let id = peer.id.default_encoding();
+        let mut label = match peer.name(petnames) {
+            Some(name) => format!("{}\n{}", name, id),
+            None => id.clone(),
+        };
+        if peer.meta.as_ref().map_or(false, |m| m.delegate) {
+            label.push_str("\n(delegate)");
+        }
+        if &peer.id == you {
+            label.push_str("\n(you)");
+        }
+
+        writeln!(
+            out,
+            "  {} [shape=ellipse, label={}];",
+            quote(&id),
+            quote(&label)
+        )
+        .ok();
+        writeln!(out, "  {} -> {};", quote(&root), quote(&id)).ok();
+
+        for branch in &peer.branches {
+            let node = format!("{}/{}", id, branch.name);
+            let head = branch.head.to_string();
+            let mut label = format!("{}\n{}", branch.name, &head[..7.min(head.len())]);
+            if !branch.message.is_empty() {
+                label.push('\n');
+                label.push_str(&branch.message);
+            }
+
+            writeln!(
+                out,
+                "  {} [shape=note, label={}];",
+                quote(&node),
+                quote(&label)
+            )
+            .ok();
+            writeln!(out, "  {} -> {};", quote(&id), quote(&node)).ok();
+        }
+    }
+    writeln!(out, "}}").ok();
+
+    out
+}
+
+/// Quote a string as a DOT identifier.
+fn quote(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashSet;
+    use std::convert::TryFrom;
+
+    use librad::git::Urn;
+
+    use radicle_common::git;
+    use radicle_common::project::{PeerIdentity, PeerInfo};
+
+    use crate::Branch;
+
+    const ALICE: &str = "hyb5to4rshftx4apgmu9s6wnsp4ddmp1mz6ijh4qqey7fb8wrpawxa";
+    const BOB: &str = "hyd1to75dyfpizchxp43rdwhisp8nbr76g5pxa5f4y7jh4pa6jjzns";
+    const EVE: &str = "hyd7wpd8p5aqnm9htsfoatxkckmw6ingnsdudns9code5xq17h1rhw";
+    const PROJECT: &str = "rad:git:hnrkyghsrokxzxpy9pww69xr11dr9q7edbxfo";
+    const PERSON: &str = "rad:git:hnrkbtw9t1of4ykjy6er4qqwxtc54k9943eto";
+
+    fn project() -> project::Metadata {
+        project::Metadata {
+            urn: Urn::from_str(PROJECT).unwrap(),
+            name: String::from("nakamoto"),
+            description: String::new(),
+            default_branch: git::RefLike::try_from("master").unwrap().into(),
+            delegates: vec![],
+            remotes: HashSet::new(),
+        }
+    }
+
+    fn peer(id: &str, delegate: bool, person: Option<&str>, branches: &[&str]) -> Peer {
+        let id = PeerId::from_str(id).unwrap();
+        let person = person.map(|name| PeerIdentity {
+            urn: Urn::from_str(PERSON).unwrap(),
+            name: name.to_owned(),
+            ens: None,
+            devices: vec![id],
+        });
+        let branches = branches
+            .iter()
+            .enumerate()
+            .map(|(i, name)| Branch {
+                name: name.to_string(),
+                head: git::Oid::from_str(&format!("{:040}", i + 1)).unwrap(),
+                message: format!("Update {}", name),
+            })
+            .collect();
+
+        Peer {
+            id,
+            meta: Some(PeerInfo {
+                id,
+                person,
+                delegate,
+            }),
+            branches,
+        }
+    }
+
+    /// Peers in the order they are expected to be output in.
+    fn peers() -> Vec<Peer> {
+        vec![
+            peer(BOB, true, None, &["dev", "master"]),
+            peer(ALICE, false, None, &[]),
+            peer(EVE, false, Some("eve"), &["master"]),
+        ]
+    }
+
+    /// Petnames, naming alice.
+    fn petnames() -> Petnames {
+        let mut petnames = Petnames::default();
+        petnames
+            .set(PeerId::from_str(ALICE).unwrap(), "alice")
+            .unwrap();
+        petnames
+    }
+
+    fn ids(peers: &[Peer]) -> Vec<String> {
+        peers.iter().map(|p| p.id.default_encoding()).collect()
+    }
+
+    #[test]
+    fn test_sort() {
+        let expected = ids(&peers());
+
+        let mut shuffled = peers();
+        shuffled.reverse();
+        shuffled[2].branches.reverse();
+        sort(&mut shuffled);
+
+        // Delegates come first, and the order doesn't depend on the input order.
+        assert_eq!(ids(&shuffled), expected);
+        assert_eq!(shuffled[0].branches[0].name, "dev");
+
+        let mut sorted = peers();
+        sort(&mut sorted);
+        assert_eq!(ids(&sorted), expected);
+    }
+
+    #[test]
+    fn test_json() {
+        let you = PeerId::from_str(BOB).unwrap();
+        let json = json(&project(), &peers(), &you, &petnames());
+
+        assert_eq!(json["urn"], PROJECT);
+        assert_eq!(json["name"], "nakamoto");
+        assert_eq!(json["peers"].as_array().unwrap().len(), 3);
+
+        let bob = &json["peers"][0];
+        assert_eq!(bob["delegate"], true);
+        assert_eq!(bob["you"], true);
+        assert_eq!(bob["person"], serde_json::Value::Null);
+        assert_eq!(bob["branches"][0]["name"], "dev");
+        assert_eq!(bob["branches"][0]["head"], format!("{:040}", 1));
+        assert_eq!(bob["branches"][0]["message"], "Update dev");
+
+        let alice = &json["peers"][1];
+        assert_eq!(alice["name"], "alice");
+        assert_eq!(alice["person"], serde_json::Value::Null);
+
+        let eve = &json["peers"][2];
+        assert_eq!(eve["name"], "eve");
+        assert_eq!(eve["person"], PERSON);
+        assert_eq!(eve["delegate"], false);
+        assert_eq!(eve["you"], false);
+    }
+
+    #[test]
+    fn test_dot() {
+        let you = PeerId::from_str(BOB).unwrap();
+        let dot = dot(&project(), &peers(), &you, &petnames());
+        let lines = dot.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines.first(), Some(&"digraph tracking {"));
+        assert_eq!(lines.last(), Some(&"}"));
+
+        let expected = [
+            format!(
+                "  {} [shape=ellipse, label={}];",
+                quote(BOB),
+                quote(&format!("{}\n(delegate)\n(you)", BOB))
+            ),
+            format!(
+                "  {} [shape=ellipse, label={}];",
+                quote(ALICE),
+                quote(&format!("alice\n{}", ALICE))
+            ),
+            format!(
+                "  {} [shape=ellipse, label={}];",
+                quote(EVE),
+                quote(&format!("eve\n{}", EVE))
+            ),
+            format!(
+                "  {} [shape=note, label={}];",
+                quote(&format!("{}/master", EVE)),
+                quote("master\n0000000\nUpdate master")
+            ),
+            format!("  {} -> {};", quote(PROJECT), quote(BOB)),
+            format!("  {} -> {};", quote(EVE), quote(&format!("{}/master", EVE))),
+        ];
+        for line in &expected {
+            assert!(lines.contains(&line.as_str()), "missing line: {}", line);
+        }
+    }
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("master"), "\"master\"");
+        assert_eq!(quote("say \"hi\"\n"), "\"say \\\"hi\\\"\\n\"");
+        assert_eq!(quote("a\\b"), "\"a\\\\b\"");
+    }
+}
@@ -12,13 +12,16 @@ use librad::PeerId;
 
 use radicle_common::args::Help;
 use radicle_common::nonempty::NonEmpty;
+use radicle_common::petname::Petnames;
 use radicle_common::project::PeerInfo;
 use radicle_common::tokio;
 use radicle_common::Url;
-use radicle_common::{git, keys, project, seed, sync, Urn};
+use radicle_common::{config, git, keys, project, seed, sync, tracking, Urn};
 use radicle_terminal as term;
 
+pub mod graph;
 mod options;
+pub use graph::Format;
 pub use options::Options;
 
 #[derive(Debug)]
@@ -28,6 +31,23 @@ pub struct Peer {
     branches: Vec<Branch>,
 }
 
+impl Peer {
+    /// Name of the peer: its petname, or the name of its identity.
+    fn name(&self, petnames: &Petnames) -> Option<String> {
+        let devices = self.meta.as_ref().map(|m| m.devices()).unwrap_or_default();
+
+        petnames
+            .of_person(&self.id, devices)
+            .map(ToOwned::to_owned)
+            .or_else(|| {
+                self.meta
+                    .as_ref()
+                    .and_then(|m| m.person.as_ref())
+                    .map(|p| p.name.clone())
+            })
+    }
+}
+
 #[derive(Debug)]
 pub struct Branch {
     name: String,
@@ -43,15 +63,29 @@ pub const HELP: Help = Help {
     usage: r#"
 Usage
 
-    rad track           [--local | --remote]
-    rad track           [--seed <url>]
+    rad track           [--local | --remote] [--format <format>]
+    rad track           [--seed <url>] [--format <format>]
     rad track <peer-id> [--seed <url>] [--no-sync] [--no-upstream] [--no-fetch]
+    rad track --policy
 
     If a peer id is supplied, track this peer in the context of the current project. By default,
     a remote is created in the repository and an upstream tracking branch is setup. If a seed
     is supplied as well, the seed will be associated with this peer in the local git configuration.
 
+    Peers blocked by the project's tracking policy can't be tracked. The policy is set in the
+    `[tracking]` section of `Radicle.toml`, and can automatically track the project delegates
+    and peers who open patches when syncing:
+
+        [tracking]
+        delegates = true
+        contributors = true
+        block = ["<peer-id>"]
+
+    With `--policy`, the effective tracking policy of the current project is shown.
+
     If no peer id is supplied, show the local or remote tracking graph of the current project.
+    Delegates are listed first, then other peers ordered by peer id. With `--format`, the graph
+    can be output as JSON or as a Graphviz DOT graph, eg. `rad track --format dot | dot -Tsvg`.
 
 Options
 
@@ -61,6 +95,8 @@ Options
     --no-upstream          Don't setup a tracking branch for the remote
     --no-sync              Don't sync the peer's refs
     --no-fetch             Don't fetch the peer's refs into the working copy
+    --policy               Show the tracking policy of the current project
+    --format <format>      Output format of the tracking graph: `tree`, `json` or `dot` (default: tree)
     --verbose, -v          Verbose output
     --help                 Print help
 "#,
@@ -76,12 +112,14 @@ pub fn run(options: Options, ctx: impl term::Context) -> anyhow::Result<()> {
     let proj = project::get(&storage, &urn)?
         .ok_or_else(|| anyhow!("project {} not found in local storage", &urn))?;
 
-    if let Some(peer) = options.peer {
+    if options.policy {
+        policy(&proj, &storage, &profile)?;
+    } else if let Some(peer) = options.peer {
         // Track peer.
         track(peer, proj, repo, storage, profile, signer, options)?;
     } else {
         // Show tracking graph.
-        show(proj, repo, storage.read_only(), options)?;
+        show(proj, repo, storage.read_only(), &profile, options)?;
     }
 
     Ok(())
@@ -99,6 +137,14 @@ pub fn track(
     if &peer == storage.peer_id() {
         anyhow::bail!("you can't track yourself");
     }
+    if tracking::Policy::local()?.is_blocked(&peer) {
+        return Err(radicle_common::Error::WithHint {
+            err: anyhow!("peer {} is blocked by the project's tracking policy", peer),
+            hint:
+                "hint: remove the peer from `block` in the `[tracking]` section of `Radicle.toml`",
+        }
+        .into());
+    }
     let urn = &project.urn;
 
     term::info!(
@@ -155,7 +201,7 @@ pub fn track(
         seed::set_peer_seed(&seed, &peer)?;
         term::success!(
             "Saving seed configuration for {} to local git config...",
-            term::format::tertiary(radicle_common::fmt::peer(&peer))
+            term::format::tertiary(Petnames::open(&profile)?.peer(&peer))
         );
     }
 
@@ -188,19 +234,103 @@ pub fn track(
     Ok(())
 }
 
+/// Show the effective tracking policy of a project.
+pub fn policy(
+    project: &project::Metadata,
+    storage: &Storage,
+    profile: &Profile,
+) -> anyhow::Result<()> {
+    let policy = tracking::Policy::local()?;
+    let tracked = project::tracked(project, storage)?;
+    let petnames = Petnames::open(profile)?;
+    let toggle = |on: bool| {
+        if on {
+            term::format::positive("on")
+        } else {
+            term::format::dim("off")
+        }
+    };
+
+    term::info!(
+        "Tracking policy of {} {}",
+        term::format::highlight(&project.name),
+        term::format::dim(format!("({})", radicle_common::config::FILE_NAME_LOCAL))
+    );
+    term::blank();
+
+    let mut table = term::Table::default();
+    table.push([String::from("Track delegates"), toggle(policy.delegates)]);
+    table.push([
+        String::from("Track contributors"),
+        toggle(policy.contributors),
+    ]);
+    table.render();
+    term::blank();
+
+    let contributors = if policy.contributors {
+        tracking::contributors(project, storage, profile)?
+    } else {
+        Vec::new()
+    };
+    let candidates = policy.candidates(tracking::delegates(project), contributors);
+    let mut table = term::Table::default();
+    for (peer, reason) in candidates {
+        if &peer == storage.peer_id() {
+            continue;
+        }
+        let status = if tracked.contains_key(&peer) {
+            term::format::positive("tracked")
+        } else {
+            term::format::yellow("tracked on next sync")
+        };
+        table.push([
+            term::format::tertiary(petnames.peer(&peer)),
+            term::format::dim(reason),
+            status,
+        ]);
+    }
+    if !table.is_empty() {
+        term::info!("Automatically tracked:");
+        table.render();
+        term::blank();
+    }
+
+    let mut table = term::Table::default();
+    for peer in &policy.block {
+        let status = if tracked.contains_key(peer) {
+            term::format::negative("untracked on next sync")
+        } else {
+            String::new()
+        };
+        table.push([term::format::tertiary(petnames.peer(peer)), status]);
+    }
+    if table.is_empty() {
+        term::info!("{}", term::format::dim("No blocked peers"));
+    } else {
+        term::info!("Blocked:");
+        table.render();
+    }
+
+    Ok(())
+}
+
 pub fn show(
     project: project::Metadata,
     repo: git::Repository,
     storage: &ReadOnly,
+    profile: &Profile,
     options: Options,
 ) -> anyhow::Result<()> {
-    let peers = if options.local {
-        term::info!(
-            "{} {} {}",
-            term::format::highlight(&project.name),
-            &project.urn,
-            term::format::dim("(local)")
-        );
+    let tree = options.format == Format::Tree;
+    let mut peers = if options.local {
+        if tree {
+            term::info!(
+                "{} {} {}",
+                term::format::highlight(&project.name),
+                &project.urn,
+                term::format::dim("(local)")
+            );
+        }
         show_local(&project, storage)?
     } else {
         let seed = if let Some(seed) = &options.seed {
@@ -215,31 +345,56 @@ pub fn show(
             );
         }
 
-        let spinner = term::spinner(&format!(
-            "{} {} {}",
-            term::format::highlight(&project.name),
-            &project.urn,
-            term::format::dim(format!("({})", seed.host)),
-        ));
-        let peers = show_remote(&project, &repo, &seed.url())?;
+        let spinner = tree.then(|| {
+            term::spinner(&format!(
+                "{} {} {}",
+                term::format::highlight(&project.name),
+                &project.urn,
+                term::format::dim(format!("({})", seed.host)),
+            ))
+        });
+        let config = config::Config::resolve(profile).config;
+        let client = seed::api_client(&seed.url(), config.seeds());
+        let peers = show_remote(&project, &repo, &seed.url(), &client)?;
 
-        spinner.done();
+        if let Some(spinner) = spinner {
+            spinner.done();
+        }
 
         peers
     };
+    graph::sort(&mut peers);
+    let petnames = Petnames::open(profile)?;
+
+    match options.format {
+        Format::Json => {
+            let graph = graph::json(&project, &peers, storage.peer_id(), &petnames);
+            term::print(serde_json::to_string_pretty(&graph)?);
+            return Ok(());
+        }
+        Format::Dot => {
+            term::print(graph::dot(&project, &peers, storage.peer_id(), &petnames).trim_end());
+            return Ok(());
+        }
+        Format::Tree => {}
+    }
+
     if peers.is_empty() {
         term::info!("{}", term::format::dim("No remotes found for project"));
         return Ok(());
     }
 
-    // TODO: Deterministic ordering of peers when printed.
     for (i, peer) in peers.iter().enumerate() {
         let you = &peer.id == storage.peer_id();
         let mut header = vec![term::format::bold(peer.id)];
 
+        if let Some(name) = peer.name(&petnames) {
+            header.push(term::format::tertiary(name));
+        }
         if let Some(meta) = &peer.meta {
-            if let Some(name) = meta.person.as_ref().map(|p| &p.name) {
-                header.push(term::format::tertiary(name));
+            let devices = meta.devices().len();
+            if devices > 1 {
+                header.push(term::format::dim(format!("({} devices)", devices)));
             }
             if meta.delegate {
                 header.push(term::format::badge_primary("delegate"));
@@ -287,11 +442,7 @@ pub fn show(
 
 /// Return the Peer's branches with their Oid's by parsing references in the storage's git
 /// repository.
-fn get_peer_branches(
-    storage: &ReadOnly,
-    peer: PeerId,
-    urn: &Urn,
-) -> anyhow::Result<Vec<(String, git::Oid)>> {
+fn get_peer_branches(storage: &ReadOnly, peer: PeerId, urn: &Urn) -> anyhow::Result<Vec<Branch>> {
     // Open the monorepo.
     let repo = git::Repository::open_bare(storage.as_ref().path())?;
 
@@ -310,7 +461,17 @@ fn get_peer_branches(
         };
 
         if let Some(branch_name) = ref_name.strip_prefix(ref_name_prefix) {
-            branches.push((branch_name.to_string(), head));
+            let message = repo
+                .find_commit(head)
+                .ok()
+                .and_then(|c| c.summary().map(|s| s.to_owned()))
+                .unwrap_or_default();
+
+            branches.push(Branch {
+                name: branch_name.to_string(),
+                head,
+                message,
+            });
         }
     }
 
@@ -322,14 +483,7 @@ pub fn show_local(project: &project::Metadata, storage: &ReadOnly) -> anyhow::Re
     let mut peers = Vec::new();
 
     for (id, meta) in tracked {
-        let mut branches = vec![];
-        for (branch_name, head) in get_peer_branches(storage, id, &project.urn)? {
-            branches.push(Branch {
-                name: branch_name,
-                head,
-                message: String::new(),
-            });
-        }
+        let branches = get_peer_branches(storage, id, &project.urn)?;
 
         peers.push(Peer {
             id,
@@ -344,12 +498,13 @@ pub fn show_remote(
     project: &project::Metadata,
     repo: &git::Repository,
     seed: &Url,
+    client: &seed::SeedApiClient,
 ) -> anyhow::Result<Vec<Peer>> {
     let urn = &project.urn;
     let remotes = project::list_seed_heads(repo, seed, urn)?;
     let mut commits: HashMap<_, String> = HashMap::new();
 
-    let remote_metadata = if let Ok(meta) = seed::get_remotes(seed.clone(), urn) {
+    let remote_metadata = if let Ok(meta) = client.remotes(urn) {
         meta.into_iter().map(|r| (r.id, r)).collect()
     } else {
         HashMap::new() // Support old seeds that don't have metadata.
@@ -371,7 +526,7 @@ pub fn show_remote(
         for (branch, oid) in branches {
             let message: String = if let Some(m) = commits.get(&oid) {
                 m.to_owned()
-            } else if let Ok(commit) = seed::get_commit(seed.clone(), urn, &oid) {
+            } else if let Ok(commit) = client.commit(urn, &oid) {
                 commits.insert(oid, commit.header.summary.clone());
                 commit.header.summary
             } else {
@@ -9,6 +9,8 @@ use librad::PeerId;
 use radicle_common::args::{Args, Error};
 use radicle_common::seed;
 
+use crate::graph::Format;
+
 /// Tool options.
 #[derive(Debug)]
 pub struct Options {
@@ -19,6 +21,8 @@ pub struct Options {
     pub local: bool,
     pub seed: Option<seed::Address>,
     pub verbose: bool,
+    pub format: Format,
+    pub policy: bool,
 }
 
 impl Args for Options {
@@ -33,6 +37,8 @@ impl Args for Options {
         let mut fetch = true;
         let mut verbose = false;
         let mut seed = None;
+        let mut format = Format::default();
+        let mut policy = false;
 
         while let Some(arg) = parser.next()? {
             match arg {
@@ -53,11 +59,18 @@ impl Args for Options {
                 }
                 Long("sync") => sync = true,
                 Long("local") => local = Some(true),
+                Long("policy") => policy = true,
                 Long("remote") => local = Some(false),
                 Long("no-upstream") => upstream = false,
                 Long("no-sync") => sync = false,
                 Long("no-fetch") => fetch = false,
 
+                Long("format") => {
+                    let value = parser.value()?;
+                    let value = value.to_string_lossy();
+
+                    format = value.parse().map_err(|e: String| anyhow!(e))?;
+                }
                 Long("verbose") | Short('v') => verbose = true,
                 Long("help") => {
                     return Err(Error::Help.into());
@@ -85,6 +98,13 @@ impl Args for Options {
             seed.is_none()
         };
 
+        if policy && peer.is_some() {
+            anyhow::bail!("`--policy` can't be used when tracking a peer");
+        }
+        if peer.is_some() && format != Format::Tree {
+            anyhow::bail!("`--format` can only be used when showing the tracking graph");
+        }
+
         Ok((
             Options {
                 peer,
@@ -94,6 +114,8 @@ impl Args for Options {
                 local,
                 seed,
                 verbose,
+                format,
+                policy,
             },
             vec![],
         ))
@@ -10,8 +10,11 @@ use librad::git::Urn;
 use librad::PeerId;
 
 use radicle_common::args::{Args, Error, Help};
+use radicle_common::config::{self, Config, Scope};
+use radicle_common::petname::Petnames;
 use radicle_common::profile::Profile;
-use radicle_common::{fmt, git, keys, project};
+use radicle_common::tracking as policy;
+use radicle_common::{git, keys, project};
 use radicle_terminal as term;
 
 pub const HELP: Help = Help {
@@ -21,12 +24,17 @@ pub const HELP: Help = Help {
     usage: r#"
 Usage
 
-    rad untrack [<peer-id>] [--all]
+    rad untrack [<peer-id>] [--block] [--all]
 
     Must be run within a project working copy.
 
+    Peers that the project's tracking policy tracks automatically, eg. delegates,
+    are tracked again on the next sync. To prevent this, use `--block`, which adds
+    the peer to the policy's blocklist in `Radicle.toml`. See `rad track --policy`.
+
 Options
 
+    --block             Block the peer, so that it isn't tracked again
     --help              Print help
 "#,
 };
@@ -35,6 +43,7 @@ Options
 #[derive(Debug)]
 pub struct Options {
     pub peer: Option<String>,
+    pub block: bool,
 }
 
 impl Args for Options {
@@ -44,12 +53,16 @@ impl Args for Options {
         let mut parser = lexopt::Parser::from_args(args);
         let mut peer: Option<String> = None;
         let mut all = false;
+        let mut block = false;
 
         while let Some(arg) = parser.next()? {
             match arg {
                 Long("all") if peer.is_none() => {
                     all = true;
                 }
+                Long("block") => {
+                    block = true;
+                }
                 Value(val) if peer.is_none() => {
                     let val = val.to_string_lossy();
                     peer = Some(val.to_string());
@@ -66,8 +79,11 @@ impl Args for Options {
         if peer.is_none() && !all {
             return Err(Error::Usage.into());
         }
+        if block && peer.is_none() {
+            anyhow::bail!("`--block` can only be used with a <peer-id>");
+        }
 
-        Ok((Options { peer }, vec![]))
+        Ok((Options { peer, block }, vec![]))
     }
 }
 
@@ -82,10 +98,11 @@ pub fn run(options: Options, ctx: impl term::Context) -> anyhow::Result<()> {
 fn get_peer_id(
     project: &project::Metadata,
     storage: &Storage,
+    petnames: &Petnames,
     name: &String,
 ) -> anyhow::Result<Option<PeerId>> {
     for (id, peer) in project::tracked(project, storage)? {
-        if peer.name() == *name {
+        if peer.name(petnames) == *name {
             return Ok(Some(id));
         }
     }
@@ -101,6 +118,7 @@ pub fn execute(
 ) -> anyhow::Result<()> {
     let signer = term::signer(profile)?;
     let storage = keys::storage(profile, signer)?;
+    let petnames = Petnames::open(profile)?;
 
     if let Some(peer_str) = options.peer {
         let peer = if let Ok(val) = PeerId::from_str(&peer_str) {
@@ -109,19 +127,25 @@ pub fn execute(
             let project = project::get(&storage, urn)?
                 .ok_or_else(|| anyhow!("project {} not found in local storage", &urn))?;
 
-            if let Some(v) = get_peer_id(&project, &storage, &peer_str)? {
+            if let Some(v) = get_peer_id(&project, &storage, &petnames, &peer_str)? {
                 v
             } else {
                 anyhow::bail!("invalid <peer-id> '{}'", peer_str)
             }
         };
 
+        // A peer can be blocked whether or not it's tracked.
+        let untrack = if options.block {
+            tracking::policy::Untrack::Any
+        } else {
+            tracking::policy::Untrack::MustExist
+        };
         tracking::untrack(
             &storage,
             urn,
             peer,
             tracking::UntrackArgs {
-                policy: tracking::policy::Untrack::MustExist,
+                policy: untrack,
                 prune: true,
             },
         )??;
@@ -132,9 +156,31 @@ pub fn execute(
 
         term::success!(
             "Tracking relationship {} removed for {}",
-            term::format::dim(fmt::peer(&peer)),
+            term::format::dim(petnames.peer(&peer)),
             term::format::highlight(urn)
         );
+
+        if options.block {
+            let mut config = Config::scope(Scope::Local, profile)?;
+
+            if config.tracking.block(peer) {
+                policy::write_blocklist(&Scope::Local.path(profile), &config.tracking.block)?;
+            }
+            term::success!(
+                "Peer {} blocked in {}",
+                term::format::dim(petnames.peer(&peer)),
+                term::format::highlight(config::FILE_NAME_LOCAL)
+            );
+        } else if repo.is_some() {
+            if let Some(reason) = retracked(urn, &peer, &storage, profile)? {
+                term::warning(&format!(
+                    "{} is a {} and will be tracked again on the next sync, \
+                     use `--block` to prevent this",
+                    petnames.peer(&peer),
+                    reason
+                ));
+            }
+        }
     } else {
         let all_untracked = tracking::untrack_all(
             &storage,
@@ -155,7 +201,42 @@ pub fn execute(
             "Tracking relationships for {} removed",
             term::format::highlight(urn)
         );
+
+        if repo.is_some() && !policy::Policy::local()?.is_default() {
+            term::warning(
+                "peers tracked by the project's tracking policy will be tracked again on the next sync",
+            );
+        }
     }
 
     Ok(())
 }
+
+/// Check whether the working copy's tracking policy tracks the given peer
+/// automatically, and if so, why.
+fn retracked(
+    urn: &Urn,
+    peer: &PeerId,
+    storage: &Storage,
+    profile: &Profile,
+) -> anyhow::Result<Option<policy::Reason>> {
+    let policy = policy::Policy::local()?;
+    if policy.is_default() {
+        return Ok(None);
+    }
+    let project = match project::get(storage, urn)? {
+        Some(project) => project,
+        None => return Ok(None),
+    };
+    let contributors = if policy.contributors {
+        policy::contributors(&project, storage, profile)?
+    } else {
+        Vec::new()
+    };
+
+    Ok(policy
+        .candidates(policy::delegates(&project), contributors)
+        .into_iter()
+        .find(|(p, _)| p == peer)
+        .map(|(_, reason)| reason))
+}